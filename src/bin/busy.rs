@@ -16,10 +16,14 @@ use std::{
 };
 
 use busy::{
+  clock::Clock,
   duration::{get_midnight_datetime, get_period_since_now, get_week_start_datetime, Period},
-  Busy,
+  project::ProjectChanges,
+  short_id::IdKind,
+  Busy, Config, Error, NewNames, SwitchMode, Switched,
 };
 
+use busy::sync::{merge_file, CONFLICT_REPORT_PREFIX};
 use busy::task::Task;
 use busy::task::TaskView;
 use busy::time::parse_datetime;
@@ -29,6 +33,9 @@ use colored::Colorize;
 use log::debug;
 use view::viewer::Viewer;
 
+const TASK_REFERENCE_HELP: &str =
+  "short task id, @active, @last, @-N (Nth task from the end) or @today:N (Nth task of today)";
+
 fn build_cli() -> Command<'static> {
   let command = Command::new("busy")
     .about("Simple CLI time tracker")
@@ -46,34 +53,43 @@ fn build_cli() -> Command<'static> {
           .long("start-time")
           .required(true)
           .takes_value(true)
-          .help("task start-time, format: HH:MM or YYYY-mm-dd HH:MM"),
+          .allow_hyphen_values(true)
+          .help("task start-time, e.g.: 11:05, 2024-03-14 11:05, -25m, 2h ago, yesterday 17:30, mon 9:00, noon or ISO 8601"),
         Arg::new("finish-time")
           .long("finish-time")
           .required(true)
           .takes_value(true)
-          .help("task finish-time, format: HH:MM or YYYY-mm-dd HH:MM"),
+          .allow_hyphen_values(true)
+          .help("task finish-time, same formats as start-time"),
+        new_project_arg(),
+        new_tag_arg(),
       ]),
     )
     .subcommand(
-      Command::new("start").about("start new task").args(&[
-        Arg::new("project_name").required(true).index(1),
-        Arg::new("task_title").required(true).index(2),
-        Arg::new("tags")
-          .help("should be prefixed with `+` like: +my-tag1 +mytag2")
-          .index(3)
-          .multiple_values(true),
-        Arg::new("start-time")
-          .long("start-time")
-          .takes_value(true)
-          .help("override start-time, format: HH:MM"),
-      ]),
+      Command::new("start")
+        .about("start new task")
+        .args(start_args())
+        .arg(
+          Arg::new("switch")
+            .long("switch")
+            .help("stop the active task and start the new one at the same time"),
+        ),
+    )
+    .subcommand(
+      Command::new("switch")
+        .about("stop the active task and start a new one, shortcut for `start --switch`")
+        .args(start_args()),
     )
     .subcommand(
       Command::new("status")
         .alias("st")
         .about("show active task if exists"),
     )
-    .subcommand(Command::new("stop").about("stop current task"))
+    .subcommand(
+      Command::new("stop")
+        .about("stop current task")
+        .arg(at_arg("stop time, e.g.: 12:10, -15m or 1h ago")),
+    )
     .subcommand(
       Command::new("sync")
         .about("sync tasks. please set $BUSY_REMOTE env")
@@ -82,8 +98,16 @@ fn build_cli() -> Command<'static> {
           Arg::new("pull-force").long("pull-force"),
         ]),
     )
-    .subcommand(Command::new("pause").about("pause the current task"))
-    .subcommand(Command::new("resume").about("resume the current task"))
+    .subcommand(
+      Command::new("pause")
+        .about("pause the current task")
+        .arg(at_arg("pause time, e.g.: 12:10, -15m or 1h ago")),
+    )
+    .subcommand(
+      Command::new("resume")
+        .about("resume the current task")
+        .arg(at_arg("resume time, e.g.: 13:00, -5m or 10m ago")),
+    )
     .subcommand(
       Command::new("today")
         .alias("td")
@@ -137,21 +161,192 @@ fn build_cli() -> Command<'static> {
     .subcommand(
       Command::new("continue")
         .about("continue specific task (clone and start from now again")
-        .args(&[Arg::new("short-task-id").index(1)]),
+        .args(&[
+          Arg::new("short-task-id").index(1).help(TASK_REFERENCE_HELP),
+          Arg::new("switch")
+            .long("switch")
+            .help("stop the active task and continue the task at the same time"),
+          pause_arg(),
+        ]),
     )
     .subcommand(
       Command::new("rm")
-        .about("remove specific task")
-        .args(&[Arg::new("short-task-id").index(1)]),
+        .about("move specific task to the trash")
+        .args(&[Arg::new("short-task-id").index(1).help(TASK_REFERENCE_HELP)]),
+    )
+    .subcommand(
+      Command::new("trash")
+        .about("manage removed tasks, projects and tags")
+        .subcommand_required(true)
+        .subcommand(Command::new("list").about("print trashed items"))
+        .subcommand(
+          Command::new("restore")
+            .about("restore specific item from the trash")
+            .args(&[Arg::new("short-id").required(true).index(1)]),
+        )
+        .subcommand(
+          Command::new("purge")
+            .about("remove trashed items for good")
+            .args(&[Arg::new("older-than")
+              .long("older-than")
+              .takes_value(true)
+              .default_value("30")
+              .help("purge items trashed more than this number of days ago")]),
+        ),
+    )
+    .subcommand(
+      Command::new("undo")
+        .about("undo the last operations")
+        .args(&[Arg::new("steps").index(1).default_value("1")]),
+    )
+    .subcommand(
+      Command::new("redo")
+        .about("redo the last undone operations")
+        .args(&[Arg::new("steps").index(1).default_value("1")]),
+    )
+    .subcommand(
+      Command::new("check")
+        .about("look for overlapping intervals and other broken tasks")
+        .args(&[
+          Arg::new("fix")
+            .long("fix")
+            .help("propose trims and merges which repair found issues"),
+          Arg::new("min-gap")
+            .long("min-gap")
+            .takes_value(true)
+            .help("also report untracked gaps within a day longer than this number of minutes"),
+        ]),
+    )
+    .subcommand(
+      Command::new("doctor")
+        .about("look for dangling references, duplicate ids and names in the storage")
+        .args(&[Arg::new("fix")
+          .long("fix")
+          .help("repair found problems by re-linking, deduplicating and dropping orphans")]),
+    )
+    .subcommand(
+      Command::new("merge-driver")
+        .about("git merge driver for busy storage files")
+        .hide(true)
+        .args(&[
+          Arg::new("base").required(true).index(1),
+          Arg::new("ours").required(true).index(2),
+          Arg::new("theirs").required(true).index(3),
+          Arg::new("path").required(true).index(4),
+        ]),
+    )
+    .subcommand(
+      Command::new("projects")
+        .about("print all projects")
+        .args(&[Arg::new("all")
+          .long("all")
+          .short('a')
+          .help("print archived projects as well")]),
+    )
+    .subcommand(
+      Command::new("project")
+        .about("manage projects")
+        .subcommand_required(true)
+        .subcommand(
+          Command::new("set")
+            .about("set description, client or color of a project, an empty value clears it")
+            .args(&[
+              Arg::new("project_name").required(true).index(1),
+              Arg::new("description").long("description").takes_value(true),
+              Arg::new("client").long("client").takes_value(true),
+              Arg::new("color")
+                .long("color")
+                .takes_value(true)
+                .help("e.g. red, blue or bright green"),
+            ]),
+        )
+        .subcommand(
+          Command::new("archive")
+            .about("hide a project from listings, its tasks are kept")
+            .args(&[Arg::new("project_name").required(true).index(1)]),
+        )
+        .subcommand(
+          Command::new("unarchive")
+            .about("bring an archived project back to listings")
+            .args(&[Arg::new("project_name").required(true).index(1)]),
+        )
+        .subcommand(
+          Command::new("rename")
+            .about("rename a project and its sub-projects")
+            .args(&[
+              Arg::new("project_name").required(true).index(1),
+              Arg::new("new-name").required(true).index(2),
+            ]),
+        )
+        .subcommand(
+          Command::new("merge")
+            .about("move every task of a project to another one and remove it")
+            .args(&[
+              Arg::new("project_name").required(true).index(1),
+              Arg::new("into").required(true).index(2),
+            ]),
+        )
+        .subcommand(
+          Command::new("rm")
+            .about("move a project to the trash")
+            .args(&[
+              Arg::new("project_name").required(true).index(1),
+              Arg::new("into")
+                .long("into")
+                .takes_value(true)
+                .help("project to move the tasks of the removed one to"),
+            ]),
+        ),
     )
-    .subcommand(Command::new("projects").about("print all projects"))
     .subcommand(Command::new("tags").about("print all tags"))
+    .subcommand(
+      Command::new("tag")
+        .about("manage tags, names can be given with or without `+`")
+        .subcommand_required(true)
+        .subcommand(
+          Command::new("rename")
+            .about("rename a tag")
+            .args(&[
+              Arg::new("tag").required(true).index(1),
+              Arg::new("new-name").required(true).index(2),
+            ]),
+        )
+        .subcommand(
+          Command::new("merge")
+            .about("replace a tag with another one in every task and remove it")
+            .args(&[
+              Arg::new("tag").required(true).index(1),
+              Arg::new("into").required(true).index(2),
+            ]),
+        )
+        .subcommand(
+          Command::new("rm")
+            .about("move a tag to the trash")
+            .args(&[
+              Arg::new("tag").required(true).index(1),
+              Arg::new("cascade")
+                .long("cascade")
+                .help("remove the tag from tasks which still use it"),
+            ]),
+        )
+        .subcommand(
+          Command::new("color")
+            .about("set the color of a tag, without a color it's cleared")
+            .args(&[
+              Arg::new("tag").required(true).index(1),
+              Arg::new("color")
+                .index(2)
+                .help("e.g. red, blue or bright green"),
+            ]),
+        ),
+    )
     .subcommand(
       Command::new("edit").args(&[
         Arg::new("all").long("all").short('a'),
         Arg::new("all-tags").long("all-tags"),
         Arg::new("task-id")
           .long("task")
+          .help(TASK_REFERENCE_HELP)
           .multiple_occurrences(true)
           .takes_value(true),
         Arg::new("project-id")
@@ -170,16 +365,91 @@ fn build_cli() -> Command<'static> {
 fn main() {
   env_logger::init();
 
-  let busy = Rc::new(RefCell::new(Busy::new()));
   let cmd = build_cli();
   let matches = cmd.get_matches();
+
+  // git runs the driver in the middle of a merge, it must not touch the storage
+  if let Some(driver_matches) = matches.subcommand_matches("merge-driver") {
+    std::process::exit(run_merge_driver(driver_matches));
+  }
+
+  let busy = match Busy::new() {
+    Ok(busy) => Rc::new(RefCell::new(busy)),
+    Err(err) => {
+      eprintln!("can't start busy: {}", err);
+      std::process::exit(1);
+    }
+  };
   let viewer = Viewer::new(Rc::clone(&busy));
+  let clock = busy.borrow().clock();
 
   match matches.subcommand_name() {
     Some("projects") => {
       clear_screen();
       println!("{}", "Projects: ".bright_cyan());
-      viewer.print_projects();
+      let subcommand_matches = matches.subcommand_matches("projects").unwrap();
+      viewer.print_projects(subcommand_matches.is_present("all"));
+    }
+
+    Some("project") => {
+      let subcommand_matches = matches.subcommand_matches("project").unwrap();
+      let result = match subcommand_matches.subcommand() {
+        Some(("set", set_matches)) => {
+          let value = |name: &str| set_matches.value_of(name).map(str::to_owned);
+          busy.borrow_mut().update_project(
+            set_matches.value_of("project_name").unwrap(),
+            ProjectChanges {
+              description: value("description"),
+              client: value("client"),
+              color: value("color"),
+            },
+          )
+        }
+        Some((cmd @ ("archive" | "unarchive"), archive_matches)) => {
+          busy.borrow_mut().archive_project(
+            archive_matches.value_of("project_name").unwrap(),
+            cmd == "archive",
+          )
+        }
+        Some(("rename", rename_matches)) => busy.borrow_mut().rename_project(
+          rename_matches.value_of("project_name").unwrap(),
+          rename_matches.value_of("new-name").unwrap(),
+        ),
+        Some((cmd @ ("merge" | "rm"), merge_matches)) => {
+          let project_name = merge_matches.value_of("project_name").unwrap();
+          let project = busy.borrow().project_by_name(project_name);
+          let result = match merge_matches.value_of("into") {
+            Some(into) => busy
+              .borrow_mut()
+              .merge_projects(project_name, into)
+              .map(|moved| format!("{} tasks moved to {}", moved, into)),
+            None => match project {
+              Some(project) => busy
+                .borrow_mut()
+                .remove_project(project.id())
+                .map(|_| "no tasks to move".to_string()),
+              None => Err(Error::UnknownProject(project_name.to_owned())),
+            },
+          };
+          match result {
+            Ok(msg) => println!("Project {} moved to the trash, {}", project_name, msg),
+            Err(Error::InUse(msg)) => {
+              println!("couldn't {} project: {}", cmd, msg);
+              println!("use --into <project> to move the tasks to another project");
+            }
+            Err(err) => println!("couldn't {} project: {}", cmd, err),
+          };
+          return;
+        }
+        _ => unreachable!("project subcommand is required"),
+      };
+      match result {
+        Ok(project) => {
+          println!("Updated project: ");
+          viewer.print_project(&project);
+        }
+        Err(err) => println!("couldn't update project: {}", err),
+      };
     }
 
     Some("tags") => {
@@ -188,6 +458,61 @@ fn main() {
       viewer.print_tags();
     }
 
+    Some("tag") => {
+      let subcommand_matches = matches.subcommand_matches("tag").unwrap();
+      let (cmd, tag_matches) = subcommand_matches.subcommand().unwrap();
+      let tag_name = |name: &str| {
+        let value = tag_matches.value_of(name).unwrap();
+        value.strip_prefix('+').unwrap_or(value).to_string()
+      };
+      let tag = tag_name("tag");
+      let result = match cmd {
+        "rename" => busy
+          .borrow_mut()
+          .rename_tag(&tag, &tag_name("new-name"))
+          .map(|tag| format!("Tag renamed to {}", tag.name())),
+        "merge" => busy
+          .borrow_mut()
+          .merge_tags(&tag, &tag_name("into"))
+          .map(|changed| format!("Tag {} merged, {} tasks changed", tag, changed)),
+        "rm" => {
+          let found = busy.borrow().find_tag_by_names(std::slice::from_ref(&tag));
+          match found.first() {
+            Some(found) if tag_matches.is_present("cascade") => busy
+              .borrow_mut()
+              .remove_tag_cascade(found.id())
+              .map(|changed| {
+                format!(
+                  "Tag {} removed from {} tasks and moved to the trash",
+                  tag, changed
+                )
+              }),
+            Some(found) => busy
+              .borrow_mut()
+              .remove_tag(found.id())
+              .map(|_| format!("Tag {} moved to the trash", tag)),
+            None => Err(Error::UnknownTag(tag.clone())),
+          }
+        }
+        "color" => busy
+          .borrow_mut()
+          .set_tag_color(&tag, tag_matches.value_of("color").map(str::to_owned))
+          .map(|tag| match tag.color() {
+            Some(color) => format!("Tag {} is {} now", tag.name(), color),
+            None => format!("Tag {} has no color now", tag.name()),
+          }),
+        _ => unreachable!("tag subcommand is required"),
+      };
+      match result {
+        Ok(msg) => println!("{}", msg),
+        Err(Error::InUse(msg)) => {
+          println!("couldn't remove tag: {}", msg);
+          println!("use --cascade to remove it from the tasks as well");
+        }
+        Err(err) => println!("couldn't {} tag: {}", cmd, err),
+      };
+    }
+
     Some("status") => {
       match busy.borrow().active_task() {
         Some(task) => {
@@ -206,15 +531,17 @@ fn main() {
       let task_title = command_matches.value_of("task_title").unwrap();
       let tags = extract_tags("tags", command_matches);
 
-      let start_time = parse_datetime(command_matches.value_of("start-time").unwrap());
-      let finish_time = parse_datetime(command_matches.value_of("finish-time").unwrap());
+      let start_time = parse_datetime(
+        command_matches.value_of("start-time").unwrap(),
+        clock.as_ref(),
+      );
+      let finish_time = parse_datetime(
+        command_matches.value_of("finish-time").unwrap(),
+        clock.as_ref(),
+      );
 
-      if start_time.is_err() || finish_time.is_err() {
-        println!(
-          "failed to parse start or finish time: {:?} {:?}",
-          start_time.err(),
-          finish_time.err()
-        );
+      if let Err(err) = start_time.as_ref().and(finish_time.as_ref()) {
+        println!("failed to parse start or finish time: {}", err);
         return;
       }
 
@@ -225,6 +552,7 @@ fn main() {
           tags,
           start_time.unwrap(),
           finish_time.unwrap(),
+          new_names(command_matches),
         )
       };
       match started_task_result {
@@ -232,41 +560,72 @@ fn main() {
           println!("Task added: ");
           viewer.log_task(&task, true);
         }
-        Err(err) => println!("add task err: {}", err),
+        Err(err) => {
+          println!("add task err: {}", err);
+          print_new_name_hint(&err);
+        }
       };
     }
 
-    Some("start") => {
-      let command_matches = matches.subcommand_matches("start").unwrap();
+    Some(subcommand @ ("start" | "switch")) => {
+      let command_matches = matches.subcommand_matches(subcommand).unwrap();
       let project_name = command_matches.value_of("project_name").unwrap();
       let task_title = command_matches.value_of("task_title").unwrap();
       let tags = extract_tags("tags", command_matches);
-      let start_time_str = command_matches.value_of("start-time");
       let mut start_time = None;
-      if start_time_str.is_some() {
-        let parsed_start_time = parse_datetime(start_time_str.unwrap());
-        if parsed_start_time.is_err() {
-          println!(
-            "Can't parse start-time parameter: {}, err: {:?}",
-            start_time_str.unwrap(),
-            parsed_start_time.err()
-          );
+      if let Some(start_time_str) = command_matches.value_of("start-time") {
+        let parsed_start_time = parse_datetime(start_time_str, clock.as_ref());
+        if let Err(err) = parsed_start_time.as_ref() {
+          println!("Can't parse start-time parameter: {}", err);
           return;
         }
         start_time = Some(parsed_start_time.unwrap());
       }
 
+      let switch_mode = get_switch_mode(
+        command_matches,
+        subcommand == "switch",
+        busy.borrow().config(),
+      );
+      if let Some(mode) = switch_mode {
+        let switched = {
+          busy.borrow_mut().switch(
+            project_name,
+            task_title,
+            tags,
+            start_time,
+            mode,
+            new_names(command_matches),
+          )
+        };
+        match switched {
+          Ok(switched) => print_switched(&viewer, &switched),
+          Err(err) => {
+            println!("switch task err: {}", err);
+            print_new_name_hint(&err);
+          }
+        };
+        return;
+      }
+
       let started_task_result = {
-        busy
-          .borrow_mut()
-          .start(project_name, task_title, tags, start_time)
+        busy.borrow_mut().start(
+          project_name,
+          task_title,
+          tags,
+          start_time,
+          new_names(command_matches),
+        )
       };
       match started_task_result {
         Ok(task) => {
           println!("Task started: ");
           viewer.log_task(&task, true);
         }
-        Err(err) => println!("start task err: {}", err),
+        Err(err) => {
+          println!("start task err: {}", err);
+          print_new_name_hint(&err);
+        }
       };
     }
 
@@ -291,7 +650,13 @@ fn main() {
         println!("Start syncing…");
         let sync_result = busy.borrow_mut().sync();
         match sync_result {
-          Ok(_) => {
+          Ok(conflicts) => {
+            if !conflicts.is_empty() {
+              println!("Records changed on both sides were merged automatically:");
+              for conflict in conflicts.iter() {
+                println!("  {}", conflict);
+              }
+            }
             println!("Syncing finished");
           }
           Err(err) => {
@@ -303,7 +668,14 @@ fn main() {
     }
 
     Some("stop") => {
-      let stopped_task_result = { busy.borrow_mut().stop() };
+      let at = match parse_at(matches.subcommand_matches("stop").unwrap(), clock.as_ref()) {
+        Ok(at) => at,
+        Err(err) => {
+          println!("couldn't stop: {}", err);
+          return;
+        }
+      };
+      let stopped_task_result = { busy.borrow_mut().stop(at) };
       match stopped_task_result {
         Ok(task) => {
           println!("Task stopped:");
@@ -314,7 +686,14 @@ fn main() {
     }
 
     Some("pause") => {
-      let paused_task_result = { busy.borrow_mut().pause() };
+      let at = match parse_at(matches.subcommand_matches("pause").unwrap(), clock.as_ref()) {
+        Ok(at) => at,
+        Err(err) => {
+          println!("couldn't pause: {}", err);
+          return;
+        }
+      };
+      let paused_task_result = { busy.borrow_mut().pause(at) };
       match paused_task_result {
         Ok(task) => {
           println!("Task paused:");
@@ -325,7 +704,17 @@ fn main() {
     }
 
     Some("resume") => {
-      let unpaused_task_result = { busy.borrow_mut().resume() };
+      let at = match parse_at(
+        matches.subcommand_matches("resume").unwrap(),
+        clock.as_ref(),
+      ) {
+        Ok(at) => at,
+        Err(err) => {
+          println!("couldn't resume: {}", err);
+          return;
+        }
+      };
+      let unpaused_task_result = { busy.borrow_mut().resume(at) };
       match unpaused_task_result {
         Ok(task) => {
           println!("Task resumed:");
@@ -341,7 +730,7 @@ fn main() {
         subcommand_matches,
         Rc::clone(&busy),
         &viewer,
-        get_period(subcommand_matches),
+        get_period(subcommand_matches, clock.as_ref()),
       );
     }
 
@@ -350,7 +739,7 @@ fn main() {
         matches.subcommand_matches("today").unwrap(),
         Rc::clone(&busy),
         &viewer,
-        Period::new_to_now(get_midnight_datetime()),
+        Period::new_to_now(get_midnight_datetime(clock.as_ref()), clock.as_ref()),
       );
     }
 
@@ -367,7 +756,7 @@ fn main() {
       let found_tags = busy.borrow().find_tag_by_names(&tags);
 
       viewer.show_stat(
-        get_period(subcommand_matches),
+        get_period(subcommand_matches, clock.as_ref()),
         project_ids,
         &found_tags,
         with_tags,
@@ -377,12 +766,19 @@ fn main() {
     Some("continue") => {
       let subcommand_matches = matches.subcommand_matches("continue").unwrap();
       let short_task_id = subcommand_matches.value_of("short-task-id").unwrap();
-      let task_id = restore_id_by_short_id(Rc::clone(&busy), short_task_id);
-      if task_id.is_err() {
-        println!(
-          "Continue parse short id into uuid error: {:?}",
-          task_id.err()
-        );
+      let task_id = restore_id_by_short_id(Rc::clone(&busy), IdKind::Task, short_task_id);
+      if let Err(err) = task_id.as_ref() {
+        println!("Continue parse short id into uuid error: {}", err);
+        return;
+      }
+
+      let switch_mode = get_switch_mode(subcommand_matches, false, busy.borrow().config());
+      if let Some(mode) = switch_mode {
+        let switched = { busy.borrow_mut().switch_to_task(task_id.unwrap(), mode) };
+        match switched {
+          Ok(switched) => print_switched(&viewer, &switched),
+          Err(err) => println!("Continue task error: {}", err),
+        };
         return;
       }
 
@@ -399,9 +795,9 @@ fn main() {
     Some("rm") => {
       let subcommand_matches = matches.subcommand_matches("rm").unwrap();
       let short_task_id = subcommand_matches.value_of("short-task-id").unwrap();
-      let task_id = restore_id_by_short_id(Rc::clone(&busy), short_task_id);
-      if task_id.is_err() {
-        println!("Parse short id into uuid error: {:?}", task_id.err());
+      let task_id = restore_id_by_short_id(Rc::clone(&busy), IdKind::Task, short_task_id);
+      if let Err(err) = task_id.as_ref() {
+        println!("Parse short id into uuid error: {}", err);
         return;
       }
 
@@ -411,10 +807,179 @@ fn main() {
         task = p.task_by_id(task_id.unwrap()).unwrap();
         p.remove_task(task.id()).unwrap();
       };
-      println!("Removed task:");
+      println!("Task moved to the trash:");
       viewer.log_task(&task, true);
     }
 
+    Some("trash") => {
+      let subcommand_matches = matches.subcommand_matches("trash").unwrap();
+      match subcommand_matches.subcommand() {
+        Some(("list", _)) => {
+          let trash = busy.borrow().trash();
+          if trash.is_empty() {
+            println!("Trash is empty");
+            return;
+          }
+          viewer.print_trash(&trash);
+        }
+
+        Some(("restore", restore_matches)) => {
+          let short_id = restore_matches.value_of("short-id").unwrap();
+          let resolved = busy.borrow().resolve_trash_id(short_id);
+          let id = match resolved {
+            Ok(id) => id,
+            Err(err) => {
+              println!("couldn't restore: {}", err);
+              return;
+            }
+          };
+          let result = busy.borrow_mut().restore(id);
+          match result {
+            Ok(_) => println!("Restored: {}", short_id),
+            Err(err) => println!("couldn't restore: {}", err),
+          };
+        }
+
+        Some(("purge", purge_matches)) => {
+          let days: i64 = match purge_matches.value_of_t("older-than") {
+            Ok(days) => days,
+            Err(err) => {
+              println!("can't parse older-than: {}", err);
+              return;
+            }
+          };
+          let result = busy.borrow_mut().purge_trash(chrono::Duration::days(days));
+          match result {
+            Ok(purged) => println!("Purged {} items from trash", purged.len()),
+            Err(err) => println!("couldn't purge trash: {}", err),
+          };
+        }
+
+        _ => unreachable!("trash subcommand is required"),
+      }
+    }
+
+    Some("check") => {
+      let subcommand_matches = matches.subcommand_matches("check").unwrap();
+      let min_gap = match subcommand_matches.is_present("min-gap") {
+        true => match subcommand_matches.value_of_t::<i64>("min-gap") {
+          Ok(min_gap) => Some(chrono::Duration::minutes(min_gap)),
+          Err(err) => {
+            println!("can't parse min-gap: {}", err);
+            return;
+          }
+        },
+        false => None,
+      };
+      let issues = busy.borrow().check(min_gap);
+      if issues.is_empty() {
+        println!("No issues found");
+        return;
+      }
+      for issue in issues.iter() {
+        let mut line = issue.to_string();
+        for id in issue.task_ids() {
+          line = line.replace(&id.to_string(), &busy.borrow().shorten_id(id));
+        }
+        println!("{}", line);
+      }
+      if !subcommand_matches.is_present("fix") {
+        return;
+      }
+
+      let fixes = busy.borrow().propose_fixes();
+      if fixes.is_empty() {
+        println!("Nothing to fix automatically");
+        return;
+      }
+      println!();
+      for fix in fixes.iter() {
+        println!(
+          "{} {}: {}",
+          busy.borrow().shorten_id(fix.task.id()),
+          fix.task.title(),
+          fix.description
+        );
+      }
+      print!("Apply {} fixes? [y/N] ", fixes.len());
+      std::io::stdout().flush().unwrap();
+      let mut answer = String::new();
+      if std::io::stdin().read_line(&mut answer).is_err()
+        || !answer.trim().eq_ignore_ascii_case("y")
+      {
+        println!("Nothing changed");
+        return;
+      }
+      let result = busy.borrow_mut().apply_fixes(&fixes);
+      match result {
+        Ok(_) => println!("Applied {} fixes", fixes.len()),
+        Err(err) => println!("couldn't apply fixes: {}", err),
+      };
+    }
+
+    Some("doctor") => {
+      let subcommand_matches = matches.subcommand_matches("doctor").unwrap();
+      let problems = busy.borrow().diagnose();
+      if problems.is_empty() {
+        println!("No problems found");
+        return;
+      }
+      for problem in problems.iter() {
+        let mut line = problem.to_string();
+        for id in problem.ids() {
+          line = line.replace(&id.to_string(), &busy.borrow().shorten_id(id));
+        }
+        println!("{}", line);
+      }
+      if !subcommand_matches.is_present("fix") {
+        return;
+      }
+
+      print!("Repair {} problems? [y/N] ", problems.len());
+      std::io::stdout().flush().unwrap();
+      let mut answer = String::new();
+      if std::io::stdin().read_line(&mut answer).is_err()
+        || !answer.trim().eq_ignore_ascii_case("y")
+      {
+        println!("Nothing changed");
+        return;
+      }
+      let result = busy.borrow_mut().repair();
+      match result {
+        Ok(repaired) => {
+          for description in repaired.iter() {
+            println!("{}", description);
+          }
+        }
+        Err(err) => println!("couldn't repair: {}", err),
+      };
+    }
+
+    Some(cmd @ ("undo" | "redo")) => {
+      let subcommand_matches = matches.subcommand_matches(cmd).unwrap();
+      let steps: usize = match subcommand_matches.value_of_t("steps") {
+        Ok(steps) => steps,
+        Err(err) => {
+          println!("can't parse steps: {}", err);
+          return;
+        }
+      };
+
+      for _ in 0..steps {
+        let result = match cmd {
+          "undo" => busy.borrow_mut().undo(),
+          _ => busy.borrow_mut().redo(),
+        };
+        match result {
+          Ok(entry) => println!("{}: {}", cmd, entry.description),
+          Err(err) => {
+            println!("couldn't {}: {}", cmd, err);
+            return;
+          }
+        };
+      }
+    }
+
     Some("edit") => {
       let subcommand_matches = matches.subcommand_matches("edit").unwrap();
       if subcommand_matches.is_present("all-tags") {
@@ -437,21 +1002,19 @@ fn main() {
         return;
       }
 
-      let extract_ids_and_edit = |name: &str, edit_type: EditDataType| {
+      let extract_ids_and_edit = |name: &str, kind: IdKind, edit_type: EditDataType| {
         let short_item_ids: Vec<String> = subcommand_matches.values_of_t(name).unwrap_or_default();
-        let ids: Vec<uuid::Uuid> = short_item_ids
-          .iter()
-          .map(|short_id| restore_id_by_short_id(Rc::clone(&busy), short_id).unwrap())
-          .collect();
-
-        for id in ids {
-          edit(Rc::clone(&busy), &viewer, edit_type, id);
+        for short_id in short_item_ids.iter() {
+          match restore_id_by_short_id(Rc::clone(&busy), kind, short_id) {
+            Ok(id) => edit(Rc::clone(&busy), &viewer, edit_type, id),
+            Err(err) => println!("couldn't edit {}: {}", short_id, err),
+          };
         }
       };
 
-      extract_ids_and_edit("task-id", EditDataType::Task);
-      extract_ids_and_edit("project-id", EditDataType::Project);
-      extract_ids_and_edit("tag-id", EditDataType::Tag);
+      extract_ids_and_edit("task-id", IdKind::Task, EditDataType::Task);
+      extract_ids_and_edit("project-id", IdKind::Project, EditDataType::Project);
+      extract_ids_and_edit("tag-id", IdKind::Tag, EditDataType::Tag);
 
       println!("\nEdit completed");
     }
@@ -462,6 +1025,37 @@ fn main() {
   };
 }
 
+fn run_merge_driver(matches: &ArgMatches) -> i32 {
+  let read = |name: &str| std::fs::read_to_string(matches.value_of(name).unwrap());
+  let path = matches.value_of("path").unwrap();
+  let ours_path = matches.value_of("ours").unwrap();
+
+  let merged = match (read("base"), read("ours"), read("theirs")) {
+    (Ok(base), Ok(ours), Ok(theirs)) => merge_file(path, &base, &ours, &theirs),
+    (base, ours, theirs) => Err(format!(
+      "can't read merged files: {:?} {:?} {:?}",
+      base.err(),
+      ours.err(),
+      theirs.err()
+    )),
+  };
+  match merged.and_then(|(content, conflicts)| {
+    std::fs::write(ours_path, content).map_err(|err| err.to_string())?;
+    Ok(conflicts)
+  }) {
+    Ok(conflicts) => {
+      for conflict in conflicts {
+        println!("{} {}: {}", CONFLICT_REPORT_PREFIX, path, conflict);
+      }
+      0
+    }
+    Err(err) => {
+      eprintln!("busy can't merge {}: {}", path, err);
+      1
+    }
+  }
+}
+
 fn show_tasks(
   subcommand_matches: &ArgMatches,
   busy: Rc<RefCell<Busy>>,
@@ -541,44 +1135,66 @@ fn edit(busy: Rc<RefCell<Busy>>, viewer: &Viewer, edit_data_type: EditDataType,
       let updated_task_view = run_edit_and_get_result(&task_view, &mut tmp_file, &editor);
 
       let new_tags = updated_task_view.resolve_new_tags(&all_tags);
-      busy.borrow_mut().upsert_tags(new_tags);
+      if let Err(err) = busy.borrow_mut().upsert_tags(new_tags) {
+        println!("couldn't edit: {}", err);
+        return;
+      }
       all_tags = busy.borrow().tags();
 
       let updated_task = updated_task_view.to_task(&all_tags);
-      viewer.log_task(&updated_task, true);
-      busy.borrow_mut().replace_task(&updated_task).unwrap();
+      let replaced = busy.borrow_mut().replace_task(&updated_task);
+      match replaced {
+        Ok(_) => viewer.log_task(&updated_task, true),
+        Err(err) => println!("couldn't edit: {}", err),
+      }
     }
 
     EditDataType::Project => {
       let project = busy.borrow().project_by_id(id).unwrap();
       let updated_project = run_edit_and_get_result(&project, &mut tmp_file, &editor);
 
-      println!("{}", "Updated project: ".bright_yellow());
-      viewer.print_project(&updated_project);
-      busy.borrow_mut().replace_project(&updated_project).unwrap();
+      let replaced = busy.borrow_mut().replace_project(&updated_project);
+      match replaced {
+        Ok(_) => {
+          println!("{}", "Updated project: ".bright_yellow());
+          viewer.print_project(&updated_project);
+        }
+        Err(err) => println!("couldn't edit: {}", err),
+      }
     }
 
     EditDataType::Tag => {
       let tag = busy.borrow().tag_by_id(id).unwrap();
       let updated_tag = run_edit_and_get_result(&tag, &mut tmp_file, &editor);
 
-      println!("{}", "Updated tag: ".bright_yellow());
-      viewer.print_tag(&updated_tag);
-      busy.borrow_mut().replace_tag(&updated_tag).unwrap();
+      let replaced = busy.borrow_mut().replace_tag(&updated_tag);
+      match replaced {
+        Ok(_) => {
+          println!("{}", "Updated tag: ".bright_yellow());
+          viewer.print_tag(&updated_tag);
+        }
+        Err(err) => println!("couldn't edit: {}", err),
+      }
     }
 
     EditDataType::AllTags => {
       let edited_data =
         run_edit_and_get_result(&busy.borrow().all_tags(), &mut tmp_file, editor.as_str());
-      busy.borrow_mut().replace_tags(edited_data);
-      println!("Edit finished, tags were saved");
+      let replaced = busy.borrow_mut().replace_tags(edited_data);
+      match replaced {
+        Ok(_) => println!("Edit finished, tags were saved"),
+        Err(err) => println!("couldn't edit: {}", err),
+      }
     }
 
     EditDataType::AllTasks => {
       let edited_data =
         run_edit_and_get_result(&busy.borrow().all_tasks(), &mut tmp_file, editor.as_str());
-      busy.borrow_mut().replace_tasks(edited_data);
-      println!("Edit finished, tasks were saved");
+      let replaced = busy.borrow_mut().replace_tasks(edited_data);
+      match replaced {
+        Ok(_) => println!("Edit finished, tasks were saved"),
+        Err(err) => println!("couldn't edit: {}", err),
+      }
     }
   };
 }
@@ -609,9 +1225,8 @@ fn projects_to_ids_set(
 ) -> Option<HashSet<uuid::Uuid>> {
   let mut project_ids = HashSet::new();
   for project_name in project_names.iter() {
-    let project = busy.borrow().project_by_name(project_name);
-    if project.is_some() {
-      project_ids.insert(project.unwrap().id().clone());
+    for project in busy.borrow().projects_within(project_name) {
+      project_ids.insert(project.id());
     }
   }
   if project_ids.is_empty() {
@@ -620,23 +1235,124 @@ fn projects_to_ids_set(
   return Some(project_ids);
 }
 
-fn get_period(subcommand_matches: &ArgMatches) -> Period {
+fn start_args() -> Vec<Arg<'static>> {
+  vec![
+    Arg::new("project_name").required(true).index(1),
+    Arg::new("task_title").required(true).index(2),
+    Arg::new("tags")
+      .help("should be prefixed with `+` like: +my-tag1 +mytag2")
+      .index(3)
+      .multiple_values(true),
+    Arg::new("start-time")
+      .long("start-time")
+      .takes_value(true)
+      .allow_hyphen_values(true)
+      .help("override start-time, e.g.: 11:05, -25m, 2h ago or yesterday 17:30"),
+    pause_arg(),
+    new_project_arg(),
+    new_tag_arg(),
+  ]
+}
+
+fn new_project_arg() -> Arg<'static> {
+  Arg::new("new-project")
+    .long("new-project")
+    .help("create the project if it doesn't exist, needed with `strict` in the config")
+}
+
+fn new_tag_arg() -> Arg<'static> {
+  Arg::new("new-tag")
+    .long("new-tag")
+    .help("create tags which don't exist, needed with `strict` in the config")
+}
+
+fn new_names(command_matches: &ArgMatches) -> NewNames {
+  NewNames {
+    project: command_matches.is_present("new-project"),
+    tags: command_matches.is_present("new-tag"),
+  }
+}
+
+fn print_new_name_hint(err: &Error) {
+  match err {
+    Error::NewProject(..) => println!("use --new-project to create it"),
+    Error::NewTag(..) => println!("use --new-tag to create it"),
+    _ => {}
+  }
+}
+
+fn pause_arg() -> Arg<'static> {
+  Arg::new("pause")
+    .long("pause")
+    .help("pause the active task instead of stopping it, implies switching")
+}
+
+// Switching is on with the flags, the `switch` command or the config default.
+fn get_switch_mode(
+  subcommand_matches: &ArgMatches,
+  forced: bool,
+  config: &Config,
+) -> Option<SwitchMode> {
+  if subcommand_matches.is_present("pause") {
+    return Some(SwitchMode::Pause);
+  }
+  if forced || subcommand_matches.is_present("switch") {
+    return Some(config.switch.unwrap_or_default());
+  }
+  return config.switch;
+}
+
+fn print_switched(viewer: &Viewer, switched: &Switched) {
+  if let Some(previous) = switched.previous.as_ref() {
+    match previous.is_paused() {
+      true => println!("Task paused:"),
+      false => println!("Task stopped:"),
+    };
+    viewer.log_task(previous, true);
+  }
+  println!("Task started: ");
+  viewer.log_task(&switched.started, true);
+}
+
+fn at_arg(help: &'static str) -> Arg<'static> {
+  Arg::new("at")
+    .long("at")
+    .takes_value(true)
+    .allow_hyphen_values(true)
+    .help(help)
+}
+
+fn parse_at(
+  subcommand_matches: &ArgMatches,
+  clock: &dyn Clock,
+) -> Result<Option<chrono::DateTime<chrono::Local>>, Error> {
+  subcommand_matches
+    .value_of("at")
+    .map(|at| parse_datetime(at, clock))
+    .transpose()
+}
+
+fn get_period(subcommand_matches: &ArgMatches, clock: &dyn Clock) -> Period {
   let show_today_only = subcommand_matches.is_present("today");
   if show_today_only {
-    return Period::new_to_now(get_midnight_datetime());
+    return Period::new_to_now(get_midnight_datetime(clock), clock);
   }
 
   let period_days = subcommand_matches.value_of_t("days").ok();
   if period_days.is_none() {
-    return Period::new_to_now(get_week_start_datetime());
+    return Period::new_to_now(get_week_start_datetime(clock), clock);
   }
 
-  return Period::new_to_now(get_period_since_now(period_days.unwrap()));
+  return Period::new_to_now(get_period_since_now(period_days.unwrap(), clock), clock);
 }
 
-fn restore_id_by_short_id(busy: Rc<RefCell<Busy>>, short_id: &str) -> Result<uuid::Uuid, String> {
-  match busy.borrow().resolve_id(short_id) {
-    Some(id) => Ok(id.clone()),
-    None => Err(format!("id by short name: {} not found", short_id)),
+fn restore_id_by_short_id(
+  busy: Rc<RefCell<Busy>>,
+  kind: IdKind,
+  short_id: &str,
+) -> Result<uuid::Uuid, Error> {
+  match kind {
+    IdKind::Task => busy.borrow().resolve_task(short_id),
+    _ => busy.borrow().resolve_id(kind, short_id),
   }
 }
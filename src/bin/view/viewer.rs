@@ -9,12 +9,14 @@ use colored::{Color, ColoredString, Colorize};
 
 use super::fmt::{format_duration, format_duration_without_paddings};
 use {
+  busy::clock::Clock,
   busy::duration::Period,
-  busy::project::Project,
+  busy::project::{Project, PROJECT_SEPARATOR},
+  busy::storage::Trash,
   busy::tag::Tag,
   busy::task::{self, Task},
   busy::time::DateTimeInterval,
-  busy::traits::Indexable,
+  busy::traits::{Indexable, Trashable},
   busy::Busy,
 };
 
@@ -52,8 +54,8 @@ impl ViewPaddings {
   pub const SPACE: Padding = Padding(1);
   pub const PAD: Padding = Padding(2);
   pub const LINE_INDENT: Padding = Padding(4);
-  // `dfcf..d73b`
-  pub const ID: Padding = Padding(10);
+  // `dfcf7`, ids get longer only when there are many records
+  pub const ID: Padding = Padding(6);
   // `12h`
   pub const DURATION_PART: Padding = Padding(3);
   // `12h 12m`
@@ -114,9 +116,35 @@ impl Viewer {
     }
   }
 
-  pub fn print_projects(&self) {
+  pub fn print_projects(&self, with_archived: bool) {
     for project in self.busy.borrow().projects() {
-      self.print_project(&project);
+      if with_archived || !project.is_archived() {
+        self.print_project(&project);
+      }
+    }
+  }
+
+  pub fn print_trash(&self, trash: &Trash) {
+    if !trash.tasks.is_empty() {
+      println!("{}", "Tasks: ".bright_cyan());
+      for task in trash.tasks.iter() {
+        self.log_task(task, false);
+        print_deleted_at(ViewPaddings::TILL_PROJECT, task);
+      }
+    }
+    if !trash.projects.is_empty() {
+      println!("{}", "Projects: ".bright_cyan());
+      for project in trash.projects.iter() {
+        self.print_project(project);
+        print_deleted_at(ViewPaddings::TILL_TIME_FRAME, project);
+      }
+    }
+    if !trash.tags.is_empty() {
+      println!("{}", "Tags: ".bright_cyan());
+      for tag in trash.tags.iter() {
+        self.print_tag(tag);
+        print_deleted_at(ViewPaddings::TILL_TIME_FRAME, tag);
+      }
     }
   }
 
@@ -125,24 +153,46 @@ impl Viewer {
       "{pad}{id}{pad}{tag_name}",
       pad = ViewPaddings::PAD,
       id = self.format_id_with_color(tag.id()),
-      tag_name = tag.name()
+      tag_name = match tag.color() {
+        Some(_) => tag.name().color(tag_color(tag, ViewColors::TASK_TAG)),
+        None => tag.name().normal(),
+      }
     );
   }
 
   pub fn print_project(&self, project: &Project) {
+    let mut details = Vec::new();
+    if let Some(client) = project.client() {
+      details.push(format!("client: {}", client));
+    }
+    if project.is_archived() {
+      details.push("archived".to_string());
+    }
+    let details = match details.is_empty() {
+      true => String::new(),
+      false => format!(" [{}]", details.join(", ")),
+    };
     println!(
-      "{pad}{id}{pad}{project_name}",
+      "{pad}{id}{pad}{project_name}{details}",
       pad = ViewPaddings::PAD,
       id = self.format_id_with_color(project.id()),
-      project_name = project.name()
+      project_name = project.name().color(project_color(project)),
+      details = details.dimmed()
     );
+    if let Some(description) = project.description() {
+      println!(
+        "{padding}{description}",
+        padding = ViewPaddings::TILL_TIME_FRAME,
+        description = description.dimmed().italic()
+      );
+    }
   }
 
   pub fn show_stat(
     &self,
     period: Period,
     project_ids: Option<HashSet<uuid::Uuid>>,
-    tags: &Vec<Tag>,
+    tags: &[Tag],
     with_tags: bool,
   ) {
     let by_dates = self.tasks_by_day(period, project_ids, tags);
@@ -151,12 +201,14 @@ impl Viewer {
       return;
     }
 
+    let clock = self.busy.borrow().clock();
     let mut total_duration = chrono::Duration::zero();
     for tasks in by_dates.iter() {
       total_duration = total_duration + self.total_time(tasks);
       self.print_header(tasks);
       let mut project_times: BTreeMap<uuid::Uuid, chrono::Duration> = BTreeMap::new();
       let mut tag_times: HashMap<String, chrono::Duration> = HashMap::new();
+      let mut tag_colors: HashMap<String, Color> = HashMap::new();
       let mut project_to_tags: HashMap<uuid::Uuid, BTreeSet<String>> = HashMap::new();
 
       for task in tasks {
@@ -164,48 +216,84 @@ impl Viewer {
         let task_duration = project_times
           .entry(project_id)
           .or_insert(chrono::Duration::zero());
-        *task_duration = task_duration.clone().checked_add(&task.duration()).unwrap();
+        *task_duration = task_duration
+          .clone()
+          .checked_add(&task.duration(clock.as_ref()))
+          .unwrap();
 
-        let project_tags = project_to_tags.entry(project_id).or_insert(BTreeSet::new());
+        let project_tags = project_to_tags.entry(project_id).or_default();
         let task_tags = self.busy.borrow().find_tags(task.tags());
 
         for tag in task_tags {
           let tag_duration = tag_times
             .entry(tag.name().to_string())
             .or_insert(chrono::Duration::zero());
-          *tag_duration = tag_duration.clone().checked_add(&task.duration()).unwrap();
+          *tag_duration = tag_duration
+            .clone()
+            .checked_add(&task.duration(clock.as_ref()))
+            .unwrap();
           project_tags.insert(tag.name().to_string());
+          tag_colors.insert(
+            tag.name().to_string(),
+            tag_color(&tag, ViewColors::STAT_TAG),
+          );
         }
       }
 
+      // every level of `acme/backend/api` gets a subtotal of its sub-projects
+      let mut tree: BTreeMap<Vec<String>, (chrono::Duration, Vec<uuid::Uuid>)> = BTreeMap::new();
       for (&project_id, &project_time) in project_times.iter() {
+        let project_name = self.get_project_name(project_id);
+        let path: Vec<String> = project_name
+          .split(PROJECT_SEPARATOR)
+          .map(|part| part.to_string())
+          .collect();
+        for depth in 1..=path.len() {
+          let node = tree
+            .entry(path[..depth].to_vec())
+            .or_insert((chrono::Duration::zero(), Vec::new()));
+          node.0 = node.0 + project_time;
+        }
+        tree.get_mut(&path).unwrap().1.push(project_id);
+      }
+
+      for (path, (project_time, own_project_ids)) in tree.iter() {
+        let indent = sum_pads!(
+          ViewPaddings::LINE_INDENT,
+          Padding(ViewPaddings::PAD.size() * (path.len() - 1))
+        );
         let mut tags_str = "".to_string();
         if with_tags {
-          for tag in project_to_tags.entry(project_id).or_default().iter() {
+          let mut node_tags = BTreeSet::new();
+          for project_id in own_project_ids.iter() {
+            node_tags.extend(
+              project_to_tags
+                .entry(*project_id)
+                .or_default()
+                .iter()
+                .cloned(),
+            );
+          }
+          for tag in node_tags.iter() {
             tags_str += &format!(
               "\n{indent}{pad}+ {tag_name}: {duration}",
-              indent = ViewPaddings::LINE_INDENT,
+              indent = indent,
               pad = ViewPaddings::PAD,
-              tag_name = tag.color(ViewColors::STAT_TAG).bold(),
+              tag_name = tag.color(tag_colors[tag]).bold(),
               duration = format_duration_without_paddings(*tag_times.get(tag).unwrap())
             );
           }
-          tags_str += "\n";
         }
 
         println!(
           "{indent}{project_name}: {duration}{tags}",
-          indent = ViewPaddings::LINE_INDENT,
-          project_name = self
-            .get_project_name(project_id)
-            .color(ViewColors::STAT_PROJECT),
-          duration = format_duration_without_paddings(project_time).bold(),
+          indent = indent,
+          project_name = path.last().unwrap().color(ViewColors::STAT_PROJECT),
+          duration = format_duration_without_paddings(*project_time).bold(),
           tags = tags_str
         );
       }
-      if !with_tags {
-        println!("");
-      }
+      println!();
     }
 
     println!(
@@ -218,7 +306,7 @@ impl Viewer {
     &self,
     period: Period,
     maybe_project_ids: Option<HashSet<uuid::Uuid>>,
-    tags: &Vec<Tag>,
+    tags: &[Tag],
   ) -> Vec<Vec<Task>> {
     let tasks = self.busy.borrow().tasks(period);
     if tasks.is_empty() {
@@ -235,14 +323,13 @@ impl Viewer {
         continue;
       }
 
-      if !tags.is_empty() {
-        if !task
+      if !tags.is_empty()
+        && !task
           .tags()
           .iter()
           .any(|t| tags.iter().position(|tag| tag.id() == *t).is_some())
-        {
-          continue;
-        }
+      {
+        continue;
       }
 
       let task_date = task.start_time().date_naive();
@@ -259,7 +346,7 @@ impl Viewer {
     &self,
     period: Period,
     project_ids: Option<HashSet<uuid::Uuid>>,
-    tags: &Vec<Tag>,
+    tags: &[Tag],
     show_full: bool,
   ) {
     let by_dates = self.tasks_by_day(period, project_ids, tags);
@@ -273,19 +360,20 @@ impl Viewer {
       for t in tasks.iter() {
         self.log_task(t, show_full);
       }
-      println!("");
+      println!();
     }
   }
 
-  fn total_time(&self, tasks: &Vec<Task>) -> chrono::Duration {
+  fn total_time(&self, tasks: &[Task]) -> chrono::Duration {
+    let clock = self.busy.borrow().clock();
     return tasks
       .iter()
-      .map(|t| t.duration())
+      .map(|t| t.duration(clock.as_ref()))
       .reduce(|acc, new_d| acc + new_d)
       .unwrap_or(chrono::Duration::zero());
   }
 
-  fn print_header(&self, tasks: &Vec<Task>) {
+  fn print_header(&self, tasks: &[Task]) {
     let date = tasks.first().unwrap().start_time().date_naive();
     let total_time = self.total_time(tasks);
     println!(
@@ -312,23 +400,33 @@ impl Viewer {
     let task_tags = self.busy.borrow().find_tags(task.tags());
     let tags: Vec<String> = task_tags
       .iter()
-      .map(|tag| tag.name().color(ViewColors::TASK_TAG).to_string())
+      .map(|tag| {
+        tag
+          .name()
+          .color(tag_color(tag, ViewColors::TASK_TAG))
+          .to_string()
+      })
       .collect();
 
     let project_name = self.get_project_name(task.project_id());
-    let mut project_name_msg = project_name.as_str().color(ViewColors::TASK_PROJECT_NAME);
+    let project_color = match self.busy.borrow().project_by_id(task.project_id()) {
+      Some(project) => project_color(&project),
+      None => ViewColors::TASK_PROJECT_NAME,
+    };
+    let mut project_name_msg = project_name.as_str().color(project_color);
     if task.is_paused() {
       project_name_msg = (project_name + " [paused]").color(ViewColors::TASK_PAUSED_PROJECT_NAME);
     }
 
-    let time_frames = get_formatted_time_intervals(task);
+    let clock = self.busy.borrow().clock();
+    let time_frames = get_formatted_time_intervals(task, clock.as_ref());
     println!(
       "{line_indent}{task_id}{pad}{time_frame}{pad}{duration:7}{pad}{project:10}{pad}{tags}",
       line_indent = ViewPaddings::LINE_INDENT,
       pad = ViewPaddings::PAD,
       task_id = self.format_id_with_color(task.id()),
       time_frame = time_frames.first().unwrap(),
-      duration = format_duration(task.duration()),
+      duration = format_duration(task.duration(clock.as_ref())),
       project = project_name_msg,
       tags = tags.join(", ").italic()
     );
@@ -357,11 +455,37 @@ impl Viewer {
   }
 
   fn format_id_with_color(&self, id: uuid::Uuid) -> ColoredString {
-    self.busy.borrow().shorten_id(id).color(ViewColors::ID)
+    let short_id = self.busy.borrow().shorten_id(id);
+    format!("{:width$}", short_id, width = ViewPaddings::ID.size()).color(ViewColors::ID)
+  }
+}
+
+fn project_color(project: &Project) -> Color {
+  project
+    .color()
+    .and_then(|color| color.parse().ok())
+    .unwrap_or(ViewColors::TASK_PROJECT_NAME)
+}
+
+fn tag_color(tag: &Tag, default: Color) -> Color {
+  tag
+    .color()
+    .and_then(|color| color.parse().ok())
+    .unwrap_or(default)
+}
+
+fn print_deleted_at(padding: Padding, item: &dyn Trashable) {
+  if let Some(deleted_at) = item.deleted_at() {
+    println!(
+      "{padding}{}",
+      format!("deleted at {}", deleted_at.format("%Y-%m-%d %H:%M"))
+        .dimmed()
+        .italic()
+    );
   }
 }
 
-fn get_formatted_time_intervals(task: &Task) -> Vec<String> {
+fn get_formatted_time_intervals(task: &Task, clock: &dyn Clock) -> Vec<String> {
   let interval_count = task.times().len();
   let mut formatted_time_frames = Vec::new();
   for i in 0..interval_count {
@@ -395,6 +519,7 @@ fn get_formatted_time_intervals(task: &Task) -> Vec<String> {
         false => ViewColors::TIME_ACTIVE,
       },
       with_duration,
+      clock,
     ));
   }
   return formatted_time_frames;
@@ -405,6 +530,7 @@ fn format_time_frame(
   start_time_color: Color,
   stop_time_color: Color,
   with_duration: bool,
+  clock: &dyn Clock,
 ) -> String {
   let mut duration = String::new();
   if with_duration {
@@ -412,7 +538,7 @@ fn format_time_frame(
       "{pad}{duration}",
       pad = ViewPaddings::PAD,
       duration =
-        format_duration(time_interval.duration()).color(ViewColors::TASK_ADDITIONAL_DURATION)
+        format_duration(time_interval.duration(clock)).color(ViewColors::TASK_ADDITIONAL_DURATION)
     );
   }
 
@@ -420,7 +546,7 @@ fn format_time_frame(
     "{start_time} to {stop_time}{duration}",
     start_time = format_time(&time_interval.start_time, start_time_color),
     stop_time = format_time(
-      &time_interval.stop_time.unwrap_or(chrono::Local::now()),
+      &time_interval.stop_time.unwrap_or_else(|| clock.now()),
       stop_time_color
     ),
   )
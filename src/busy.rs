@@ -1,89 +1,331 @@
+use std::{
+  cell::{Cell, RefCell},
+  rc::Rc,
+};
+
 use log::debug;
 
 use crate::{
-  duration::Period,
-  project::Project,
-  storage::{JsonStorage, Storage},
+  clock::{Clock, SystemClock},
+  doctor::{self, Problem},
+  duration::{get_midnight_datetime, Period},
+  journal::{self, Journal, JournalEntry, State},
+  project::{Project, ProjectChanges},
+  short_id::{IdKind, ShortIds},
+  storage::{
+    lock::{StorageLock, LOCK_TIMEOUT},
+    migrate, EventLogStorage, JsonStorage, SqliteStorage, Storage, StorageConfig, StorageError,
+    Trash,
+  },
+  suggest,
   sync::Syncer,
-  sync::{EmptySyncer, GitSyncer, SyncerConfig},
+  sync::{DirectorySyncer, EmptySyncer, GitSyncer, SyncerConfig, CONFLICT_REPORT_PREFIX},
   tag::Tag,
   task::Task,
   traits::Indexable,
-  Config,
+  validation::{self, Fix, Issue},
+  Config, Error, SwitchMode,
 };
 
+// Separate from the lock files of the storages, which are taken under it.
+const COMMAND_LOCK_FILE: &str = ".busy.command.lock";
+
 pub struct Busy {
   storage: Box<dyn Storage>,
   syncer: Box<dyn Syncer>,
+  journal: Journal,
   config: Config,
+  clock: Rc<dyn Clock>,
+  // storage opened from the config is reopened after sync,
+  // an injected one is owned by the caller
+  reopen_storage: bool,
+  // built on the first use and dropped on every change
+  short_ids: RefCell<Option<Rc<ShortIds>>>,
+  // set while a command holds the command lock
+  locked: Rc<Cell<bool>>,
 }
 
-impl Busy {
-  pub fn new() -> Self {
-    let config = Config::new();
+/// Exclusive lock held by a command from its checks to its last write, so
+/// concurrent busy processes don't act on what they read before. Commands
+/// called by other commands share the lock of the outer one.
+struct CommandLock {
+  locked: Rc<Cell<bool>>,
+  lock: Option<StorageLock>,
+}
 
-    debug!("busy data folder: {}", config.storage_dir_path);
-    std::fs::create_dir_all(&config.storage_dir_path).unwrap();
+impl Drop for CommandLock {
+  fn drop(&mut self) {
+    if self.lock.take().is_some() {
+      self.locked.set(false);
+    }
+  }
+}
 
-    let syncer: Box<dyn Syncer> = match config.syncer.clone() {
-      SyncerConfig::Empty => Box::new(EmptySyncer::new()),
-      SyncerConfig::Git {
-        remote,
-        remote_branch,
-        key_file,
-      } => Box::new(GitSyncer::new(
-        &config.storage_dir_path,
-        key_file,
-        Some(remote),
-        remote_branch,
-      )),
-    };
+/// Names `start`, `switch` and `add` may create in strict mode (`strict`
+/// in the config), other unknown projects and tags are refused. Without
+/// strict mode every name may be created.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NewNames {
+  pub project: bool,
+  pub tags: bool,
+}
 
+/// Result of a switch: the task which was active and the started one.
+pub struct Switched {
+  pub previous: Option<Task>,
+  pub started: Task,
+}
+
+/// Builds `Busy` from an explicit config. Storage and syncer which aren't
+/// set are created from the config, the same way `Busy::new` does it.
+pub struct BusyBuilder {
+  config: Config,
+  storage: Option<Box<dyn Storage>>,
+  syncer: Option<Box<dyn Syncer>>,
+  journal_path: Option<String>,
+  clock: Rc<dyn Clock>,
+}
+
+impl BusyBuilder {
+  pub fn new(config: Config) -> Self {
     Self {
-      storage: Box::new(JsonStorage::new(&config.storage_dir_path)),
-      syncer,
       config,
+      storage: None,
+      syncer: None,
+      journal_path: None,
+      clock: Rc::new(SystemClock),
+    }
+  }
+
+  pub fn storage(mut self, storage: Box<dyn Storage>) -> Self {
+    self.storage = Some(storage);
+    self
+  }
+
+  pub fn syncer(mut self, syncer: Box<dyn Syncer>) -> Self {
+    self.syncer = Some(syncer);
+    self
+  }
+
+  /// Undo history is kept in memory if there is neither the journal path
+  /// nor the storage opened from the config.
+  pub fn journal_path(mut self, journal_path: &str) -> Self {
+    self.journal_path = Some(journal_path.to_owned());
+    self
+  }
+
+  pub fn clock(mut self, clock: Rc<dyn Clock>) -> Self {
+    self.clock = clock;
+    self
+  }
+
+  pub fn build(self) -> Result<Busy, Error> {
+    let config = self.config;
+    if self.storage.is_none() || self.syncer.is_none() {
+      debug!("busy data folder: {}", config.storage_dir_path);
+      std::fs::create_dir_all(&config.storage_dir_path).map_err(|err| {
+        Error::Storage(format!(
+          "can't create data folder {}: {}",
+          config.storage_dir_path, err
+        ))
+      })?;
     }
+
+    let syncer = match self.syncer {
+      Some(syncer) => syncer,
+      None => open_syncer(&config)?,
+    };
+
+    let reopen_storage = self.storage.is_none();
+    let mut journal_path = self.journal_path;
+    let storage = match self.storage {
+      Some(storage) => storage,
+      None => {
+        if journal_path.is_none() {
+          let path = std::path::Path::new(&config.storage_dir_path).join("journal.json");
+          journal_path = Some(path.to_string_lossy().into_owned());
+        }
+        open_storage(&config, &self.clock).map_err(Error::storage)?
+      }
+    };
+
+    Ok(Busy {
+      storage,
+      syncer,
+      journal: Journal::new(journal_path).map_err(Error::Journal)?,
+      config,
+      clock: self.clock,
+      reopen_storage,
+      short_ids: RefCell::new(None),
+      locked: Rc::new(Cell::new(false)),
+    })
   }
+}
 
-  pub fn sync(&mut self) -> std::io::Result<String> {
-    self.syncer.sync()?;
-    self.storage = Box::new(JsonStorage::new(&self.config.storage_dir_path));
+impl Busy {
+  pub fn new() -> Result<Self, Error> {
+    BusyBuilder::new(Config::new()?).build()
+  }
 
-    return Ok("sync success".to_string());
+  pub fn config(&self) -> &Config {
+    &self.config
   }
 
-  pub fn push_force(&mut self) -> std::io::Result<String> {
-    self.syncer.push_force()
+  pub fn clock(&self) -> Rc<dyn Clock> {
+    Rc::clone(&self.clock)
   }
 
-  pub fn pull_force(&mut self) -> std::io::Result<String> {
-    self.syncer.pull_force()
+  /// Returns conflicting changes which were merged automatically.
+  pub fn sync(&mut self) -> Result<Vec<String>, Error> {
+    let _lock = self.lock()?;
+    let output = self.syncer.sync().map_err(Error::sync)?;
+    self.reopen_storage()?;
+
+    return Ok(
+      output
+        .lines()
+        .filter_map(|line| line.strip_prefix(CONFLICT_REPORT_PREFIX))
+        .map(|line| line.trim().to_owned())
+        .collect(),
+    );
   }
 
+  pub fn push_force(&mut self) -> Result<String, Error> {
+    self.syncer.push_force().map_err(Error::sync)
+  }
+
+  /// Storage is reopened, it was replaced by the remote one.
+  pub fn pull_force(&mut self) -> Result<String, Error> {
+    let _lock = self.lock()?;
+    let output = self.syncer.pull_force().map_err(Error::sync)?;
+    self.reopen_storage()?;
+    Ok(output)
+  }
+
+  /// The shortest prefix of the id which is unique among all records.
   pub fn shorten_id(&self, id: uuid::Uuid) -> String {
-    self.storage.shorten_id(id)
+    self.short_ids().shorten(id)
+  }
+
+  /// Resolves a short id of a record of the given kind, trashed records
+  /// aren't looked at.
+  pub fn resolve_id(&self, kind: IdKind, short_id: &str) -> Result<uuid::Uuid, Error> {
+    self.short_ids().resolve(short_id, Some(kind), false)
+  }
+
+  /// Resolves a short task id or a reference to a task by its position:
+  /// `@active`, `@last` (the last stopped one), `@-N` (the Nth task from the
+  /// end, `@-1` is the last started one) and `@today:N` (the Nth task of
+  /// today).
+  pub fn resolve_task(&self, reference: &str) -> Result<uuid::Uuid, Error> {
+    let position = match reference.strip_prefix('@') {
+      Some(position) => position,
+      None => return self.resolve_id(IdKind::Task, reference),
+    };
+    let invalid = || Error::InvalidReference(reference.to_owned());
+    let not_found = || Error::IdNotFound(reference.to_owned());
+    let mut tasks = self.storage.tasks();
+    tasks.sort_by_key(|task| task.start_time());
+
+    let task = if position == "active" {
+      self.active_task().ok_or(Error::NoActiveTask)?
+    } else if position == "last" {
+      // a paused task is stopped too, but it's still going on
+      tasks
+        .into_iter()
+        .filter(|task| task.stop_time().is_some() && !task.is_paused())
+        .max_by_key(|task| task.stop_time())
+        .ok_or_else(not_found)?
+    } else if let Some(n) = position.strip_prefix('-') {
+      let n: usize = n.parse().map_err(|_| invalid())?;
+      match n.checked_sub(1) {
+        Some(n) => tasks.into_iter().rev().nth(n).ok_or_else(not_found)?,
+        None => return Err(invalid()),
+      }
+    } else if let Some(n) = position.strip_prefix("today:") {
+      let n: usize = n.parse().map_err(|_| invalid())?;
+      let midnight = get_midnight_datetime(self.clock.as_ref());
+      let mut today = tasks
+        .into_iter()
+        .filter(|task| task.start_time() >= midnight);
+      match n.checked_sub(1) {
+        Some(n) => today.nth(n).ok_or_else(not_found)?,
+        None => return Err(invalid()),
+      }
+    } else {
+      return Err(invalid());
+    };
+    Ok(task.id())
   }
 
-  pub fn resolve_id(&self, short_id: &str) -> Option<uuid::Uuid> {
-    self.storage.resolve_id(short_id)
+  fn short_ids(&self) -> Rc<ShortIds> {
+    if let Some(short_ids) = self.short_ids.borrow().as_ref() {
+      return Rc::clone(short_ids);
+    }
+    let trash = self.storage.trash();
+    let mut ids = Vec::new();
+    for (tasks, trashed) in [(self.storage.tasks(), false), (trash.tasks, true)] {
+      ids.extend(tasks.iter().map(|task| (task.id(), IdKind::Task, trashed)));
+    }
+    for (projects, trashed) in [(self.storage.projects(), false), (trash.projects, true)] {
+      ids.extend(projects.iter().map(|p| (p.id(), IdKind::Project, trashed)));
+    }
+    for (tags, trashed) in [(self.storage.tags(), false), (trash.tags, true)] {
+      ids.extend(tags.iter().map(|tag| (tag.id(), IdKind::Tag, trashed)));
+    }
+    let short_ids = Rc::new(ShortIds::new(ids));
+    *self.short_ids.borrow_mut() = Some(Rc::clone(&short_ids));
+    short_ids
+  }
+
+  // Checked before anything is created, so a refused tag doesn't leave
+  // a new project behind.
+  fn check_new_names(
+    &self,
+    project_name: &str,
+    tags: &[String],
+    new_names: NewNames,
+  ) -> Result<(), Error> {
+    if !self.config.strict {
+      return Ok(());
+    }
+    if !new_names.project && self.project_by_name(project_name).is_none() {
+      let projects = self.projects();
+      let suggestion = suggest::closest(project_name, projects.iter().map(|p| p.name()));
+      return Err(Error::NewProject(
+        project_name.to_owned(),
+        suggestion.map(str::to_owned),
+      ));
+    }
+    if new_names.tags {
+      return Ok(());
+    }
+    let known_tags = self.tags();
+    for tag in tags.iter() {
+      if known_tags.iter().all(|known| known.name() != tag) {
+        let suggestion = suggest::closest(tag, known_tags.iter().map(|t| t.name()));
+        return Err(Error::NewTag(tag.clone(), suggestion.map(str::to_owned)));
+      }
+    }
+    Ok(())
   }
 
-  pub fn upsert_tags(&mut self, tags: Vec<String>) -> Vec<uuid::Uuid> {
+  pub fn upsert_tags(&mut self, tags: Vec<String>) -> Result<Vec<uuid::Uuid>, Error> {
+    let _lock = self.lock()?;
     let mut pushed_ids = Vec::new();
     for tag in tags.iter() {
       match self.storage.find_tag_by_name(tag) {
         Some(found_tag) => {
-          pushed_ids.push(found_tag.id().clone());
+          pushed_ids.push(found_tag.id());
         }
         None => {
           let new_tag = Tag::new(tag);
-          self.storage.add_tag(&new_tag);
-          pushed_ids.push(new_tag.id().clone());
+          self.storage.add_tag(&new_tag).map_err(Error::storage)?;
+          pushed_ids.push(new_tag.id());
         }
       }
     }
-    return pushed_ids;
+    return Ok(pushed_ids);
   }
 
   pub fn add(
@@ -93,18 +335,32 @@ impl Busy {
     tags: Vec<String>,
     start_time: chrono::DateTime<chrono::Local>,
     finish_time: chrono::DateTime<chrono::Local>,
-  ) -> Result<Task, String> {
-    let project = self.upsert_project(project_name);
+    new_names: NewNames,
+  ) -> Result<Task, Error> {
+    let _lock = self.lock()?;
+    self.check_new_names(project_name, &tags, new_names)?;
+    // checked before the project and tags are created, only times matter
+    self.validate(&Task::new(
+      uuid::Uuid::nil(),
+      title,
+      vec![],
+      Some(start_time),
+      Some(finish_time),
+      self.clock.as_ref(),
+    ))?;
+    let before = self.state();
+    let project = self.upsert_project(project_name)?;
     let task = Task::new(
       project.id(),
       title,
-      self.upsert_tags(tags),
+      self.upsert_tags(tags)?,
       Some(start_time),
       Some(finish_time),
+      self.clock.as_ref(),
     );
-    self.storage.add_task(&task);
+    self.storage.add_task(&task).map_err(Error::storage)?;
 
-    self.commit(&format_task_commit("added", &task));
+    self.commit(before, &format_task_commit("added", &task));
     return Ok(task);
   }
 
@@ -114,92 +370,201 @@ impl Busy {
     title: &str,
     tags: Vec<String>,
     start_time: Option<chrono::DateTime<chrono::Local>>,
-  ) -> Result<Task, String> {
-    if !self.active_task().is_none() {
-      return Err("active task already exists, stop it firstly".to_string());
+    new_names: NewNames,
+  ) -> Result<Task, Error> {
+    let _lock = self.lock()?;
+    if self.active_task().is_some() {
+      return Err(Error::ActiveTaskExists);
     }
-    let project = self.upsert_project(project_name);
+    self.check_new_names(project_name, &tags, new_names)?;
+    let before = self.state();
+    let project = self.upsert_project(project_name)?;
     let task = Task::new(
       project.id(),
       title,
-      self.upsert_tags(tags),
+      self.upsert_tags(tags)?,
       start_time,
       None,
+      self.clock.as_ref(),
     );
-    self.storage.add_task(&task);
+    self.storage.add_task(&task).map_err(Error::storage)?;
 
-    self.commit(&format_task_commit("started", &task));
+    self.commit(before, &format_task_commit("started", &task));
 
     return Ok(task);
   }
 
-  pub fn stop(&mut self) -> Result<Task, String> {
-    let maybe_active_task = self.active_task();
-    if maybe_active_task.is_none() {
-      return Err("there is no active task to stop".to_owned());
+  /// Stops or pauses the active task and starts a new one at the same
+  /// instant, as a single change.
+  pub fn switch(
+    &mut self,
+    project_name: &str,
+    title: &str,
+    tags: Vec<String>,
+    start_time: Option<chrono::DateTime<chrono::Local>>,
+    mode: SwitchMode,
+    new_names: NewNames,
+  ) -> Result<Switched, Error> {
+    let _lock = self.lock()?;
+    let at = self.moment(start_time)?;
+    self.check_new_names(project_name, &tags, new_names)?;
+    let previous = self.leave_active_task(at, mode)?;
+    let before = self.state();
+    let project = self.upsert_project(project_name)?;
+    let task = Task::new(
+      project.id(),
+      title,
+      self.upsert_tags(tags)?,
+      Some(at),
+      None,
+      self.clock.as_ref(),
+    );
+    // the active task is changed only when the new one is ready
+    if let Some(previous) = previous.as_ref() {
+      self
+        .storage
+        .replace_task(previous)
+        .map_err(Error::storage)?;
     }
+    self.storage.add_task(&task).map_err(Error::storage)?;
 
-    let mut active_task = maybe_active_task.unwrap();
-    active_task.stop();
+    self.commit(before, &format_task_commit("switched to", &task));
+    Ok(Switched {
+      previous,
+      started: task,
+    })
+  }
+
+  /// Same as `continue_task`, but the active task is stopped or paused.
+  pub fn switch_to_task(
+    &mut self,
+    task_id: uuid::Uuid,
+    mode: SwitchMode,
+  ) -> Result<Switched, Error> {
+    let _lock = self.lock()?;
+    let existing_task = self
+      .task_by_id(task_id)
+      .ok_or(Error::TaskNotFound(task_id))?;
+    let at = self.clock.now();
+    let previous = self.leave_active_task(at, mode)?;
+    let before = self.state();
+    let task = Task::new(
+      existing_task.project_id(),
+      existing_task.title(),
+      existing_task.tags().clone(),
+      Some(at),
+      None,
+      self.clock.as_ref(),
+    );
+    if let Some(previous) = previous.as_ref() {
+      self
+        .storage
+        .replace_task(previous)
+        .map_err(Error::storage)?;
+    }
+    self.storage.add_task(&task).map_err(Error::storage)?;
+
+    self.commit(before, &format_task_commit("switched to", &task));
+    Ok(Switched {
+      previous,
+      started: task,
+    })
+  }
+
+  // The active task as it is after the switch, the caller stores it.
+  // A paused task is left as it is on pause and keeps its pause time
+  // on stop, the time on pause isn't counted.
+  fn leave_active_task(
+    &self,
+    at: chrono::DateTime<chrono::Local>,
+    mode: SwitchMode,
+  ) -> Result<Option<Task>, Error> {
+    let mut task = match self.active_task() {
+      Some(task) => task,
+      None => return Ok(None),
+    };
+    match (task.is_paused(), mode) {
+      (true, SwitchMode::Pause) => return Ok(Some(task)),
+      (true, SwitchMode::Stop) => task.stop(task.stop_time().unwrap()),
+      (false, SwitchMode::Stop) => task.stop(at),
+      (false, SwitchMode::Pause) => task.pause(at),
+    }?;
+    Ok(Some(task))
+  }
+
+  /// Stops the active task now or at `at` if it's set. A paused task keeps
+  /// the time it was paused at, the pause isn't counted as work.
+  pub fn stop(&mut self, at: Option<chrono::DateTime<chrono::Local>>) -> Result<Task, Error> {
+    let _lock = self.lock()?;
+    let at = self.moment(at)?;
+    let active_task = self
+      .leave_active_task(at, SwitchMode::Stop)?
+      .ok_or(Error::NoActiveTask)?;
+
+    let before = self.state();
 
     match self.storage.replace_task(&active_task.clone()) {
       Ok(_) => {
-        self.commit(&format_task_commit("stopped", &active_task));
+        self.commit(before, &format_task_commit("stopped", &active_task));
         Ok(active_task)
       }
-      Err(err) => Err(err),
+      Err(err) => Err(Error::storage(err)),
     }
   }
 
-  pub fn pause(&mut self) -> Result<Task, String> {
+  pub fn pause(&mut self, at: Option<chrono::DateTime<chrono::Local>>) -> Result<Task, Error> {
+    let _lock = self.lock()?;
     let maybe_active_task = self.active_task();
     if maybe_active_task.is_none() {
-      return Err("there is no active task to pause".to_owned());
+      return Err(Error::NoActiveTask);
     }
 
+    let before = self.state();
     let mut active_task = maybe_active_task.unwrap();
-    active_task.pause();
+    active_task.pause(self.moment(at)?)?;
 
     match self.storage.replace_task(&active_task) {
       Ok(_) => {
-        self.commit(&format_task_commit("paused", &active_task));
+        self.commit(before, &format_task_commit("paused", &active_task));
         Ok(active_task)
       }
-      Err(err) => Err(err),
+      Err(err) => Err(Error::storage(err)),
     }
   }
 
-  pub fn resume(&mut self) -> Result<Task, String> {
-    const ERR_MSG: &str = "there is no paused task to continue";
-
+  pub fn resume(&mut self, at: Option<chrono::DateTime<chrono::Local>>) -> Result<Task, Error> {
+    let _lock = self.lock()?;
     let maybe_active_task = self.active_task();
     if maybe_active_task.is_none() {
-      return Err(ERR_MSG.to_owned());
+      return Err(Error::NoPausedTask);
     }
 
     let mut active_task = maybe_active_task.unwrap();
     if active_task.stop_time().is_none() {
-      return Err(ERR_MSG.to_owned());
+      return Err(Error::NoPausedTask);
     }
-    active_task.resume();
+    let before = self.state();
+    active_task.resume(self.moment(at)?)?;
     match self.storage.replace_task(&active_task) {
       Ok(_) => {
-        self.commit(&format_task_commit("continue", &active_task));
+        self.commit(before, &format_task_commit("continue", &active_task));
         Ok(active_task)
       }
-      Err(err) => Err(err),
+      Err(err) => Err(Error::storage(err)),
     }
   }
 
-  pub fn continue_task(&mut self, task_id: uuid::Uuid) -> Result<Task, String> {
+  pub fn continue_task(&mut self, task_id: uuid::Uuid) -> Result<Task, Error> {
+    let _lock = self.lock()?;
     if self.active_task().is_some() {
-      return Err("found active task, please stop it firstly".to_owned());
+      return Err(Error::ActiveTaskExists);
     }
 
     let maybe_task_to_continue = self.task_by_id(task_id);
     if maybe_task_to_continue.is_none() {
-      return Err(format!("task with id: {} not found", task_id));
+      return Err(Error::TaskNotFound(task_id));
     }
+    let before = self.state();
     let existing_task = maybe_task_to_continue.unwrap();
     let new_task = Task::new(
       existing_task.project_id(),
@@ -207,38 +572,512 @@ impl Busy {
       existing_task.tags().clone(),
       None,
       None,
+      self.clock.as_ref(),
     );
-    self.storage.add_task(&new_task);
-    self.commit(&format_task_commit("continue", &new_task));
+    self.storage.add_task(&new_task).map_err(Error::storage)?;
+    self.commit(before, &format_task_commit("continue", &new_task));
     return Ok(new_task);
   }
 
-  pub fn replace_task(&mut self, task: &Task) -> Result<(), String> {
+  pub fn replace_task(&mut self, task: &Task) -> Result<(), Error> {
+    let _lock = self.lock()?;
+    self.validate(task)?;
+    let before = self.state();
     match self.storage.replace_task(task) {
       Ok(_) => {
-        self.commit(&format_task_commit("replace", &task));
+        self.commit(before, &format_task_commit("replace", task));
         return Ok(());
       }
-      Err(err) => Err(err),
+      Err(err) => Err(Error::storage(err)),
     }
   }
 
-  pub fn replace_project(&mut self, project: &Project) -> Result<(), String> {
+  pub fn replace_project(&mut self, project: &Project) -> Result<(), Error> {
+    let _lock = self.lock()?;
+    let before = self.state();
     match self.storage.replace_project(project) {
       Ok(_) => {
-        self.commit(&format!(
-          "replace task, name: {} id: {}",
-          project.name(),
-          project.id()
-        ));
+        self.commit(
+          before,
+          &format!(
+            "replace project, name: {} id: {}",
+            project.name(),
+            project.id()
+          ),
+        );
         return Ok(());
       }
-      Err(err) => Err(err),
+      Err(err) => Err(Error::storage(err)),
+    }
+  }
+
+  pub fn update_project(
+    &mut self,
+    project_name: &str,
+    changes: ProjectChanges,
+  ) -> Result<Project, Error> {
+    let _lock = self.lock()?;
+    let mut project = self
+      .project_by_name(project_name)
+      .ok_or_else(|| Error::UnknownProject(project_name.to_owned()))?;
+    project.apply(changes).map_err(Error::InvalidProject)?;
+    let before = self.state();
+    self
+      .storage
+      .replace_project(&project)
+      .map_err(Error::storage)?;
+    self.commit(before, &format!("update project, name: {}", project.name()));
+    Ok(project)
+  }
+
+  /// Renames the project along with its sub-projects, e.g. `acme/api`
+  /// becomes `new-name/api` when `acme` is renamed.
+  pub fn rename_project(&mut self, project_name: &str, new_name: &str) -> Result<Project, Error> {
+    let _lock = self.lock()?;
+    let project = self
+      .project_by_name(project_name)
+      .ok_or_else(|| Error::UnknownProject(project_name.to_owned()))?;
+    if new_name.is_empty() {
+      return Err(Error::InvalidProject("name can't be empty".to_owned()));
+    }
+    let mut renamed = Vec::new();
+    let prefix_len = project.name().len();
+    for mut project in self.projects_within(project.name()) {
+      let name = format!("{}{}", new_name, &project.name()[prefix_len..]);
+      if self.project_by_name(&name).is_some() {
+        return Err(Error::InvalidProject(format!(
+          "project {} already exists, merge the projects instead",
+          name
+        )));
+      }
+      project.set_name(&name);
+      renamed.push(project);
+    }
+
+    let before = self.state();
+    for project in renamed.iter() {
+      self
+        .storage
+        .replace_project(project)
+        .map_err(Error::storage)?;
+    }
+    self.commit(
+      before,
+      &format!(
+        "rename project {} to {}, id: {}",
+        project_name,
+        new_name,
+        project.id()
+      ),
+    );
+    Ok(
+      renamed
+        .into_iter()
+        .find(|renamed| renamed.id() == project.id())
+        .unwrap(),
+    )
+  }
+
+  /// Moves every task of `from_name` to `into_name` and moves the merged
+  /// project to the trash. Sub-projects are moved under `into_name` the way
+  /// `rename_project` does it, the ones which already exist there are merged
+  /// too. Returns the number of moved tasks.
+  pub fn merge_projects(&mut self, from_name: &str, into_name: &str) -> Result<usize, Error> {
+    let _lock = self.lock()?;
+    let from = self
+      .project_by_name(from_name)
+      .ok_or_else(|| Error::UnknownProject(from_name.to_owned()))?;
+    let into = self
+      .project_by_name(into_name)
+      .ok_or_else(|| Error::UnknownProject(into_name.to_owned()))?;
+    if from.id() == into.id() {
+      return Err(Error::InvalidProject(format!(
+        "can't merge project {} into itself",
+        from_name
+      )));
+    }
+    if into.is_within(from.name()) {
+      return Err(Error::InvalidProject(format!(
+        "can't merge project {} into its sub-project {}",
+        from_name, into_name
+      )));
+    }
+
+    // projects which are merged and the ones their tasks move to
+    let mut merged = vec![(from.id(), into.id())];
+    let mut renamed = Vec::new();
+    let prefix_len = from.name().len();
+    for mut project in self.projects_within(from.name()) {
+      if project.id() == from.id() {
+        continue;
+      }
+      let name = format!("{}{}", into.name(), &project.name()[prefix_len..]);
+      match self.project_by_name(&name) {
+        Some(existing) => merged.push((project.id(), existing.id())),
+        None => {
+          project.set_name(&name);
+          renamed.push(project);
+        }
+      }
+    }
+
+    let before = self.state();
+    let mut moved = 0;
+    let tasks: Vec<Task> = self
+      .storage
+      .tasks()
+      .into_iter()
+      .map(|mut task| {
+        let target = merged.iter().find(|(id, _)| *id == task.project_id());
+        if let Some((_, target_id)) = target {
+          task.set_project_id(*target_id);
+          moved += 1;
+        }
+        task
+      })
+      .collect();
+    // every task is written at once, so none of them is left behind
+    if moved > 0 {
+      self.storage.replace_tasks(tasks).map_err(Error::storage)?;
+    }
+    for project in renamed.iter() {
+      self
+        .storage
+        .replace_project(project)
+        .map_err(Error::storage)?;
+    }
+    for (project_id, _) in merged.iter() {
+      self
+        .storage
+        .move_to_trash(*project_id, self.clock.now())
+        .map_err(Error::storage)?;
+    }
+    self.commit(
+      before,
+      &format!(
+        "merge project {} into {}, {} tasks moved",
+        from_name, into_name, moved
+      ),
+    );
+    Ok(moved)
+  }
+
+  /// Archived projects are hidden from listings, their tasks stay as they are.
+  pub fn archive_project(&mut self, project_name: &str, archived: bool) -> Result<Project, Error> {
+    let _lock = self.lock()?;
+    let mut project = self
+      .project_by_name(project_name)
+      .ok_or_else(|| Error::UnknownProject(project_name.to_owned()))?;
+    project.set_archived(archived);
+    let before = self.state();
+    self
+      .storage
+      .replace_project(&project)
+      .map_err(Error::storage)?;
+    let action = match archived {
+      true => "archive",
+      false => "unarchive",
+    };
+    self.commit(
+      before,
+      &format!("{} project, name: {}", action, project.name()),
+    );
+    Ok(project)
+  }
+
+  pub fn remove_task(&mut self, task_id: uuid::Uuid) -> Result<(), Error> {
+    let _lock = self.lock()?;
+    let before = self.state();
+    self
+      .storage
+      .move_to_trash(task_id, self.clock.now())
+      .map_err(Error::storage)?;
+    self.commit(before, &format!("remove task id: {}", task_id));
+    Ok(())
+  }
+
+  pub fn remove_project(&mut self, project_id: uuid::Uuid) -> Result<(), Error> {
+    let _lock = self.lock()?;
+    let project = match self.project_by_id(project_id) {
+      Some(project) => project,
+      None => return Err(Error::ProjectNotFound(project_id)),
+    };
+    let used_by = self
+      .storage
+      .tasks()
+      .iter()
+      .filter(|task| task.project_id() == project_id)
+      .count();
+    if used_by > 0 {
+      return Err(Error::InUse(format!(
+        "project {} is used by {} tasks, remove them firstly",
+        project.name(),
+        used_by
+      )));
+    }
+
+    let before = self.state();
+    self
+      .storage
+      .move_to_trash(project_id, self.clock.now())
+      .map_err(Error::storage)?;
+    self.commit(
+      before,
+      &format!(
+        "remove project, name: {} id: {}",
+        project.name(),
+        project.id()
+      ),
+    );
+    Ok(())
+  }
+
+  pub fn remove_tag(&mut self, tag_id: uuid::Uuid) -> Result<(), Error> {
+    let _lock = self.lock()?;
+    let tag = match self.tag_by_id(tag_id) {
+      Some(tag) => tag,
+      None => return Err(Error::TagNotFound(tag_id)),
+    };
+    let used_by = self
+      .storage
+      .tasks()
+      .iter()
+      .filter(|task| task.tags().contains(&tag_id))
+      .count();
+    if used_by > 0 {
+      return Err(Error::InUse(format!(
+        "tag {} is used by {} tasks, remove them firstly",
+        tag.name(),
+        used_by
+      )));
+    }
+
+    let before = self.state();
+    self
+      .storage
+      .move_to_trash(tag_id, self.clock.now())
+      .map_err(Error::storage)?;
+    self.commit(
+      before,
+      &format!("remove tag, name: {} id: {}", tag.name(), tag.id()),
+    );
+    Ok(())
+  }
+
+  /// Removes the tag from every task which has it and moves it to the trash
+  /// in a single step. Returns the number of changed tasks.
+  pub fn remove_tag_cascade(&mut self, tag_id: uuid::Uuid) -> Result<usize, Error> {
+    let _lock = self.lock()?;
+    let tag = self.tag_by_id(tag_id).ok_or(Error::TagNotFound(tag_id))?;
+    let before = self.state();
+    let changed = self.retag_tasks(tag_id, None)?;
+    self
+      .storage
+      .move_to_trash(tag_id, self.clock.now())
+      .map_err(Error::storage)?;
+    self.commit(
+      before,
+      &format!(
+        "remove tag from {} tasks, name: {} id: {}",
+        changed,
+        tag.name(),
+        tag.id()
+      ),
+    );
+    Ok(changed)
+  }
+
+  pub fn rename_tag(&mut self, tag_name: &str, new_name: &str) -> Result<Tag, Error> {
+    let _lock = self.lock()?;
+    let mut tag = self.tag_by_name(tag_name)?;
+    if new_name.is_empty() {
+      return Err(Error::InvalidTag("name can't be empty".to_owned()));
+    }
+    if self.storage.find_tag_by_name(new_name).is_some() {
+      return Err(Error::InvalidTag(format!(
+        "tag {} already exists, merge the tags instead",
+        new_name
+      )));
+    }
+    tag.set_name(new_name);
+    let before = self.state();
+    self.storage.replace_tag(&tag).map_err(Error::storage)?;
+    self.commit(
+      before,
+      &format!("rename tag {} to {}, id: {}", tag_name, new_name, tag.id()),
+    );
+    Ok(tag)
+  }
+
+  /// Replaces `from_name` with `into_name` in every task and moves the
+  /// merged tag to the trash. Returns the number of changed tasks.
+  pub fn merge_tags(&mut self, from_name: &str, into_name: &str) -> Result<usize, Error> {
+    let _lock = self.lock()?;
+    let from = self.tag_by_name(from_name)?;
+    let into = self.tag_by_name(into_name)?;
+    if from.id() == into.id() {
+      return Err(Error::InvalidTag(format!(
+        "can't merge tag {} into itself",
+        from_name
+      )));
+    }
+    let before = self.state();
+    let changed = self.retag_tasks(from.id(), Some(into.id()))?;
+    self
+      .storage
+      .move_to_trash(from.id(), self.clock.now())
+      .map_err(Error::storage)?;
+    self.commit(
+      before,
+      &format!(
+        "merge tag {} into {} in {} tasks",
+        from_name, into_name, changed
+      ),
+    );
+    Ok(changed)
+  }
+
+  /// `None` clears the color.
+  pub fn set_tag_color(&mut self, tag_name: &str, color: Option<String>) -> Result<Tag, Error> {
+    let _lock = self.lock()?;
+    let mut tag = self.tag_by_name(tag_name)?;
+    tag.set_color(color).map_err(Error::InvalidTag)?;
+    let before = self.state();
+    self.storage.replace_tag(&tag).map_err(Error::storage)?;
+    self.commit(before, &format!("set color of tag {}", tag_name));
+    Ok(tag)
+  }
+
+  fn tag_by_name(&self, tag_name: &str) -> Result<Tag, Error> {
+    self
+      .storage
+      .find_tag_by_name(tag_name)
+      .ok_or_else(|| Error::UnknownTag(tag_name.to_owned()))
+  }
+
+  // Every task is written at once, so the tag is replaced everywhere or
+  // nowhere.
+  fn retag_tasks(
+    &mut self,
+    tag_id: uuid::Uuid,
+    new_id: Option<uuid::Uuid>,
+  ) -> Result<usize, Error> {
+    let mut changed = 0;
+    let tasks: Vec<Task> = self
+      .storage
+      .tasks()
+      .into_iter()
+      .map(|mut task| {
+        if !task.tags().contains(&tag_id) {
+          return task;
+        }
+        changed += 1;
+        let mut tags = Vec::new();
+        for id in task.tags().iter() {
+          let id = match *id == tag_id {
+            true => new_id,
+            false => Some(*id),
+          };
+          if let Some(id) = id.filter(|id| !tags.contains(id)) {
+            tags.push(id);
+          }
+        }
+        task.set_tags(tags);
+        task
+      })
+      .collect();
+    if changed > 0 {
+      self.storage.replace_tasks(tasks).map_err(Error::storage)?;
+    }
+    Ok(changed)
+  }
+
+  pub fn trash(&self) -> Trash {
+    self.storage.trash()
+  }
+
+  /// Resolves a short id of a trashed task, project or tag.
+  pub fn resolve_trash_id(&self, short_id: &str) -> Result<uuid::Uuid, Error> {
+    self.short_ids().resolve(short_id, None, true)
+  }
+
+  /// Restores a record from the trash. A restored task brings back
+  /// its project and tags if they were trashed too.
+  pub fn restore(&mut self, id: uuid::Uuid) -> Result<(), Error> {
+    let _lock = self.lock()?;
+    let trash = self.storage.trash();
+    let before = self.state();
+    if let Some(task) = trash.tasks.iter().find(|task| task.id() == id) {
+      if self.project_by_id(task.project_id()).is_none()
+        && !trash.projects.iter().any(|p| p.id() == task.project_id())
+      {
+        return Err(Error::ProjectNotFound(task.project_id()));
+      }
+      for project in trash
+        .projects
+        .iter()
+        .filter(|p| p.id() == task.project_id())
+      {
+        self
+          .storage
+          .restore_from_trash(project.id())
+          .map_err(Error::storage)?;
+      }
+      for tag in trash
+        .tags
+        .iter()
+        .filter(|tag| task.tags().contains(&tag.id()))
+      {
+        self
+          .storage
+          .restore_from_trash(tag.id())
+          .map_err(Error::storage)?;
+      }
     }
+    self
+      .storage
+      .restore_from_trash(id)
+      .map_err(Error::storage)?;
+    self.commit(before, &format!("restore id: {}", id));
+    Ok(())
+  }
+
+  /// Removes for good everything which was trashed more than `older_than` ago.
+  pub fn purge_trash(&mut self, older_than: chrono::Duration) -> Result<Trash, Error> {
+    let _lock = self.lock()?;
+    let before = self.state();
+    let purged = self
+      .storage
+      .purge_trash(self.clock.now() - older_than)
+      .map_err(Error::storage)?;
+    if !purged.is_empty() {
+      self.commit(before, &format!("purge {} items from trash", purged.len()));
+    }
+    Ok(purged)
+  }
+
+  pub fn undo(&mut self) -> Result<JournalEntry, Error> {
+    let _lock = self.lock()?;
+    let entry = match self.journal.last_undo() {
+      Some(entry) => entry.clone(),
+      None => return Err(Error::NothingToUndo),
+    };
+    journal::apply(self.storage.as_mut(), &entry.changes, true).map_err(Error::storage)?;
+    self.journal.move_to_redo().map_err(Error::Journal)?;
+    self.sync_commit(&format!("undo: {}", entry.description));
+    Ok(entry)
   }
 
-  pub fn remove_task(&mut self, task_id: uuid::Uuid) -> Result<(), String> {
-    self.storage.remove_task(task_id)
+  pub fn redo(&mut self) -> Result<JournalEntry, Error> {
+    let _lock = self.lock()?;
+    let entry = match self.journal.last_redo() {
+      Some(entry) => entry.clone(),
+      None => return Err(Error::NothingToRedo),
+    };
+    journal::apply(self.storage.as_mut(), &entry.changes, false).map_err(Error::storage)?;
+    self.journal.move_to_undo().map_err(Error::Journal)?;
+    self.sync_commit(&format!("redo: {}", entry.description));
+    Ok(entry)
   }
 
   pub fn tasks(&self, period: Period) -> Vec<Task> {
@@ -253,11 +1092,11 @@ impl Busy {
         }
         return within_the_period;
       })
-      .map(|t| t.clone())
+      .cloned()
       .collect()
   }
 
-  pub fn find_tags(&self, tag_ids: &Vec<uuid::Uuid>) -> Vec<Tag> {
+  pub fn find_tags(&self, tag_ids: &[uuid::Uuid]) -> Vec<Tag> {
     self.storage.find_tags(tag_ids)
   }
 
@@ -267,18 +1106,18 @@ impl Busy {
       .tasks()
       .iter()
       .find(|t| t.id() == task_id)
-      .map(|t| t.clone());
+      .cloned();
   }
 
+  /// Returns the running task or the latest paused one, several tasks can
+  /// be paused after switching between them.
   pub fn active_task(&self) -> Option<Task> {
     let tasks = self.storage.tasks();
     let found_task = tasks
       .iter()
-      .find(|t| t.stop_time().is_none() || t.is_paused());
-    match found_task {
-      Some(task) => Some(task.clone()),
-      None => None,
-    }
+      .find(|t| t.stop_time().is_none() && !t.is_paused())
+      .or_else(|| tasks.iter().rev().find(|t| t.is_paused()));
+    found_task.cloned()
   }
 
   pub fn projects(&self) -> Vec<Project> {
@@ -298,12 +1137,19 @@ impl Busy {
     })
   }
 
-  pub fn find_tag_by_names(&self, tags: &Vec<String>) -> Vec<Tag> {
+  pub fn find_tag_by_names(&self, tags: &[String]) -> Vec<Tag> {
     self.storage.find_tag_by_names(tags)
   }
 
-  pub fn replace_tag(&mut self, tag: &Tag) -> Result<(), String> {
-    self.storage.replace_tag(tag)
+  pub fn replace_tag(&mut self, tag: &Tag) -> Result<(), Error> {
+    let _lock = self.lock()?;
+    let before = self.state();
+    self.storage.replace_tag(tag).map_err(Error::storage)?;
+    self.commit(
+      before,
+      &format!("replace tag, name: {} id: {}", tag.name(), tag.id()),
+    );
+    Ok(())
   }
 
   pub fn all_tasks(&self) -> Vec<Task> {
@@ -314,28 +1160,34 @@ impl Busy {
     return self.storage.tags();
   }
 
-  pub fn replace_tags(&mut self, tags: Vec<Tag>) {
-    self.storage.replace_tags(tags);
-    self.commit("Edit all tags");
+  pub fn replace_tags(&mut self, tags: Vec<Tag>) -> Result<(), Error> {
+    let _lock = self.lock()?;
+    let before = self.state();
+    self.storage.replace_tags(tags).map_err(Error::storage)?;
+    self.commit(before, "Edit all tags");
+    Ok(())
   }
 
-  pub fn replace_tasks(&mut self, tasks: Vec<Task>) {
-    self.storage.replace_tasks(tasks);
-    self.commit("Edit all tasks");
+  pub fn replace_tasks(&mut self, tasks: Vec<Task>) -> Result<(), Error> {
+    let _lock = self.lock()?;
+    let before = self.state();
+    self.storage.replace_tasks(tasks).map_err(Error::storage)?;
+    self.commit(before, "Edit all tasks");
+    Ok(())
   }
 
-  fn add_project(&mut self, project_name: &str) -> Project {
+  fn add_project(&mut self, project_name: &str) -> Result<Project, Error> {
     let project = Project::new(project_name);
-    self.storage.add_project(&project);
-    return project;
+    self.storage.add_project(&project).map_err(Error::storage)?;
+    return Ok(project);
   }
 
-  fn upsert_project(&mut self, project_name: &str) -> Project {
+  fn upsert_project(&mut self, project_name: &str) -> Result<Project, Error> {
     let project = self.project_by_name(project_name);
     if project.is_none() {
       return self.add_project(project_name);
     }
-    return project.unwrap();
+    return Ok(project.unwrap());
   }
 
   pub fn project_by_name(&self, project_name: &str) -> Option<Project> {
@@ -347,6 +1199,17 @@ impl Busy {
     });
   }
 
+  /// The project named `project_name` and all its sub-projects. The parent
+  /// itself doesn't have to exist, e.g. `acme` finds `acme/backend`.
+  pub fn projects_within(&self, project_name: &str) -> Vec<Project> {
+    self
+      .storage
+      .projects()
+      .into_iter()
+      .filter(|project| project.is_within(project_name))
+      .collect()
+  }
+
   pub fn project_by_id(&self, project_id: uuid::Uuid) -> Option<Project> {
     self.storage.projects().iter().find_map(|c| {
       if c.id() == project_id {
@@ -356,14 +1219,190 @@ impl Busy {
     })
   }
 
-  fn commit(&mut self, msg: &str) {
-    match self.syncer.commit(msg) {
-      Err(err) => println!("commit err: {} msg: {}", err, msg),
-      _ => {}
+  /// Issues of the stored tasks, gaps are looked for if `min_gap` is set.
+  pub fn check(&self, min_gap: Option<chrono::Duration>) -> Vec<Issue> {
+    let tasks = self.storage.tasks();
+    let now = self.clock.now();
+    let mut issues = validation::check(&tasks, now);
+    if let Some(min_gap) = min_gap {
+      issues.extend(validation::gaps(&tasks, now, min_gap));
+    }
+    issues
+  }
+
+  pub fn propose_fixes(&self) -> Vec<Fix> {
+    validation::propose_fixes(&self.storage.tasks(), self.clock.now())
+  }
+
+  pub fn apply_fixes(&mut self, fixes: &[Fix]) -> Result<(), Error> {
+    let _lock = self.lock()?;
+    let before = self.state();
+    for fix in fixes.iter() {
+      self
+        .storage
+        .replace_task(&fix.task)
+        .map_err(Error::storage)?;
+    }
+    self.commit(before, &format!("fix {} task issues", fixes.len()));
+    Ok(())
+  }
+
+  /// Dangling references, duplicate ids and names in the storage.
+  pub fn diagnose(&self) -> Vec<Problem> {
+    doctor::diagnose(self.storage.as_ref())
+  }
+
+  /// Repairs what `diagnose` finds in a single step of the history and
+  /// returns descriptions of the repairs.
+  pub fn repair(&mut self) -> Result<Vec<String>, Error> {
+    let _lock = self.lock()?;
+    let before = self.state();
+    let repaired =
+      doctor::repair(self.storage.as_mut(), self.clock.now()).map_err(Error::storage)?;
+    self.commit(before, &format!("repair {} problems", repaired.len()));
+    Ok(repaired)
+  }
+
+  // Rejects the new or changed task if it breaks the checks, issues
+  // which were there before aren't its fault.
+  fn validate(&self, task: &Task) -> Result<(), Error> {
+    let mut tasks: Vec<Task> = self
+      .storage
+      .tasks()
+      .into_iter()
+      .filter(|stored| stored.id() != task.id())
+      .collect();
+    tasks.push(task.clone());
+    let issues = validation::check_task(task, &tasks, self.clock.now());
+    if issues.is_empty() {
+      return Ok(());
+    }
+    Err(Error::InvalidTask(
+      issues
+        .iter()
+        .map(|issue| {
+          let mut message = issue.to_string();
+          for id in issue.task_ids().into_iter().filter(|id| *id != task.id()) {
+            message = message.replace(&id.to_string(), &self.shorten_id(id));
+          }
+          message.replace(&format!("task {}", task.id()), "the task")
+        })
+        .collect::<Vec<String>>()
+        .join("; "),
+    ))
+  }
+
+  fn moment(
+    &self,
+    at: Option<chrono::DateTime<chrono::Local>>,
+  ) -> Result<chrono::DateTime<chrono::Local>, Error> {
+    let now = self.clock.now();
+    match at {
+      Some(at) if at > now => Err(Error::InvalidTime(format!(
+        "time {} is in the future",
+        at.format("%Y-%m-%d %H:%M")
+      ))),
+      Some(at) => Ok(at),
+      None => Ok(now),
+    }
+  }
+
+  // Storage opened from the config and the journal are read again under the
+  // lock, another busy process may have changed them.
+  fn lock(&mut self) -> Result<CommandLock, Error> {
+    let mut command_lock = CommandLock {
+      locked: Rc::clone(&self.locked),
+      lock: None,
+    };
+    if !self.reopen_storage || self.locked.get() {
+      return Ok(command_lock);
+    }
+    let lock_path = std::path::Path::new(&self.config.storage_dir_path).join(COMMAND_LOCK_FILE);
+    command_lock.lock = Some(
+      StorageLock::exclusive(&lock_path.to_string_lossy(), LOCK_TIMEOUT).map_err(Error::storage)?,
+    );
+    self.locked.set(true);
+    self.reopen_storage()?;
+    self.journal.reload().map_err(Error::Journal)?;
+    Ok(command_lock)
+  }
+
+  fn reopen_storage(&mut self) -> Result<(), Error> {
+    if self.reopen_storage {
+      self.storage = open_storage(&self.config, &self.clock).map_err(Error::storage)?;
+      self.short_ids.replace(None);
+    }
+    Ok(())
+  }
+
+  fn state(&self) -> State {
+    State::capture(self.storage.as_ref())
+  }
+
+  fn commit(&mut self, before: State, msg: &str) {
+    let changes = before.diff(&self.state());
+    if let Err(err) = self.journal.record(msg, changes, self.clock.now()) {
+      println!("journal err: {} msg: {}", err, msg)
+    };
+    self.sync_commit(msg);
+  }
+
+  fn sync_commit(&mut self, msg: &str) {
+    self.short_ids.replace(None);
+    if let Err(err) = self.syncer.commit(msg) {
+      println!("commit err: {} msg: {}", err, msg)
     };
   }
 }
 
+fn open_syncer(config: &Config) -> Result<Box<dyn Syncer>, Error> {
+  Ok(match config.syncer.clone() {
+    SyncerConfig::Empty => Box::new(EmptySyncer::new()),
+    SyncerConfig::Git {
+      remote,
+      remote_branch,
+      key_file,
+      merge_driver,
+    } => Box::new(
+      GitSyncer::new(
+        &config.storage_dir_path,
+        key_file,
+        Some(remote),
+        remote_branch,
+        merge_driver,
+      )
+      .map_err(Error::sync)?,
+    ),
+    SyncerConfig::Directory { path } => {
+      if !matches!(config.storage, StorageConfig::Json) {
+        return Err(Error::Config(
+          "directory syncer works only with the Json storage".to_string(),
+        ));
+      }
+      Box::new(DirectorySyncer::new(&config.storage_dir_path, &path))
+    }
+  })
+}
+
+fn open_storage(config: &Config, clock: &Rc<dyn Clock>) -> Result<Box<dyn Storage>, StorageError> {
+  let storage_dir_path = config.storage_dir_path.as_str();
+  match config.storage {
+    StorageConfig::Json => Ok(Box::new(JsonStorage::new(storage_dir_path)?)),
+    StorageConfig::Sqlite => {
+      let mut storage = SqliteStorage::new(storage_dir_path)?;
+      if storage.is_empty() && JsonStorage::exists(storage_dir_path) {
+        debug!("migrate json storage into: {}", storage.storage_path());
+        migrate(&JsonStorage::new(storage_dir_path)?, &mut storage)?;
+      }
+      Ok(Box::new(storage))
+    }
+    StorageConfig::EventLog => Ok(Box::new(EventLogStorage::new(
+      storage_dir_path,
+      Rc::clone(clock),
+    )?)),
+  }
+}
+
 fn format_task_commit(prefix: &str, task: &Task) -> String {
   format!(
     "{} task title: {} id: {} project: {}",
@@ -373,3 +1412,792 @@ fn format_task_commit(prefix: &str, task: &Task) -> String {
     task.project_id()
   )
 }
+
+#[cfg(test)]
+mod test {
+  use std::rc::Rc;
+
+  use chrono::TimeZone;
+
+  use super::{Busy, BusyBuilder};
+  use crate::{
+    clock::{Clock, ManualClock},
+    project::ProjectChanges,
+    short_id::IdKind,
+    storage::{InMemoryStorage, StorageConfig},
+    sync::{EmptySyncer, SyncerConfig},
+    task::Task,
+    time::DateTimeInterval,
+    traits::Indexable,
+    Config, Error, NewNames, SwitchMode,
+  };
+
+  fn in_memory_busy() -> Busy {
+    in_memory_busy_with_clock(Rc::new(ManualClock::new(chrono::Local::now())))
+  }
+
+  fn in_memory_busy_with_clock(clock: Rc<dyn Clock>) -> Busy {
+    in_memory_busy_with_config(test_config(), clock)
+  }
+
+  fn test_config() -> Config {
+    Config {
+      storage_dir_path: "/nonexistent/busy".to_owned(),
+      storage: StorageConfig::Json,
+      syncer: SyncerConfig::Empty,
+      switch: None,
+      strict: false,
+    }
+  }
+
+  fn in_memory_busy_with_config(config: Config, clock: Rc<dyn Clock>) -> Busy {
+    BusyBuilder::new(config)
+      .storage(Box::new(InMemoryStorage::new()))
+      .syncer(Box::new(EmptySyncer::new()))
+      .clock(clock)
+      .build()
+      .unwrap()
+  }
+
+  fn at(day: u32, hour: u32, minute: u32) -> chrono::DateTime<chrono::Local> {
+    chrono::Local
+      .with_ymd_and_hms(2024, 3, day, hour, minute, 0)
+      .unwrap()
+  }
+
+  fn clock() -> Rc<ManualClock> {
+    Rc::new(ManualClock::new(at(14, 9, 0)))
+  }
+
+  fn tags(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+  }
+
+  fn start_and_stop(busy: &mut Busy, project: &str, title: &str, tag_names: &[&str]) -> Task {
+    let task = busy
+      .start(project, title, tags(tag_names), None, NewNames::default())
+      .unwrap();
+    busy.stop(None).unwrap();
+    task
+  }
+
+  #[test]
+  fn busy_in_memory() {
+    let mut busy = in_memory_busy();
+    assert!(matches!(busy.stop(None), Err(Error::NoActiveTask)));
+
+    let task = busy
+      .start(
+        "project",
+        "title",
+        vec!["tag".to_owned()],
+        None,
+        NewNames::default(),
+      )
+      .unwrap();
+    assert!(matches!(
+      busy.start("project", "other", vec![], None, NewNames::default()),
+      Err(Error::ActiveTaskExists)
+    ));
+    busy.stop(None).unwrap();
+    assert_eq!(busy.projects().len(), 1);
+    assert_eq!(busy.tags().len(), 1);
+
+    busy.remove_task(task.id()).unwrap();
+    assert!(busy.all_tasks().is_empty());
+    assert_eq!(busy.trash().tasks.len(), 1);
+
+    busy.undo().unwrap();
+    assert_eq!(busy.all_tasks().len(), 1);
+    assert!(busy.trash().is_empty());
+  }
+
+  #[test]
+  fn busy_commands_see_other_processes() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = Config {
+      storage_dir_path: tmp_dir.path().to_str().unwrap().to_owned(),
+      ..test_config()
+    };
+    let open = || {
+      BusyBuilder::new(config.clone())
+        .clock(clock())
+        .build()
+        .unwrap()
+    };
+    let mut first = open();
+    let mut second = open();
+
+    first
+      .start("project", "first", vec![], None, NewNames::default())
+      .unwrap();
+    // the second one read the storage before, the check is done on fresh data
+    assert!(matches!(
+      second.start("project", "second", vec![], None, NewNames::default()),
+      Err(Error::ActiveTaskExists)
+    ));
+    second.stop(None).unwrap();
+    first
+      .start("project", "third", vec![], None, NewNames::default())
+      .unwrap();
+    assert_eq!(first.all_tasks().len(), 2);
+
+    // the history is shared as well
+    second.undo().unwrap();
+    assert_eq!(second.all_tasks().len(), 1);
+  }
+
+  #[test]
+  fn busy_resolve_id() {
+    let mut busy = in_memory_busy();
+    let task = busy
+      .start("project", "title", vec![], None, NewNames::default())
+      .unwrap();
+    let short_id = busy.shorten_id(task.id());
+    assert_eq!(short_id.len(), 4);
+    assert_eq!(busy.resolve_id(IdKind::Task, &short_id).unwrap(), task.id());
+    assert_eq!(
+      busy
+        .resolve_id(IdKind::Task, &task.id().to_string())
+        .unwrap(),
+      task.id()
+    );
+    assert!(matches!(
+      busy.resolve_id(IdKind::Tag, &short_id),
+      Err(Error::IdNotFound(_))
+    ));
+    assert!(matches!(
+      busy.resolve_id(IdKind::Task, "zzzz"),
+      Err(Error::IdNotFound(_))
+    ));
+
+    // the index is rebuilt after changes
+    busy.stop(None).unwrap();
+    busy.remove_task(task.id()).unwrap();
+    assert!(busy.resolve_id(IdKind::Task, &short_id).is_err());
+    assert_eq!(busy.resolve_trash_id(&short_id).unwrap(), task.id());
+  }
+
+  #[test]
+  fn busy_resolve_task_references() {
+    let clock = clock();
+    clock.set(at(14, 12, 0));
+    let mut busy = in_memory_busy_with_clock(clock.clone());
+    assert!(matches!(
+      busy.resolve_task("@active"),
+      Err(Error::NoActiveTask)
+    ));
+    assert!(matches!(
+      busy.resolve_task("@last"),
+      Err(Error::IdNotFound(_))
+    ));
+
+    let yesterday = busy
+      .add(
+        "project",
+        "yesterday",
+        vec![],
+        at(13, 10, 0),
+        at(13, 11, 0),
+        NewNames::default(),
+      )
+      .unwrap();
+    let second = busy
+      .add(
+        "project",
+        "second",
+        vec![],
+        at(14, 9, 0),
+        at(14, 9, 30),
+        NewNames::default(),
+      )
+      .unwrap();
+    let third = busy
+      .add(
+        "project",
+        "third",
+        vec![],
+        at(14, 10, 0),
+        at(14, 11, 30),
+        NewNames::default(),
+      )
+      .unwrap();
+    // added later, but started first today
+    let first = busy
+      .add(
+        "project",
+        "first",
+        vec![],
+        at(14, 8, 0),
+        at(14, 8, 15),
+        NewNames::default(),
+      )
+      .unwrap();
+    let active = busy
+      .start("project", "active", vec![], None, NewNames::default())
+      .unwrap();
+
+    let resolve = |reference: &str| busy.resolve_task(reference).unwrap();
+    assert_eq!(resolve("@active"), active.id());
+    assert_eq!(resolve("@last"), third.id());
+    assert_eq!(resolve("@-1"), active.id());
+    assert_eq!(resolve("@-2"), third.id());
+    assert_eq!(resolve("@-5"), yesterday.id());
+    assert_eq!(resolve("@today:1"), first.id());
+    assert_eq!(resolve("@today:2"), second.id());
+    assert_eq!(resolve("@today:4"), active.id());
+    assert_eq!(resolve(&busy.shorten_id(second.id())), second.id());
+
+    for missing in ["@-6", "@today:5"] {
+      assert!(matches!(
+        busy.resolve_task(missing),
+        Err(Error::IdNotFound(_))
+      ));
+    }
+    for invalid in ["@-0", "@today:0", "@today:x", "@first", "@"] {
+      assert!(matches!(
+        busy.resolve_task(invalid),
+        Err(Error::InvalidReference(_))
+      ));
+    }
+
+    clock.advance(chrono::Duration::minutes(30));
+    busy.pause(None).unwrap();
+    assert_eq!(busy.resolve_task("@active").unwrap(), active.id());
+    assert_eq!(busy.resolve_task("@last").unwrap(), third.id());
+    busy.resume(None).unwrap();
+    busy.stop(None).unwrap();
+    assert_eq!(busy.resolve_task("@last").unwrap(), active.id());
+  }
+
+  #[test]
+  fn busy_start_pause_resume_stop() {
+    let clock = clock();
+    let mut busy = in_memory_busy_with_clock(clock.clone());
+
+    let task = busy
+      .start("project", "title", vec![], None, NewNames::default())
+      .unwrap();
+    assert_eq!(task.start_time(), clock.now());
+    assert!(matches!(busy.resume(None), Err(Error::NoPausedTask)));
+
+    clock.advance(chrono::Duration::minutes(25));
+    let paused = busy.pause(None).unwrap();
+    assert!(paused.is_paused());
+    assert_eq!(paused.stop_time(), Some(clock.now()));
+    // a paused task is still the active one
+    assert_eq!(busy.active_task().unwrap().id(), task.id());
+
+    clock.advance(chrono::Duration::minutes(15));
+    let resumed = busy.resume(None).unwrap();
+    assert!(resumed.stop_time().is_none());
+
+    clock.advance(chrono::Duration::minutes(35));
+    let stopped = busy.stop(None).unwrap();
+    assert_eq!(stopped.stop_time(), Some(clock.now()));
+    assert_eq!(stopped.duration(clock.as_ref()), chrono::Duration::hours(1));
+    assert!(busy.active_task().is_none());
+    assert!(matches!(busy.pause(None), Err(Error::NoActiveTask)));
+
+    // stopping a paused task keeps the pause out of the work time
+    let paused = busy
+      .start("project", "paused", vec![], None, NewNames::default())
+      .unwrap();
+    clock.advance(chrono::Duration::minutes(10));
+    busy.pause(None).unwrap();
+    let paused_at = clock.now();
+    clock.advance(chrono::Duration::hours(1));
+    let stopped = busy.stop(None).unwrap();
+    assert_eq!(stopped.id(), paused.id());
+    assert!(!stopped.is_paused());
+    assert_eq!(stopped.stop_time(), Some(paused_at));
+    assert_eq!(
+      stopped.duration(clock.as_ref()),
+      chrono::Duration::minutes(10)
+    );
+
+    let continued = busy.continue_task(task.id()).unwrap();
+    assert_eq!(continued.title(), "title");
+    assert_eq!(continued.start_time(), clock.now());
+  }
+
+  #[test]
+  fn busy_trash_uses_clock() {
+    let clock = clock();
+    let mut busy = in_memory_busy_with_clock(clock.clone());
+    let task = start_and_stop(&mut busy, "project", "title", &[]);
+    busy.remove_task(task.id()).unwrap();
+
+    clock.advance(chrono::Duration::days(10));
+    assert!(busy
+      .purge_trash(chrono::Duration::days(30))
+      .unwrap()
+      .is_empty());
+    clock.advance(chrono::Duration::days(21));
+    assert_eq!(
+      busy
+        .purge_trash(chrono::Duration::days(30))
+        .unwrap()
+        .tasks
+        .len(),
+      1
+    );
+  }
+
+  #[test]
+  fn busy_backdated_stop_and_resume() {
+    let clock = clock();
+    let start = clock.now();
+    let mut busy = in_memory_busy_with_clock(clock.clone());
+    busy
+      .start("project", "title", vec![], None, NewNames::default())
+      .unwrap();
+    clock.advance(chrono::Duration::hours(4));
+
+    let before_start = start - chrono::Duration::minutes(1);
+    assert!(matches!(
+      busy.pause(Some(before_start)),
+      Err(Error::InvalidTime(_))
+    ));
+    assert!(matches!(
+      busy.pause(Some(clock.now() + chrono::Duration::minutes(1))),
+      Err(Error::InvalidTime(_))
+    ));
+
+    let lunch = start + chrono::Duration::hours(3);
+    let paused = busy.pause(Some(lunch)).unwrap();
+    assert_eq!(paused.stop_time(), Some(lunch));
+
+    // resuming inside the paused interval would overlap it
+    assert!(matches!(
+      busy.resume(Some(lunch - chrono::Duration::minutes(5))),
+      Err(Error::InvalidTime(_))
+    ));
+    let back = lunch + chrono::Duration::minutes(30);
+    let resumed = busy.resume(Some(back)).unwrap();
+    assert_eq!(resumed.times().len(), 2);
+    assert_eq!(resumed.times()[1].start_time, back);
+
+    assert!(matches!(
+      busy.stop(Some(back - chrono::Duration::minutes(1))),
+      Err(Error::InvalidTime(_))
+    ));
+    let stopped = busy
+      .stop(Some(back + chrono::Duration::minutes(20)))
+      .unwrap();
+    assert_eq!(
+      stopped.duration(clock.as_ref()),
+      chrono::Duration::minutes(200)
+    );
+  }
+
+  #[test]
+  fn busy_switch() {
+    let clock = clock();
+    let start = clock.now();
+    let mut busy = in_memory_busy_with_clock(clock.clone());
+    let first = busy
+      .start("project", "first", vec![], None, NewNames::default())
+      .unwrap();
+
+    clock.advance(chrono::Duration::hours(1));
+    // a failed switch leaves everything as it was
+    assert!(matches!(
+      busy.switch(
+        "other",
+        "second",
+        vec!["tag".to_owned()],
+        Some(start - chrono::Duration::minutes(5)),
+        SwitchMode::Stop,
+        NewNames::default()
+      ),
+      Err(Error::InvalidTime(_))
+    ));
+    assert!(busy.active_task().unwrap().stop_time().is_none());
+    assert_eq!(busy.projects().len(), 1);
+    assert!(busy.tags().is_empty());
+
+    let switched = busy
+      .switch(
+        "project",
+        "second",
+        vec![],
+        None,
+        SwitchMode::Stop,
+        NewNames::default(),
+      )
+      .unwrap();
+    let previous = switched.previous.unwrap();
+    assert_eq!(previous.id(), first.id());
+    assert_eq!(previous.stop_time(), Some(clock.now()));
+    assert_eq!(switched.started.start_time(), clock.now());
+    assert_eq!(busy.active_task().unwrap().id(), switched.started.id());
+
+    // the switch is a single step of the history
+    busy.undo().unwrap();
+    assert_eq!(busy.active_task().unwrap().id(), first.id());
+    assert_eq!(busy.all_tasks().len(), 1);
+  }
+
+  #[test]
+  fn busy_switch_with_pause() {
+    let clock = clock();
+    let mut busy = in_memory_busy_with_clock(clock.clone());
+    let first = busy
+      .start("project", "first", vec![], None, NewNames::default())
+      .unwrap();
+    clock.advance(chrono::Duration::minutes(30));
+
+    let switched = busy.switch_to_task(first.id(), SwitchMode::Pause).unwrap();
+    assert!(switched.previous.unwrap().is_paused());
+    assert_eq!(busy.active_task().unwrap().id(), switched.started.id());
+
+    // the paused task becomes active again once the new one is stopped
+    clock.advance(chrono::Duration::minutes(10));
+    busy.stop(None).unwrap();
+    assert_eq!(busy.active_task().unwrap().id(), first.id());
+    let resumed = busy.resume(None).unwrap();
+    assert_eq!(resumed.times().len(), 2);
+  }
+
+  #[test]
+  fn busy_rejects_and_fixes_overlaps() {
+    let clock = clock();
+    clock.set(at(14, 18, 0));
+    let mut busy = in_memory_busy_with_clock(clock.clone());
+    let at = |hour, minute| at(14, hour, minute);
+    let first = busy
+      .add(
+        "project",
+        "first",
+        vec![],
+        at(9, 0),
+        at(11, 0),
+        NewNames::default(),
+      )
+      .unwrap();
+    assert!(matches!(
+      busy.add(
+        "other",
+        "second",
+        vec![],
+        at(10, 0),
+        at(12, 0),
+        NewNames::default()
+      ),
+      Err(Error::InvalidTask(_))
+    ));
+    assert!(matches!(
+      busy.add(
+        "other",
+        "negative",
+        vec![],
+        at(13, 0),
+        at(12, 0),
+        NewNames::default()
+      ),
+      Err(Error::InvalidTask(_))
+    ));
+    // rejected tasks don't leave their projects behind
+    assert_eq!(busy.projects().len(), 1);
+
+    let second = busy
+      .add(
+        "project",
+        "second",
+        vec![],
+        at(11, 0),
+        at(12, 0),
+        NewNames::default(),
+      )
+      .unwrap();
+    assert!(busy.check(None).is_empty());
+    assert_eq!(busy.check(Some(chrono::Duration::minutes(15))).len(), 0);
+
+    let mut broken = busy.task_by_id(second.id()).unwrap();
+    broken.set_times(vec![DateTimeInterval {
+      start_time: at(10, 30),
+      stop_time: Some(at(12, 0)),
+    }]);
+    assert!(matches!(
+      busy.replace_task(&broken),
+      Err(Error::InvalidTask(_))
+    ));
+
+    // data which got broken some other way is repaired by the fixes
+    busy
+      .replace_tasks(vec![busy.task_by_id(first.id()).unwrap(), broken])
+      .unwrap();
+    assert_eq!(busy.check(None).len(), 1);
+    let fixes = busy.propose_fixes();
+    assert_eq!(fixes.len(), 1);
+    busy.apply_fixes(&fixes).unwrap();
+    assert!(busy.check(None).is_empty());
+
+    busy.undo().unwrap();
+    assert_eq!(busy.check(None).len(), 1);
+  }
+
+  #[test]
+  fn busy_project_metadata() {
+    let mut busy = in_memory_busy();
+    busy
+      .start("project", "title", vec![], None, NewNames::default())
+      .unwrap();
+    assert!(matches!(
+      busy.update_project("missing", ProjectChanges::default()),
+      Err(Error::UnknownProject(_))
+    ));
+    assert!(matches!(
+      busy.update_project(
+        "project",
+        ProjectChanges {
+          color: Some("no-such-color".to_owned()),
+          ..Default::default()
+        }
+      ),
+      Err(Error::InvalidProject(_))
+    ));
+
+    let project = busy
+      .update_project(
+        "project",
+        ProjectChanges {
+          description: Some("description".to_owned()),
+          client: Some("client".to_owned()),
+          color: Some("blue".to_owned()),
+        },
+      )
+      .unwrap();
+    assert_eq!(project.client(), Some("client"));
+    let project = busy
+      .update_project(
+        "project",
+        ProjectChanges {
+          client: Some(String::new()),
+          ..Default::default()
+        },
+      )
+      .unwrap();
+    assert_eq!(project.client(), None);
+    assert_eq!(project.description(), Some("description"));
+
+    busy.archive_project("project", true).unwrap();
+    assert!(busy.project_by_name("project").unwrap().is_archived());
+    assert_eq!(busy.all_tasks().len(), 1);
+    busy.undo().unwrap();
+    assert!(!busy.project_by_name("project").unwrap().is_archived());
+  }
+
+  #[test]
+  fn busy_projects_within() {
+    let mut busy = in_memory_busy();
+    for project in ["acme/backend/api", "acme/frontend", "acme-labs", "acme"] {
+      start_and_stop(&mut busy, project, "title", &[]);
+    }
+    let names = |name: &str| {
+      busy
+        .projects_within(name)
+        .iter()
+        .map(|project| project.name().to_owned())
+        .collect::<Vec<String>>()
+    };
+    assert_eq!(names("acme"), ["acme/backend/api", "acme/frontend", "acme"]);
+    assert_eq!(names("acme/backend"), ["acme/backend/api"]);
+    assert_eq!(names("acme/"), names("acme"));
+    assert!(names("acm").is_empty());
+
+    let api = busy.project_by_name("acme/backend/api").unwrap();
+    assert_eq!(api.path(), ["acme", "backend", "api"]);
+    assert_eq!(api.parent_name(), Some("acme/backend"));
+  }
+
+  #[test]
+  fn busy_strict_names() {
+    let config = Config {
+      strict: true,
+      ..test_config()
+    };
+    let mut busy = in_memory_busy_with_config(config, clock());
+    let all_new = NewNames {
+      project: true,
+      tags: true,
+    };
+    assert!(matches!(
+      busy.start("acme-backend", "first", vec![], None, NewNames::default()),
+      Err(Error::NewProject(_, None))
+    ));
+
+    busy
+      .start("acme-backend", "first", tags(&["review"]), None, all_new)
+      .unwrap();
+    busy.stop(None).unwrap();
+
+    // the permission is given per call
+    match busy.start("acme_backend", "second", vec![], None, NewNames::default()) {
+      Err(Error::NewProject(name, suggestion)) => {
+        assert_eq!(name, "acme_backend");
+        assert_eq!(suggestion.as_deref(), Some("acme-backend"));
+      }
+      _ => panic!("unknown project is accepted"),
+    }
+    match busy.start(
+      "acme-backend",
+      "second",
+      tags(&["reveiw"]),
+      None,
+      NewNames::default(),
+    ) {
+      Err(Error::NewTag(name, suggestion)) => {
+        assert_eq!(name, "reveiw");
+        assert_eq!(suggestion.as_deref(), Some("review"));
+      }
+      _ => panic!("unknown tag is accepted"),
+    }
+    // nothing is created by refused tasks
+    assert_eq!(busy.projects().len(), 1);
+    assert_eq!(busy.tags().len(), 1);
+
+    let new_tags = NewNames {
+      project: false,
+      tags: true,
+    };
+    busy
+      .start(
+        "acme-backend",
+        "second",
+        tags(&["review", "urgent"]),
+        None,
+        new_tags,
+      )
+      .unwrap();
+    assert_eq!(busy.tags().len(), 2);
+  }
+
+  #[test]
+  fn busy_manage_tags() {
+    let mut busy = in_memory_busy();
+    let first = start_and_stop(&mut busy, "project", "first", &["review", "code-review"]);
+    start_and_stop(&mut busy, "project", "second", &["review", "urgent"]);
+
+    assert!(matches!(
+      busy.rename_tag("review", "urgent"),
+      Err(Error::InvalidTag(_))
+    ));
+    assert!(matches!(
+      busy.merge_tags("missing", "review"),
+      Err(Error::UnknownTag(_))
+    ));
+    busy.rename_tag("code-review", "cr").unwrap();
+
+    assert_eq!(busy.merge_tags("review", "cr").unwrap(), 2);
+    let cr = busy.find_tag_by_names(&tags(&["cr"]))[0].clone();
+    let first = busy.task_by_id(first.id()).unwrap();
+    assert_eq!(first.tags(), &vec![cr.id()]);
+    assert_eq!(busy.tags().len(), 2);
+    // the merge is a single step of the history
+    busy.undo().unwrap();
+    assert_eq!(busy.tags().len(), 3);
+    busy.redo().unwrap();
+
+    let urgent = busy.find_tag_by_names(&tags(&["urgent"]))[0].clone();
+    assert!(matches!(busy.remove_tag(urgent.id()), Err(Error::InUse(_))));
+    assert_eq!(busy.remove_tag_cascade(urgent.id()).unwrap(), 1);
+    assert!(busy
+      .all_tasks()
+      .iter()
+      .all(|task| task.tags() == &vec![cr.id()]));
+
+    assert!(busy.set_tag_color("cr", Some("violet".to_owned())).is_err());
+    let cr = busy
+      .set_tag_color("cr", Some("magenta".to_owned()))
+      .unwrap();
+    assert_eq!(cr.color(), Some("magenta"));
+  }
+
+  #[test]
+  fn busy_manage_projects() {
+    let mut busy = in_memory_busy();
+    for project in ["acme", "acme/api", "acme-backend", "acme_backend"] {
+      start_and_stop(&mut busy, project, "title", &[]);
+    }
+
+    let acme = busy.project_by_name("acme").unwrap();
+    assert!(matches!(
+      busy.rename_project("acme", "acme-backend"),
+      Err(Error::InvalidProject(_))
+    ));
+    let renamed = busy.rename_project("acme", "acme-corp").unwrap();
+    assert_eq!(renamed.id(), acme.id());
+    assert_eq!(renamed.name(), "acme-corp");
+    assert!(busy.project_by_name("acme-corp/api").is_some());
+    assert!(busy.project_by_name("acme/api").is_none());
+
+    let into = busy.project_by_name("acme_backend").unwrap();
+    assert!(matches!(
+      busy.remove_project(busy.project_by_name("acme-backend").unwrap().id()),
+      Err(Error::InUse(_))
+    ));
+    assert_eq!(
+      busy.merge_projects("acme-backend", "acme_backend").unwrap(),
+      1
+    );
+    assert!(busy.project_by_name("acme-backend").is_none());
+    assert_eq!(
+      busy
+        .all_tasks()
+        .iter()
+        .filter(|task| task.project_id() == into.id())
+        .count(),
+      2
+    );
+    assert_eq!(busy.trash().projects.len(), 1);
+    busy.undo().unwrap();
+    assert!(busy.project_by_name("acme-backend").is_some());
+  }
+
+  #[test]
+  fn busy_merge_project_tree() {
+    let mut busy = in_memory_busy();
+    for project in [
+      "acme",
+      "acme/api",
+      "acme/api/v2",
+      "acme/web",
+      "other",
+      "other/web",
+    ] {
+      start_and_stop(&mut busy, project, "title", &[]);
+    }
+    assert!(matches!(
+      busy.merge_projects("acme", "acme/api"),
+      Err(Error::InvalidProject(_))
+    ));
+
+    let other_web = busy.project_by_name("other/web").unwrap();
+    let api = busy.project_by_name("acme/api").unwrap();
+    assert_eq!(busy.merge_projects("acme", "other").unwrap(), 2);
+    let mut names: Vec<String> = busy
+      .projects()
+      .iter()
+      .map(|project| project.name().to_owned())
+      .collect();
+    names.sort();
+    assert_eq!(names, ["other", "other/api", "other/api/v2", "other/web"]);
+    // moved sub-projects keep their ids, existing ones get the tasks
+    assert_eq!(busy.project_by_name("other/api").unwrap().id(), api.id());
+    assert_eq!(
+      busy
+        .all_tasks()
+        .iter()
+        .filter(|task| task.project_id() == other_web.id())
+        .count(),
+      2
+    );
+    assert_eq!(busy.trash().projects.len(), 2);
+
+    busy.undo().unwrap();
+    assert!(busy.project_by_name("acme/web").is_some());
+    assert!(busy.project_by_name("acme/api/v2").is_some());
+  }
+}
@@ -0,0 +1,43 @@
+/// Source of the current time. Everything busy does with "now" goes through
+/// a clock, so it can be replaced in tests or to recalculate the past.
+pub trait Clock {
+  fn now(&self) -> chrono::DateTime<chrono::Local>;
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+  fn now(&self) -> chrono::DateTime<chrono::Local> {
+    chrono::Local::now()
+  }
+}
+
+/// Clock which stays at the given moment until it's moved with `set`
+/// or `advance`.
+#[derive(Debug)]
+pub struct ManualClock {
+  now: std::cell::Cell<chrono::DateTime<chrono::Local>>,
+}
+
+impl ManualClock {
+  pub fn new(now: chrono::DateTime<chrono::Local>) -> Self {
+    Self {
+      now: std::cell::Cell::new(now),
+    }
+  }
+
+  pub fn set(&self, now: chrono::DateTime<chrono::Local>) {
+    self.now.set(now);
+  }
+
+  pub fn advance(&self, duration: chrono::Duration) {
+    self.now.set(self.now.get() + duration);
+  }
+}
+
+impl Clock for ManualClock {
+  fn now(&self) -> chrono::DateTime<chrono::Local> {
+    self.now.get()
+  }
+}
@@ -0,0 +1,10 @@
+/// Checks a color name of projects and tags, the names are the ones
+/// `colored` knows, e.g. `red`, `blue` or `bright magenta`.
+pub fn parse_color(color: &str) -> Result<colored::Color, String> {
+  color.parse().map_err(|_| {
+    format!(
+      "unknown color '{}', expected e.g. red, blue or bright green",
+      color
+    )
+  })
+}
@@ -1,48 +1,80 @@
-use crate::sync::SyncerConfig;
+use crate::{storage::StorageConfig, sync::SyncerConfig, Error};
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct Config {
   pub storage_dir_path: String,
+  #[serde(default)]
+  pub storage: StorageConfig,
   pub syncer: SyncerConfig,
+  /// When set, `busy start` and `busy continue` switch from the active task
+  /// instead of refusing to start.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub switch: Option<SwitchMode>,
+  /// When set, unknown projects and tags are refused instead of being
+  /// created, so typos don't create new ones.
+  #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+  pub strict: bool,
+}
+
+/// What happens with the active task when another one is started.
+#[derive(Debug, Default, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum SwitchMode {
+  #[default]
+  Stop,
+  Pause,
 }
 
 impl Config {
-  pub fn new() -> Self {
+  pub fn new() -> Result<Self, Error> {
     const BUSY_DEFAULT_STORAGE_DIR: &str = ".busy";
     const BUSY_DEFAULT_CONFIG_PATH: &str = ".config/busy/config.json";
 
-    let home_env = std::env::var("HOME").unwrap();
+    let home_env =
+      std::env::var("HOME").map_err(|_| Error::Config("HOME env isn't set".to_string()))?;
     let home = std::path::Path::new(home_env.as_str());
 
     let config_file_path = match std::env::var("BUSY_CONFIG") {
       Ok(file_path) => std::path::Path::new(&file_path).to_path_buf(),
       Err(_) => home.join(BUSY_DEFAULT_CONFIG_PATH),
     };
+    let config_error = |err: &dyn std::fmt::Display| {
+      Error::Config(format!(
+        "config file: {}, err: {}",
+        config_file_path.display(),
+        err
+      ))
+    };
 
-    let get_config_file = || {
-      std::fs::create_dir_all(config_file_path.parent().unwrap()).unwrap();
+    let get_config_file = || -> Result<std::fs::File, Error> {
+      if let Some(config_folder) = config_file_path.parent() {
+        std::fs::create_dir_all(config_folder).map_err(|err| config_error(&err))?;
+      }
       std::fs::File::options()
         .create(true)
+        .truncate(false)
         .write(true)
         .read(true)
         .open(config_file_path.clone())
-        .unwrap()
+        .map_err(|err| config_error(&err))
     };
 
     if !config_file_path.exists() {
       let config = Self {
         storage_dir_path: home
           .join(BUSY_DEFAULT_STORAGE_DIR)
-          .to_str()
-          .unwrap()
-          .to_owned(),
+          .to_string_lossy()
+          .to_string(),
+        storage: StorageConfig::Json,
         syncer: SyncerConfig::Empty,
+        switch: None,
+        strict: false,
       };
 
-      serde_json::to_writer_pretty(get_config_file(), &config).unwrap();
-      return config;
+      serde_json::to_writer_pretty(get_config_file()?, &config)
+        .map_err(|err| config_error(&err))?;
+      return Ok(config);
     }
 
-    return serde_json::from_reader(get_config_file()).unwrap();
+    return serde_json::from_reader(get_config_file()?).map_err(|err| config_error(&err));
   }
 }
@@ -0,0 +1,492 @@
+//! Integrity checks of the whole storage: references between records,
+//! duplicate ids and names, and repairs of what's found.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+  project::Project,
+  storage::{Storage, StorageError},
+  task::Task,
+  traits::Indexable,
+};
+
+// Tasks which lost their project are linked to the project with this name,
+// it's the name the viewer shows for them anyway.
+const DEFAULT_PROJECT_NAME: &str = "default";
+
+// Bounds the repair loop, every repair removes a problem.
+const MAX_REPAIRS: usize = 10000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+  /// Several tasks share the id.
+  DuplicateTaskId { task_id: uuid::Uuid },
+  /// Several projects share the id.
+  DuplicateProjectId { project_id: uuid::Uuid },
+  /// Several tags share the id.
+  DuplicateTagId { tag_id: uuid::Uuid },
+  /// Task refers to a project which doesn't exist or is in the trash.
+  MissingProject {
+    task_id: uuid::Uuid,
+    project_id: uuid::Uuid,
+  },
+  /// Task refers to a tag which doesn't exist or is in the trash.
+  MissingTag {
+    task_id: uuid::Uuid,
+    tag_id: uuid::Uuid,
+  },
+  /// Several projects have the same name.
+  DuplicateProjectName {
+    name: String,
+    project_ids: Vec<uuid::Uuid>,
+  },
+  /// Several tags have the same name.
+  DuplicateTagName {
+    name: String,
+    tag_ids: Vec<uuid::Uuid>,
+  },
+}
+
+impl Problem {
+  pub fn ids(&self) -> Vec<uuid::Uuid> {
+    match self {
+      Problem::DuplicateTaskId { task_id } => vec![*task_id],
+      Problem::DuplicateProjectId { project_id } => vec![*project_id],
+      Problem::DuplicateTagId { tag_id } => vec![*tag_id],
+      Problem::MissingProject {
+        task_id,
+        project_id,
+      } => vec![*task_id, *project_id],
+      Problem::MissingTag { task_id, tag_id } => vec![*task_id, *tag_id],
+      Problem::DuplicateProjectName { project_ids, .. } => project_ids.clone(),
+      Problem::DuplicateTagName { tag_ids, .. } => tag_ids.clone(),
+    }
+  }
+}
+
+impl std::fmt::Display for Problem {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Problem::DuplicateTaskId { task_id } => {
+        write!(f, "several tasks have id {}", task_id)
+      }
+      Problem::DuplicateProjectId { project_id } => {
+        write!(f, "several projects have id {}", project_id)
+      }
+      Problem::DuplicateTagId { tag_id } => write!(f, "several tags have id {}", tag_id),
+      Problem::MissingProject {
+        task_id,
+        project_id,
+      } => write!(
+        f,
+        "task {} refers to missing project {}",
+        task_id, project_id
+      ),
+      Problem::MissingTag { task_id, tag_id } => {
+        write!(f, "task {} refers to missing tag {}", task_id, tag_id)
+      }
+      Problem::DuplicateProjectName { name, project_ids } => write!(
+        f,
+        "projects {} have the same name '{}'",
+        join_ids(project_ids),
+        name
+      ),
+      Problem::DuplicateTagName { name, tag_ids } => write!(
+        f,
+        "tags {} have the same name '{}'",
+        join_ids(tag_ids),
+        name
+      ),
+    }
+  }
+}
+
+/// Scans tasks, projects and tags which aren't in the trash.
+pub fn diagnose(storage: &dyn Storage) -> Vec<Problem> {
+  let tasks = storage.tasks();
+  let projects = storage.projects();
+  let tags = storage.tags();
+  let mut problems = Vec::new();
+
+  problems.extend(
+    duplicates(tasks.iter().map(|task| task.id()))
+      .into_iter()
+      .map(|task_id| Problem::DuplicateTaskId { task_id }),
+  );
+  problems.extend(
+    duplicates(projects.iter().map(|project| project.id()))
+      .into_iter()
+      .map(|project_id| Problem::DuplicateProjectId { project_id }),
+  );
+  problems.extend(
+    duplicates(tags.iter().map(|tag| tag.id()))
+      .into_iter()
+      .map(|tag_id| Problem::DuplicateTagId { tag_id }),
+  );
+
+  let project_ids: HashSet<uuid::Uuid> = projects.iter().map(|project| project.id()).collect();
+  let tag_ids: HashSet<uuid::Uuid> = tags.iter().map(|tag| tag.id()).collect();
+  for task in tasks.iter() {
+    if !project_ids.contains(&task.project_id()) {
+      problems.push(Problem::MissingProject {
+        task_id: task.id(),
+        project_id: task.project_id(),
+      });
+    }
+    for tag_id in task.tags().iter().filter(|id| !tag_ids.contains(id)) {
+      problems.push(Problem::MissingTag {
+        task_id: task.id(),
+        tag_id: *tag_id,
+      });
+    }
+  }
+
+  for (name, project_ids) in same_names(projects.iter().map(|p| (p.name(), p.id()))) {
+    problems.push(Problem::DuplicateProjectName { name, project_ids });
+  }
+  for (name, tag_ids) in same_names(tags.iter().map(|tag| (tag.name(), tag.id()))) {
+    problems.push(Problem::DuplicateTagName { name, tag_ids });
+  }
+  return problems;
+}
+
+/// Repairs every problem `diagnose` finds and describes what was done:
+/// duplicate records are dropped or get new ids, references are linked to
+/// restored or existing records, dangling tags are dropped and records
+/// with the same name are merged into the first one.
+pub fn repair(
+  storage: &mut dyn Storage,
+  now: chrono::DateTime<chrono::Local>,
+) -> Result<Vec<String>, StorageError> {
+  let mut repaired = Vec::new();
+  while repaired.len() < MAX_REPAIRS {
+    let problem = match diagnose(storage).into_iter().next() {
+      Some(problem) => problem,
+      None => break,
+    };
+    repaired.push(repair_problem(storage, &problem, now)?);
+  }
+  Ok(repaired)
+}
+
+fn repair_problem(
+  storage: &mut dyn Storage,
+  problem: &Problem,
+  now: chrono::DateTime<chrono::Local>,
+) -> Result<String, StorageError> {
+  match problem {
+    Problem::DuplicateTaskId { task_id } => {
+      let mut tasks = storage.tasks();
+      let copies: Vec<usize> = (0..tasks.len())
+        .filter(|position| tasks[*position].id() == *task_id)
+        .collect();
+      if copies[1..]
+        .iter()
+        .all(|position| tasks[*position] == tasks[copies[0]])
+      {
+        for _ in copies[1..].iter() {
+          storage.remove_task(*task_id)?;
+        }
+        return Ok(format!(
+          "drop copies of task '{}'",
+          tasks[copies[0]].title()
+        ));
+      }
+      for position in copies[1..].iter() {
+        let task = &tasks[*position];
+        tasks[*position] = Task::from_parts(
+          uuid::Uuid::new_v4(),
+          task.project_id(),
+          task.times().clone(),
+          task.title(),
+          task.tags().clone(),
+          task.is_paused(),
+        );
+      }
+      let title = tasks[copies[0]].title().to_owned();
+      storage.replace_tasks(tasks)?;
+      Ok(format!("give new ids to copies of task '{}'", title))
+    }
+    // projects and tags with the same id can't be told apart by tasks,
+    // so only one of them is kept
+    Problem::DuplicateProjectId { project_id } => {
+      let project = storage
+        .projects()
+        .into_iter()
+        .find(|p| p.id() == *project_id)
+        .unwrap();
+      storage.remove_project(*project_id)?;
+      Ok(format!("drop a copy of project '{}'", project.name()))
+    }
+    Problem::DuplicateTagId { tag_id } => {
+      let tag = storage
+        .tags()
+        .into_iter()
+        .find(|tag| tag.id() == *tag_id)
+        .unwrap();
+      storage.remove_tag(*tag_id)?;
+      Ok(format!("drop a copy of tag '{}'", tag.name()))
+    }
+    Problem::MissingProject {
+      task_id,
+      project_id,
+    } => {
+      if let Some(project) = storage
+        .trash()
+        .projects
+        .into_iter()
+        .find(|p| p.id() == *project_id)
+      {
+        storage.restore_from_trash(project.id())?;
+        return Ok(format!(
+          "restore project '{}' from the trash",
+          project.name()
+        ));
+      }
+      let default_project = match storage
+        .projects()
+        .into_iter()
+        .find(|p| p.name() == DEFAULT_PROJECT_NAME)
+      {
+        Some(project) => project,
+        None => {
+          let project = Project::new(DEFAULT_PROJECT_NAME);
+          storage.add_project(&project)?;
+          project
+        }
+      };
+      let mut task = task_by_id(storage, *task_id)?;
+      task.set_project_id(default_project.id());
+      storage.replace_task(&task)?;
+      Ok(format!(
+        "link task '{}' to project '{}'",
+        task.title(),
+        DEFAULT_PROJECT_NAME
+      ))
+    }
+    Problem::MissingTag { task_id, tag_id } => {
+      if let Some(tag) = storage
+        .trash()
+        .tags
+        .into_iter()
+        .find(|tag| tag.id() == *tag_id)
+      {
+        storage.restore_from_trash(tag.id())?;
+        return Ok(format!("restore tag '{}' from the trash", tag.name()));
+      }
+      let mut task = task_by_id(storage, *task_id)?;
+      let tags = task
+        .tags()
+        .iter()
+        .filter(|id| *id != tag_id)
+        .cloned()
+        .collect();
+      task.set_tags(tags);
+      storage.replace_task(&task)?;
+      Ok(format!("drop missing tag of task '{}'", task.title()))
+    }
+    Problem::DuplicateProjectName { name, project_ids } => {
+      let (kept, merged) = project_ids.split_first().unwrap();
+      for mut task in storage.tasks() {
+        if merged.contains(&task.project_id()) {
+          task.set_project_id(*kept);
+          storage.replace_task(&task)?;
+        }
+      }
+      for project_id in merged.iter() {
+        storage.move_to_trash(*project_id, now)?;
+      }
+      Ok(format!(
+        "merge {} projects named '{}'",
+        project_ids.len(),
+        name
+      ))
+    }
+    Problem::DuplicateTagName { name, tag_ids } => {
+      let (kept, merged) = tag_ids.split_first().unwrap();
+      for mut task in storage.tasks() {
+        if !task.tags().iter().any(|id| merged.contains(id)) {
+          continue;
+        }
+        let mut tags = Vec::new();
+        for id in task.tags().iter() {
+          let id = if merged.contains(id) { *kept } else { *id };
+          if !tags.contains(&id) {
+            tags.push(id);
+          }
+        }
+        task.set_tags(tags);
+        storage.replace_task(&task)?;
+      }
+      for tag_id in merged.iter() {
+        storage.move_to_trash(*tag_id, now)?;
+      }
+      Ok(format!("merge {} tags named '{}'", tag_ids.len(), name))
+    }
+  }
+}
+
+fn task_by_id(storage: &dyn Storage, id: uuid::Uuid) -> Result<Task, String> {
+  storage
+    .tasks()
+    .into_iter()
+    .find(|task| task.id() == id)
+    .ok_or_else(|| format!("task with id: {} not found", id))
+}
+
+fn duplicates(ids: impl Iterator<Item = uuid::Uuid>) -> Vec<uuid::Uuid> {
+  let mut seen = HashSet::new();
+  let mut duplicates = Vec::new();
+  for id in ids {
+    if !seen.insert(id) && !duplicates.contains(&id) {
+      duplicates.push(id);
+    }
+  }
+  return duplicates;
+}
+
+// Names shared by records with different ids, ids are in storage order.
+fn same_names<'a>(
+  records: impl Iterator<Item = (&'a str, uuid::Uuid)>,
+) -> Vec<(String, Vec<uuid::Uuid>)> {
+  let mut names: Vec<String> = Vec::new();
+  let mut ids: HashMap<String, Vec<uuid::Uuid>> = HashMap::new();
+  for (name, id) in records {
+    let name_ids = ids.entry(name.to_owned()).or_insert_with(|| {
+      names.push(name.to_owned());
+      Vec::new()
+    });
+    if !name_ids.contains(&id) {
+      name_ids.push(id);
+    }
+  }
+  names
+    .into_iter()
+    .filter_map(|name| {
+      let name_ids = ids.remove(&name)?;
+      (name_ids.len() > 1).then_some((name, name_ids))
+    })
+    .collect()
+}
+
+fn join_ids(ids: &[uuid::Uuid]) -> String {
+  ids
+    .iter()
+    .map(|id| id.to_string())
+    .collect::<Vec<String>>()
+    .join(", ")
+}
+
+#[cfg(test)]
+mod test {
+  use super::{diagnose, repair, Problem};
+  use crate::{
+    clock::SystemClock,
+    project::Project,
+    storage::{encode, InMemoryStorage, JsonStorage, Storage},
+    tag::Tag,
+    task::Task,
+    traits::Indexable,
+  };
+
+  fn task(project_id: uuid::Uuid, tags: Vec<uuid::Uuid>) -> Task {
+    Task::new(
+      project_id,
+      "task",
+      tags,
+      Some(chrono::Local::now()),
+      None,
+      &SystemClock,
+    )
+  }
+
+  #[test]
+  fn dangling_references() {
+    let mut storage = InMemoryStorage::new();
+    let trashed = Project::new("trashed");
+    storage.add_project(&trashed).unwrap();
+    storage
+      .move_to_trash(trashed.id(), chrono::Local::now())
+      .unwrap();
+    let tag = Tag::new("tag");
+    storage.add_tag(&tag).unwrap();
+    let missing_tag = uuid::Uuid::new_v4();
+    let missing_project = uuid::Uuid::new_v4();
+    storage
+      .add_task(&task(trashed.id(), vec![tag.id(), missing_tag]))
+      .unwrap();
+    storage.add_task(&task(missing_project, vec![])).unwrap();
+
+    let problems = diagnose(&storage);
+    assert_eq!(problems.len(), 3);
+    assert!(problems.iter().any(
+      |problem| matches!(problem, Problem::MissingTag { tag_id, .. } if *tag_id == missing_tag)
+    ));
+
+    let repaired = repair(&mut storage, chrono::Local::now()).unwrap();
+    assert_eq!(repaired.len(), 3);
+    assert!(diagnose(&storage).is_empty());
+    assert!(storage.trash().is_empty());
+    let names: Vec<String> = storage
+      .projects()
+      .iter()
+      .map(|p| p.name().to_owned())
+      .collect();
+    assert_eq!(names, vec!["trashed", "default"]);
+    assert!(storage.tasks().iter().all(|task| task.tags().len() <= 1));
+  }
+
+  #[test]
+  fn duplicate_names_are_merged() {
+    let mut storage = InMemoryStorage::new();
+    let first = Project::new("project");
+    let second = Project::new("project");
+    let first_tag = Tag::new("tag");
+    let second_tag = Tag::new("tag");
+    for project in [&first, &second] {
+      storage.add_project(project).unwrap();
+    }
+    for tag in [&first_tag, &second_tag] {
+      storage.add_tag(tag).unwrap();
+    }
+    storage
+      .add_task(&task(second.id(), vec![first_tag.id(), second_tag.id()]))
+      .unwrap();
+
+    assert_eq!(diagnose(&storage).len(), 2);
+    repair(&mut storage, chrono::Local::now()).unwrap();
+    assert!(diagnose(&storage).is_empty());
+    let merged = &storage.tasks()[0];
+    assert_eq!(merged.project_id(), first.id());
+    assert_eq!(merged.tags(), &vec![first_tag.id()]);
+    assert_eq!(storage.trash().len(), 2);
+  }
+
+  #[test]
+  fn duplicate_ids() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let folder = tmp_dir.path();
+    let project = Project::new("project");
+    let copied = task(project.id(), vec![]);
+    let mut changed = copied.clone();
+    changed.set_paused(true);
+    let identical = task(project.id(), vec![]);
+    std::fs::write(
+      folder.join("projects.json"),
+      encode(&vec![project.clone(), project.clone()]).unwrap(),
+    )
+    .unwrap();
+    std::fs::write(
+      folder.join("tasks.json"),
+      encode(&vec![copied, changed, identical.clone(), identical]).unwrap(),
+    )
+    .unwrap();
+
+    let mut storage = JsonStorage::new(folder.to_str().unwrap()).unwrap();
+    assert_eq!(diagnose(&storage).len(), 3);
+    repair(&mut storage, chrono::Local::now()).unwrap();
+    assert!(diagnose(&storage).is_empty());
+    assert_eq!(storage.projects().len(), 1);
+    assert_eq!(storage.tasks().len(), 3);
+  }
+}
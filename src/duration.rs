@@ -1,15 +1,17 @@
 use chrono::{Datelike, Timelike};
 
+use crate::clock::Clock;
+
 pub struct Period {
   pub from: chrono::DateTime<chrono::Local>,
   pub to: chrono::DateTime<chrono::Local>,
 }
 
 impl Period {
-  pub fn new_to_now(from: chrono::DateTime<chrono::Local>) -> Self {
+  pub fn new_to_now(from: chrono::DateTime<chrono::Local>, clock: &dyn Clock) -> Self {
     return Self {
       from,
-      to: chrono::Local::now(),
+      to: clock.now(),
     };
   }
 
@@ -18,35 +20,88 @@ impl Period {
   }
 }
 
-pub fn get_period_since_now(period_days: i64) -> chrono::DateTime<chrono::Local> {
+pub fn get_period_since_now(
+  period_days: i64,
+  clock: &dyn Clock,
+) -> chrono::DateTime<chrono::Local> {
   return get_checked_sub_signed_from_now(
     chrono::Duration::days(period_days)
-      .checked_add(&get_duration_from_midnight())
+      .checked_add(&get_duration_from_midnight(clock))
       .unwrap(),
+    clock,
   );
 }
 
-pub fn get_week_start_datetime() -> chrono::DateTime<chrono::Local> {
+pub fn get_week_start_datetime(clock: &dyn Clock) -> chrono::DateTime<chrono::Local> {
   return get_checked_sub_signed_from_now(
-    chrono::Duration::days(chrono::Local::now().weekday().num_days_from_monday().into())
-      .checked_add(&get_duration_from_midnight())
+    chrono::Duration::days(clock.now().weekday().num_days_from_monday().into())
+      .checked_add(&get_duration_from_midnight(clock))
       .unwrap(),
+    clock,
   );
 }
 
-pub fn get_midnight_datetime() -> chrono::DateTime<chrono::Local> {
-  return get_checked_sub_signed_from_now(get_duration_from_midnight());
+pub fn get_midnight_datetime(clock: &dyn Clock) -> chrono::DateTime<chrono::Local> {
+  return get_checked_sub_signed_from_now(get_duration_from_midnight(clock), clock);
 }
 
-fn get_duration_from_midnight() -> chrono::Duration {
-  return chrono::Duration::seconds(
-    chrono::Local::now()
-      .time()
-      .num_seconds_from_midnight()
-      .into(),
-  );
+fn get_duration_from_midnight(clock: &dyn Clock) -> chrono::Duration {
+  return chrono::Duration::seconds(clock.now().time().num_seconds_from_midnight().into());
 }
 
-fn get_checked_sub_signed_from_now(duration: chrono::Duration) -> chrono::DateTime<chrono::Local> {
-  return chrono::Local::now().checked_sub_signed(duration).unwrap();
+fn get_checked_sub_signed_from_now(
+  duration: chrono::Duration,
+  clock: &dyn Clock,
+) -> chrono::DateTime<chrono::Local> {
+  return clock.now().checked_sub_signed(duration).unwrap();
+}
+
+#[cfg(test)]
+mod test {
+  use chrono::TimeZone;
+
+  use super::{get_midnight_datetime, get_period_since_now, get_week_start_datetime, Period};
+  use crate::clock::ManualClock;
+
+  fn at(datetime: &str) -> chrono::DateTime<chrono::Local> {
+    chrono::Local
+      .datetime_from_str(datetime, "%Y-%m-%d %H:%M:%S")
+      .unwrap()
+  }
+
+  #[test]
+  fn midnight() {
+    let clock = ManualClock::new(at("2024-03-14 17:45:30"));
+    assert_eq!(get_midnight_datetime(&clock), at("2024-03-14 00:00:00"));
+  }
+
+  #[test]
+  fn week_start() {
+    // 2024-03-14 is Thursday
+    let clock = ManualClock::new(at("2024-03-14 09:10:00"));
+    assert_eq!(get_week_start_datetime(&clock), at("2024-03-11 00:00:00"));
+
+    clock.set(at("2024-03-11 00:00:00"));
+    assert_eq!(get_week_start_datetime(&clock), at("2024-03-11 00:00:00"));
+
+    clock.set(at("2024-03-17 23:59:59"));
+    assert_eq!(get_week_start_datetime(&clock), at("2024-03-11 00:00:00"));
+  }
+
+  #[test]
+  fn period_since_now() {
+    let clock = ManualClock::new(at("2024-03-14 12:00:00"));
+    assert_eq!(get_period_since_now(0, &clock), at("2024-03-14 00:00:00"));
+    assert_eq!(get_period_since_now(3, &clock), at("2024-03-11 00:00:00"));
+  }
+
+  #[test]
+  fn period_to_now() {
+    let clock = ManualClock::new(at("2024-03-14 12:00:00"));
+    let period = Period::new_to_now(get_midnight_datetime(&clock), &clock);
+    assert!(period.contains(&at("2024-03-14 00:00:00")));
+    assert!(period.contains(&at("2024-03-14 12:00:00")));
+    assert!(!period.contains(&at("2024-03-14 12:00:01")));
+    assert!(!period.contains(&at("2024-03-13 23:59:59")));
+  }
 }
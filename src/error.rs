@@ -0,0 +1,129 @@
+use crate::storage::StorageError;
+
+/// Errors returned by `Busy`.
+#[derive(Debug)]
+pub enum Error {
+  NoActiveTask,
+  ActiveTaskExists,
+  NoPausedTask,
+  TaskNotFound(uuid::Uuid),
+  ProjectNotFound(uuid::Uuid),
+  /// There is no project with the name.
+  UnknownProject(String),
+  InvalidProject(String),
+  TagNotFound(uuid::Uuid),
+  /// There is no tag with the name.
+  UnknownTag(String),
+  InvalidTag(String),
+  /// Strict mode refuses to create the project, the closest existing name
+  /// is suggested.
+  NewProject(String, Option<String>),
+  /// Strict mode refuses to create the tag.
+  NewTag(String, Option<String>),
+  /// Nothing matches the short id.
+  IdNotFound(String),
+  /// The short id matches several records, their short ids are given.
+  AmbiguousId(String, Vec<String>),
+  /// A `@...` task reference which can't be parsed.
+  InvalidReference(String),
+  /// Project or tag can't be removed while tasks use it.
+  InUse(String),
+  InvalidTime(String),
+  /// Task breaks the checks of the `validation` module.
+  InvalidTask(String),
+  NothingToUndo,
+  NothingToRedo,
+  Journal(String),
+  Storage(String),
+  /// Storage is locked by another busy process.
+  DatabaseBusy(String),
+  Sync(String),
+  Config(String),
+}
+
+impl Error {
+  pub(crate) fn storage(err: StorageError) -> Self {
+    match err {
+      StorageError::Busy(msg) => Error::DatabaseBusy(msg),
+      StorageError::Failed(msg) => Error::Storage(msg),
+    }
+  }
+
+  pub(crate) fn sync(err: std::io::Error) -> Self {
+    Error::Sync(err.to_string())
+  }
+}
+
+impl std::fmt::Display for Error {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Error::NoActiveTask => write!(f, "there is no active task"),
+      Error::ActiveTaskExists => write!(f, "active task already exists, stop it firstly"),
+      Error::NoPausedTask => write!(f, "there is no paused task to continue"),
+      Error::TaskNotFound(id) => write!(f, "task with id: {} not found", id),
+      Error::ProjectNotFound(id) => write!(f, "project with id: {} not found", id),
+      Error::UnknownProject(name) => write!(f, "project {} not found", name),
+      Error::InvalidProject(msg) => write!(f, "invalid project: {}", msg),
+      Error::TagNotFound(id) => write!(f, "tag with id: {} not found", id),
+      Error::UnknownTag(name) => write!(f, "tag {} not found", name),
+      Error::InvalidTag(msg) => write!(f, "invalid tag: {}", msg),
+      Error::NewProject(name, suggestion) => write_new_name(f, "project", name, suggestion),
+      Error::NewTag(name, suggestion) => write_new_name(f, "tag", name, suggestion),
+      Error::IdNotFound(short_id) => write!(f, "id by short name: {} not found", short_id),
+      Error::AmbiguousId(short_id, candidates) => write!(
+        f,
+        "short id: {} matches several records: {}",
+        short_id,
+        candidates.join(", ")
+      ),
+      Error::InvalidReference(reference) => write!(
+        f,
+        "unknown task reference: {}, expected @active, @last, @-N or @today:N",
+        reference
+      ),
+      Error::InUse(msg) => write!(f, "{}", msg),
+      Error::InvalidTime(msg) => write!(f, "{}", msg),
+      Error::InvalidTask(msg) => write!(f, "invalid task: {}", msg),
+      Error::NothingToUndo => write!(f, "there is nothing to undo"),
+      Error::NothingToRedo => write!(f, "there is nothing to redo"),
+      Error::Journal(err) => write!(f, "journal error: {}", err),
+      Error::Storage(err) => write!(f, "storage error: {}", err),
+      Error::DatabaseBusy(err) => write!(f, "{}", err),
+      Error::Sync(err) => write!(f, "sync error: {}", err),
+      Error::Config(err) => write!(f, "config error: {}", err),
+    }
+  }
+}
+
+fn write_new_name(
+  f: &mut std::fmt::Formatter<'_>,
+  kind: &str,
+  name: &str,
+  suggestion: &Option<String>,
+) -> std::fmt::Result {
+  write!(f, "{} {} doesn't exist", kind, name)?;
+  match suggestion {
+    Some(suggestion) => write!(f, ", did you mean {}?", suggestion),
+    None => Ok(()),
+  }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod test {
+  use super::Error;
+  use crate::storage::StorageError;
+
+  #[test]
+  fn storage_errors() {
+    assert!(matches!(
+      Error::storage(StorageError::Busy("locked".to_owned())),
+      Error::DatabaseBusy(_)
+    ));
+    assert!(matches!(
+      Error::storage("can't parse tasks.json".to_owned().into()),
+      Error::Storage(_)
+    ));
+  }
+}
@@ -0,0 +1,369 @@
+use std::collections::HashMap;
+
+use crate::{
+  project::Project,
+  storage::{atomic, Storage, StorageError},
+  tag::Tag,
+  task::Task,
+  traits::Indexable,
+};
+
+const JOURNAL_MAX_ENTRIES: usize = 100;
+
+/// Records which were touched by an operation, before and after it.
+/// `None` means that the record doesn't exist.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Change {
+  Task {
+    before: Option<Task>,
+    after: Option<Task>,
+  },
+  Project {
+    before: Option<Project>,
+    after: Option<Project>,
+  },
+  Tag {
+    before: Option<Tag>,
+    after: Option<Tag>,
+  },
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct JournalEntry {
+  pub description: String,
+  pub at: chrono::DateTime<chrono::Local>,
+  pub changes: Vec<Change>,
+}
+
+/// Whole storage content used to find out what an operation changed.
+pub struct State {
+  tasks: Vec<Task>,
+  projects: Vec<Project>,
+  tags: Vec<Tag>,
+}
+
+impl State {
+  pub fn capture(storage: &dyn Storage) -> Self {
+    let trash = storage.trash();
+    let mut state = Self {
+      tasks: storage.tasks(),
+      projects: storage.projects(),
+      tags: storage.tags(),
+    };
+    state.tasks.extend(trash.tasks);
+    state.projects.extend(trash.projects);
+    state.tags.extend(trash.tags);
+    return state;
+  }
+
+  pub fn diff(&self, after: &State) -> Vec<Change> {
+    let mut changes = Vec::new();
+    for (before, after) in diff_items(&self.projects, &after.projects) {
+      changes.push(Change::Project { before, after });
+    }
+    for (before, after) in diff_items(&self.tags, &after.tags) {
+      changes.push(Change::Tag { before, after });
+    }
+    for (before, after) in diff_items(&self.tasks, &after.tasks) {
+      changes.push(Change::Task { before, after });
+    }
+    return changes;
+  }
+}
+
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+struct JournalData {
+  undo: Vec<JournalEntry>,
+  redo: Vec<JournalEntry>,
+}
+
+/// Undo/redo history of the operations made through `Busy`.
+/// It's kept in a local file, so it survives between busy invocations.
+pub struct Journal {
+  filepath: Option<String>,
+  data: JournalData,
+}
+
+impl Journal {
+  pub fn new(filepath: Option<String>) -> Result<Self, String> {
+    let mut journal = Self {
+      filepath,
+      data: JournalData::default(),
+    };
+    journal.reload()?;
+    Ok(journal)
+  }
+
+  /// Reads the history again, it may be changed by another busy process.
+  pub fn reload(&mut self) -> Result<(), String> {
+    if let Some(filepath) = self.filepath.as_ref() {
+      match std::fs::read_to_string(filepath) {
+        Ok(content) => {
+          self.data = serde_json::from_str(&content)
+            .map_err(|err| format!("can't load journal: {}, err: {}", filepath, err))?;
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+        Err(err) => return Err(format!("can't read journal: {}, err: {}", filepath, err)),
+      };
+    }
+    Ok(())
+  }
+
+  pub fn record(
+    &mut self,
+    description: &str,
+    changes: Vec<Change>,
+    at: chrono::DateTime<chrono::Local>,
+  ) -> Result<(), String> {
+    if changes.is_empty() {
+      return Ok(());
+    }
+    self.data.undo.push(JournalEntry {
+      description: description.to_owned(),
+      at,
+      changes,
+    });
+    if self.data.undo.len() > JOURNAL_MAX_ENTRIES {
+      self.data.undo.remove(0);
+    }
+    self.data.redo.clear();
+    self.save()
+  }
+
+  pub fn last_undo(&self) -> Option<&JournalEntry> {
+    self.data.undo.last()
+  }
+
+  pub fn last_redo(&self) -> Option<&JournalEntry> {
+    self.data.redo.last()
+  }
+
+  pub fn move_to_redo(&mut self) -> Result<(), String> {
+    if let Some(entry) = self.data.undo.pop() {
+      self.data.redo.push(entry);
+    }
+    self.save()
+  }
+
+  pub fn move_to_undo(&mut self) -> Result<(), String> {
+    if let Some(entry) = self.data.redo.pop() {
+      self.data.undo.push(entry);
+    }
+    self.save()
+  }
+
+  fn save(&self) -> Result<(), String> {
+    let filepath = match self.filepath.as_ref() {
+      Some(filepath) => filepath,
+      None => return Ok(()),
+    };
+    let content = serde_json::to_string_pretty(&self.data).map_err(|err| err.to_string())?;
+    atomic::write_file(std::path::Path::new(filepath), content.as_bytes())
+      .map_err(|err| format!("can't save journal: {}, err: {}", filepath, err))
+  }
+}
+
+/// Brings records touched by `changes` to their `before` (undo) or `after`
+/// (redo) state. Refuses to do it if a record was changed since then.
+pub fn apply(
+  storage: &mut dyn Storage,
+  changes: &[Change],
+  undo: bool,
+) -> Result<(), StorageError> {
+  let current = State::capture(storage);
+  for change in changes.iter() {
+    match change {
+      Change::Task { before, after } => check(&current.tasks, before, after, undo, "task")?,
+      Change::Project { before, after } => {
+        check(&current.projects, before, after, undo, "project")?
+      }
+      Change::Tag { before, after } => check(&current.tags, before, after, undo, "tag")?,
+    };
+  }
+
+  // tasks refer to projects and tags, so they go last on redo and first on undo
+  let mut ordered: Vec<&Change> = changes.iter().collect();
+  if undo {
+    ordered.reverse();
+  }
+  for change in ordered {
+    match change {
+      Change::Task { before, after } => {
+        let (from, to) = select(before, after, undo);
+        match (from, to) {
+          (Some(task), None) => storage.remove_task(task.id())?,
+          (None, Some(task)) => storage.add_task(task)?,
+          (Some(_), Some(task)) => storage.replace_task(task)?,
+          (None, None) => {}
+        }
+      }
+      Change::Project { before, after } => {
+        let (from, to) = select(before, after, undo);
+        match (from, to) {
+          (Some(project), None) => storage.remove_project(project.id())?,
+          (None, Some(project)) => storage.add_project(project)?,
+          (Some(_), Some(project)) => storage.replace_project(project)?,
+          (None, None) => {}
+        }
+      }
+      Change::Tag { before, after } => {
+        let (from, to) = select(before, after, undo);
+        match (from, to) {
+          (Some(tag), None) => storage.remove_tag(tag.id())?,
+          (None, Some(tag)) => storage.add_tag(tag)?,
+          (Some(_), Some(tag)) => storage.replace_tag(tag)?,
+          (None, None) => {}
+        }
+      }
+    }
+  }
+  Ok(())
+}
+
+fn select<'a, T>(
+  before: &'a Option<T>,
+  after: &'a Option<T>,
+  undo: bool,
+) -> (&'a Option<T>, &'a Option<T>) {
+  match undo {
+    true => (after, before),
+    false => (before, after),
+  }
+}
+
+fn check<T: Indexable + PartialEq>(
+  current: &[T],
+  before: &Option<T>,
+  after: &Option<T>,
+  undo: bool,
+  kind: &str,
+) -> Result<(), String> {
+  let (expected, _) = select(before, after, undo);
+  let id = before.as_ref().or(after.as_ref()).unwrap().id();
+  let found = current.iter().find(|item| item.id() == id);
+  if found != expected.as_ref() {
+    return Err(format!(
+      "{} with id: {} was changed since, it can't be restored",
+      kind, id
+    ));
+  }
+  Ok(())
+}
+
+fn diff_items<T: Indexable + Clone + PartialEq>(
+  before: &[T],
+  after: &[T],
+) -> Vec<(Option<T>, Option<T>)> {
+  // the first item wins if an id repeats, the same as a linear search
+  fn index<T: Indexable>(items: &[T]) -> HashMap<uuid::Uuid, &T> {
+    let mut by_id = HashMap::with_capacity(items.len());
+    for item in items.iter() {
+      by_id.entry(item.id()).or_insert(item);
+    }
+    by_id
+  }
+  let (before_by_id, after_by_id) = (index(before), index(after));
+
+  let mut changes = Vec::new();
+  for item in before.iter() {
+    match after_by_id.get(&item.id()) {
+      Some(found) if *found == item => {}
+      Some(found) => changes.push((Some(item.clone()), Some((*found).clone()))),
+      None => changes.push((Some(item.clone()), None)),
+    }
+  }
+  for item in after.iter() {
+    if !before_by_id.contains_key(&item.id()) {
+      changes.push((None, Some(item.clone())));
+    }
+  }
+  return changes;
+}
+
+#[cfg(test)]
+mod test {
+  use super::{apply, Journal, State};
+  use crate::clock::SystemClock;
+  use crate::{
+    project::Project,
+    storage::{JsonStorage, Storage},
+    task::Task,
+    traits::Indexable,
+  };
+
+  #[test]
+  fn journal_undo_redo() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut storage = JsonStorage::new(tmp_dir.path().to_str().unwrap()).unwrap();
+    let journal_path = tmp_dir.path().join("journal.json");
+    let mut journal = Journal::new(Some(journal_path.to_str().unwrap().to_owned())).unwrap();
+
+    let before = State::capture(&storage);
+    let project = Project::new("project");
+    storage.add_project(&project).unwrap();
+    let mut task = Task::new(project.id(), "title", vec![], None, None, &SystemClock);
+    storage.add_task(&task).unwrap();
+    journal
+      .record(
+        "start",
+        before.diff(&State::capture(&storage)),
+        chrono::Local::now(),
+      )
+      .unwrap();
+
+    let before = State::capture(&storage);
+    task.stop(chrono::Local::now()).unwrap();
+    storage.replace_task(&task).unwrap();
+    journal
+      .record(
+        "stop",
+        before.diff(&State::capture(&storage)),
+        chrono::Local::now(),
+      )
+      .unwrap();
+
+    let mut journal = Journal::new(Some(journal_path.to_str().unwrap().to_owned())).unwrap();
+    let entry = journal.last_undo().unwrap().clone();
+    assert_eq!(entry.description, "stop");
+    apply(&mut storage, &entry.changes, true).unwrap();
+    journal.move_to_redo().unwrap();
+    assert!(storage.tasks()[0].stop_time().is_none());
+
+    let entry = journal.last_undo().unwrap().clone();
+    apply(&mut storage, &entry.changes, true).unwrap();
+    journal.move_to_redo().unwrap();
+    assert!(storage.tasks().is_empty());
+    assert!(storage.projects().is_empty());
+
+    let entry = journal.last_redo().unwrap().clone();
+    assert_eq!(entry.description, "start");
+    apply(&mut storage, &entry.changes, false).unwrap();
+    journal.move_to_undo().unwrap();
+    assert_eq!(storage.tasks()[0].id(), task.id());
+    assert_eq!(storage.projects()[0].id(), project.id());
+  }
+
+  #[test]
+  fn journal_refuses_changed_records() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut storage = JsonStorage::new(tmp_dir.path().to_str().unwrap()).unwrap();
+
+    let before = State::capture(&storage);
+    let mut task = Task::new(
+      uuid::Uuid::new_v4(),
+      "title",
+      vec![],
+      None,
+      None,
+      &SystemClock,
+    );
+    storage.add_task(&task).unwrap();
+    let changes = before.diff(&State::capture(&storage));
+
+    task.stop(chrono::Local::now()).unwrap();
+    storage.replace_task(&task).unwrap();
+
+    assert!(apply(&mut storage, &changes, true).is_err());
+  }
+}
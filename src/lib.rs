@@ -6,15 +6,24 @@ extern crate uuid;
 
 mod busy;
 mod config;
+mod error;
 
+pub mod clock;
+pub mod color;
+pub mod doctor;
 pub mod duration;
+pub mod journal;
 pub mod project;
+pub mod short_id;
 pub mod storage;
+pub mod suggest;
 pub mod sync;
 pub mod tag;
 pub mod task;
 pub mod time;
 pub mod traits;
+pub mod validation;
 
 pub use self::busy::*;
 pub use config::*;
+pub use error::Error;
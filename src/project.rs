@@ -1,9 +1,38 @@
-use crate::traits::Indexable;
+use crate::{
+  color::parse_color,
+  traits::{Indexable, Trashable},
+};
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+/// Separates parent and child projects in a name, e.g. `acme/backend/api`
+/// is a child of `acme/backend`, which is a child of `acme`.
+pub const PROJECT_SEPARATOR: char = '/';
+
+/// Metadata to set on a project. `None` keeps a field as it is, an empty
+/// string clears it.
+#[derive(Debug, Default, Clone)]
+pub struct ProjectChanges {
+  pub description: Option<String>,
+  pub client: Option<String>,
+  pub color: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct Project {
   id: uuid::Uuid,
   name: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  description: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  client: Option<String>,
+  /// Color name the viewer prints the project with, e.g. `blue` or
+  /// `bright magenta`.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  color: Option<String>,
+  /// Archived projects are hidden from listings, their tasks are kept.
+  #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+  archived: bool,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  deleted_at: Option<chrono::DateTime<chrono::Local>>,
 }
 
 impl Indexable for Project {
@@ -12,15 +41,121 @@ impl Indexable for Project {
   }
 }
 
+impl Trashable for Project {
+  fn deleted_at(&self) -> Option<chrono::DateTime<chrono::Local>> {
+    self.deleted_at
+  }
+
+  fn set_deleted_at(&mut self, deleted_at: Option<chrono::DateTime<chrono::Local>>) {
+    self.deleted_at = deleted_at;
+  }
+}
+
 impl Project {
   pub fn new(name: &str) -> Self {
     Self {
       id: uuid::Uuid::new_v4(),
       name: name.to_owned(),
+      description: None,
+      client: None,
+      color: None,
+      archived: false,
+      deleted_at: None,
+    }
+  }
+
+  pub(crate) fn from_parts(id: uuid::Uuid, name: &str) -> Self {
+    Self {
+      id,
+      name: name.to_owned(),
+      description: None,
+      client: None,
+      color: None,
+      archived: false,
+      deleted_at: None,
     }
   }
 
   pub fn name(&self) -> &str {
     self.name.as_str()
   }
+
+  /// Names of the project and its parents from the top one, e.g.
+  /// `["acme", "backend", "api"]`.
+  pub fn path(&self) -> Vec<&str> {
+    self.name.split(PROJECT_SEPARATOR).collect()
+  }
+
+  pub fn parent_name(&self) -> Option<&str> {
+    self
+      .name
+      .rsplit_once(PROJECT_SEPARATOR)
+      .map(|(parent, _)| parent)
+  }
+
+  /// True for the project named `name` and all its descendants.
+  pub fn is_within(&self, name: &str) -> bool {
+    let name = name.trim_end_matches(PROJECT_SEPARATOR);
+    match self.name.strip_prefix(name) {
+      Some(rest) => rest.is_empty() || rest.starts_with(PROJECT_SEPARATOR),
+      None => false,
+    }
+  }
+
+  pub fn set_name(&mut self, name: &str) {
+    self.name = name.to_owned();
+  }
+
+  pub fn description(&self) -> Option<&str> {
+    self.description.as_deref()
+  }
+
+  pub fn client(&self) -> Option<&str> {
+    self.client.as_deref()
+  }
+
+  pub fn color(&self) -> Option<&str> {
+    self.color.as_deref()
+  }
+
+  pub fn is_archived(&self) -> bool {
+    self.archived
+  }
+
+  pub fn set_description(&mut self, description: Option<String>) {
+    self.description = description;
+  }
+
+  pub fn set_client(&mut self, client: Option<String>) {
+    self.client = client;
+  }
+
+  pub fn set_color(&mut self, color: Option<String>) -> Result<(), String> {
+    if let Some(color) = color.as_deref() {
+      parse_color(color)?;
+    }
+    self.color = color;
+    Ok(())
+  }
+
+  pub fn apply(&mut self, changes: ProjectChanges) -> Result<(), String> {
+    let value = |value: String| match value.is_empty() {
+      true => None,
+      false => Some(value),
+    };
+    if let Some(color) = changes.color {
+      self.set_color(value(color))?;
+    }
+    if let Some(description) = changes.description {
+      self.description = value(description);
+    }
+    if let Some(client) = changes.client {
+      self.client = value(client);
+    }
+    Ok(())
+  }
+
+  pub fn set_archived(&mut self, archived: bool) {
+    self.archived = archived;
+  }
 }
@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+
+use crate::Error;
+
+/// Records are shortened to at least this many characters.
+pub const MIN_SHORT_ID_LEN: usize = 4;
+
+/// Kind of record a short id is resolved to, so e.g. a task command never
+/// picks a tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdKind {
+  Task,
+  Project,
+  Tag,
+}
+
+struct Entry {
+  hex: String,
+  id: uuid::Uuid,
+  kind: IdKind,
+  trashed: bool,
+}
+
+/// Shortest unique prefixes of ids, like git abbreviates commit hashes.
+/// Prefixes are unique among records of every kind including trashed ones,
+/// so a printed short id stays valid whatever it's resolved against.
+pub struct ShortIds {
+  // sorted by `hex`
+  entries: Vec<Entry>,
+  lengths: HashMap<uuid::Uuid, usize>,
+}
+
+impl ShortIds {
+  /// Takes ids with their kind and whether they're in the trash.
+  pub fn new(ids: impl IntoIterator<Item = (uuid::Uuid, IdKind, bool)>) -> Self {
+    let mut entries: Vec<Entry> = ids
+      .into_iter()
+      .map(|(id, kind, trashed)| Entry {
+        hex: id.as_simple().to_string(),
+        id,
+        kind,
+        trashed,
+      })
+      .collect();
+    entries.sort_by(|a, b| a.hex.cmp(&b.hex));
+
+    let mut unique: Vec<&Entry> = entries.iter().collect();
+    unique.dedup_by(|a, b| a.id == b.id);
+    let mut lengths = HashMap::new();
+    for (i, entry) in unique.iter().enumerate() {
+      let mut len = MIN_SHORT_ID_LEN;
+      if i > 0 {
+        len = len.max(common_prefix_len(&entry.hex, &unique[i - 1].hex) + 1);
+      }
+      if i + 1 < unique.len() {
+        len = len.max(common_prefix_len(&entry.hex, &unique[i + 1].hex) + 1);
+      }
+      lengths.insert(entry.id, len.min(entry.hex.len()));
+    }
+
+    ShortIds { entries, lengths }
+  }
+
+  pub fn shorten(&self, id: uuid::Uuid) -> String {
+    let len = self.lengths.get(&id).copied().unwrap_or(MIN_SHORT_ID_LEN);
+    id.as_simple().to_string()[..len].to_owned()
+  }
+
+  /// Finds the record of `kind` (any kind if `None`) whose id starts with
+  /// `short_id`. Full ids are accepted as well, shorter prefixes than
+  /// `MIN_SHORT_ID_LEN` aren't, as git does.
+  pub fn resolve(
+    &self,
+    short_id: &str,
+    kind: Option<IdKind>,
+    trashed: bool,
+  ) -> Result<uuid::Uuid, Error> {
+    let prefix = short_id.replace('-', "").to_lowercase();
+    if prefix.len() < MIN_SHORT_ID_LEN || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+      return Err(Error::IdNotFound(short_id.to_owned()));
+    }
+
+    let start = self
+      .entries
+      .partition_point(|entry| entry.hex.as_str() < prefix.as_str());
+    let mut found: Vec<uuid::Uuid> = self.entries[start..]
+      .iter()
+      .take_while(|entry| entry.hex.starts_with(&prefix))
+      .filter(|entry| entry.trashed == trashed && kind.is_none_or(|kind| entry.kind == kind))
+      .map(|entry| entry.id)
+      .collect();
+    found.dedup();
+    match found.as_slice() {
+      [] => Err(Error::IdNotFound(short_id.to_owned())),
+      [id] => Ok(*id),
+      _ => Err(Error::AmbiguousId(
+        short_id.to_owned(),
+        found.iter().map(|id| self.shorten(*id)).collect(),
+      )),
+    }
+  }
+}
+
+fn common_prefix_len(a: &str, b: &str) -> usize {
+  a.bytes().zip(b.bytes()).take_while(|(a, b)| a == b).count()
+}
+
+#[cfg(test)]
+mod test {
+  use super::{IdKind, ShortIds};
+  use crate::Error;
+
+  fn id(hex: &str) -> uuid::Uuid {
+    uuid::Uuid::parse_str(hex).unwrap()
+  }
+
+  #[test]
+  fn shortest_unique_prefixes() {
+    let task = id("abcd1234000000000000000000000000");
+    let other_task = id("abcd5678000000000000000000000000");
+    let tag = id("abce0000000000000000000000000000");
+    let project = id("12345678000000000000000000000000");
+    let short_ids = ShortIds::new(vec![
+      (task, IdKind::Task, false),
+      (other_task, IdKind::Task, false),
+      (tag, IdKind::Tag, false),
+      (project, IdKind::Project, true),
+    ]);
+
+    assert_eq!(short_ids.shorten(task), "abcd1");
+    assert_eq!(short_ids.shorten(other_task), "abcd5");
+    assert_eq!(short_ids.shorten(tag), "abce");
+    assert_eq!(short_ids.shorten(project), "1234");
+
+    assert_eq!(short_ids.resolve("abcd1", None, false).unwrap(), task);
+    assert_eq!(
+      short_ids
+        .resolve("ABCD5678-0000", Some(IdKind::Task), false)
+        .unwrap(),
+      other_task
+    );
+    assert_eq!(
+      short_ids.resolve("abce", Some(IdKind::Tag), false).unwrap(),
+      tag
+    );
+    match short_ids.resolve("abcd", Some(IdKind::Task), false) {
+      Err(Error::AmbiguousId(_, candidates)) => assert_eq!(candidates, vec!["abcd1", "abcd5"]),
+      _ => panic!("ambiguous prefix is resolved"),
+    }
+    assert!(matches!(
+      short_ids.resolve("abce", Some(IdKind::Task), false),
+      Err(Error::IdNotFound(_))
+    ));
+    assert!(matches!(
+      short_ids.resolve("abc", Some(IdKind::Tag), false),
+      Err(Error::IdNotFound(_))
+    ));
+    // trashed records are resolved only in the trash
+    assert!(matches!(
+      short_ids.resolve("1234", None, false),
+      Err(Error::IdNotFound(_))
+    ));
+    assert_eq!(short_ids.resolve("1234", None, true).unwrap(), project);
+    assert!(matches!(
+      short_ids.resolve("xyz", None, false),
+      Err(Error::IdNotFound(_))
+    ));
+  }
+}
@@ -0,0 +1,27 @@
+use std::io::Write;
+
+// Writes into a temporary file next to the target and renames it over the
+// original one, so the target is never left half-written.
+pub fn write_file(filepath: &std::path::Path, content: &[u8]) -> std::io::Result<()> {
+  let folder = filepath.parent().unwrap_or(std::path::Path::new("."));
+
+  let mut tmp_file = tempfile::Builder::new()
+    .prefix(".busy")
+    .suffix(".tmp")
+    .tempfile_in(folder)?;
+  tmp_file.write_all(content)?;
+  tmp_file.as_file().sync_all()?;
+  tmp_file.persist(filepath)?;
+
+  sync_folder(folder)
+}
+
+#[cfg(unix)]
+fn sync_folder(folder: &std::path::Path) -> std::io::Result<()> {
+  std::fs::File::open(folder)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_folder(_folder: &std::path::Path) -> std::io::Result<()> {
+  Ok(())
+}
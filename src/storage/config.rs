@@ -0,0 +1,7 @@
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+pub enum StorageConfig {
+  #[default]
+  Json,
+  Sqlite,
+  EventLog,
+}
@@ -0,0 +1,33 @@
+/// Error of a storage operation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StorageError {
+  /// Storage is locked by another busy process.
+  Busy(String),
+  Failed(String),
+}
+
+impl StorageError {
+  /// Changes the message, e.g. to give more context, and keeps the kind.
+  pub fn map(self, f: impl FnOnce(String) -> String) -> Self {
+    match self {
+      StorageError::Busy(msg) => StorageError::Busy(f(msg)),
+      StorageError::Failed(msg) => StorageError::Failed(f(msg)),
+    }
+  }
+}
+
+impl std::fmt::Display for StorageError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      StorageError::Busy(msg) | StorageError::Failed(msg) => write!(f, "{}", msg),
+    }
+  }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<String> for StorageError {
+  fn from(msg: String) -> Self {
+    StorageError::Failed(msg)
+  }
+}
@@ -0,0 +1,81 @@
+use fs2::FileExt;
+use log::debug;
+
+use super::StorageError;
+
+pub const LOCK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+const LOCK_RETRY_INTERVAL: std::time::Duration = std::time::Duration::from_millis(50);
+
+/// Advisory lock on a file shared by every busy process working with the same
+/// storage folder. The lock is released on drop.
+pub struct StorageLock {
+  file: std::fs::File,
+}
+
+impl StorageLock {
+  pub fn shared(filepath: &str, timeout: std::time::Duration) -> Result<Self, StorageError> {
+    Self::acquire(filepath, timeout, FileExt::try_lock_shared)
+  }
+
+  pub fn exclusive(filepath: &str, timeout: std::time::Duration) -> Result<Self, StorageError> {
+    Self::acquire(filepath, timeout, FileExt::try_lock_exclusive)
+  }
+
+  fn acquire(
+    filepath: &str,
+    timeout: std::time::Duration,
+    try_lock: fn(&std::fs::File) -> std::io::Result<()>,
+  ) -> Result<Self, StorageError> {
+    let file = std::fs::OpenOptions::new()
+      .create(true)
+      .truncate(false)
+      .write(true)
+      .open(filepath)
+      .map_err(|err| format!("can't open lock file: {}, err: {}", filepath, err))?;
+
+    let started_at = std::time::Instant::now();
+    loop {
+      match try_lock(&file) {
+        Ok(_) => return Ok(Self { file }),
+        Err(err) if err.kind() == fs2::lock_contended_error().kind() => {
+          if started_at.elapsed() >= timeout {
+            return Err(StorageError::Busy(format!(
+              "database busy: {} is locked by another busy process, try again later",
+              filepath
+            )));
+          }
+          debug!("wait for storage lock: {}", filepath);
+          std::thread::sleep(LOCK_RETRY_INTERVAL);
+        }
+        Err(err) => return Err(format!("can't lock file: {}, err: {}", filepath, err).into()),
+      }
+    }
+  }
+}
+
+impl Drop for StorageLock {
+  fn drop(&mut self) {
+    _ = FileExt::unlock(&self.file);
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::StorageLock;
+  use crate::storage::StorageError;
+
+  #[test]
+  fn exclusive_lock_timeout() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let lock_path = tmp_dir.path().join(".busy.lock");
+    let lock_path = lock_path.to_str().unwrap();
+    let timeout = std::time::Duration::from_millis(100);
+
+    let lock = StorageLock::exclusive(lock_path, timeout).unwrap();
+    let err = StorageLock::shared(lock_path, timeout).err().unwrap();
+    assert!(matches!(err, StorageError::Busy(_)));
+
+    drop(lock);
+    StorageLock::exclusive(lock_path, timeout).unwrap();
+  }
+}
@@ -1,5 +1,19 @@
+pub(crate) mod atomic;
+mod config;
+mod error;
+pub(crate) mod lock;
+mod schema;
 mod storage;
+mod storage_event_log;
 mod storage_json;
+mod storage_memory;
+mod storage_sqlite;
 
-pub use storage::Storage;
+pub use config::StorageConfig;
+pub use error::StorageError;
+pub use schema::{decode, encode, SCHEMA_VERSION};
+pub use storage::{migrate, Storage, Trash};
+pub use storage_event_log::{Event, EventLogStorage, LogEntry};
 pub use storage_json::JsonStorage;
+pub use storage_memory::InMemoryStorage;
+pub use storage_sqlite::SqliteStorage;
@@ -0,0 +1,151 @@
+use log::debug;
+
+/// Version of the JSON documents written by this busy build.
+pub const SCHEMA_VERSION: u64 = 4;
+
+struct Migration {
+  version: u64,
+  description: &'static str,
+  migrate: fn(serde_json::Value) -> Result<serde_json::Value, String>,
+}
+
+// Every migration upgrades a document from `version - 1` to `version`.
+// Append new migrations to the end and bump `SCHEMA_VERSION`.
+const MIGRATIONS: &[Migration] = &[
+  Migration {
+    version: 1,
+    description: "wrap plain items array into a versioned document",
+    migrate: wrap_items,
+  },
+  Migration {
+    version: 2,
+    description: "items may carry `deleted_at` and be kept in the trash",
+    migrate: add_trash,
+  },
+  Migration {
+    version: 3,
+    description: "projects may carry a description, a client, a color and be archived",
+    migrate: add_project_metadata,
+  },
+  Migration {
+    version: 4,
+    description: "tags may carry a color",
+    migrate: add_tag_color,
+  },
+];
+
+fn wrap_items(items: serde_json::Value) -> Result<serde_json::Value, String> {
+  Ok(serde_json::json!({
+    "version": 1,
+    "items": items,
+  }))
+}
+
+// Older busy builds would show trashed items as alive, so the version is
+// bumped even though the existing items stay as they are.
+fn add_trash(mut document: serde_json::Value) -> Result<serde_json::Value, String> {
+  document["version"] = serde_json::json!(2);
+  Ok(document)
+}
+
+// Older busy builds would drop the new fields when they rewrite projects.
+fn add_project_metadata(mut document: serde_json::Value) -> Result<serde_json::Value, String> {
+  document["version"] = serde_json::json!(3);
+  Ok(document)
+}
+
+// Same as above for tag colors.
+fn add_tag_color(mut document: serde_json::Value) -> Result<serde_json::Value, String> {
+  document["version"] = serde_json::json!(4);
+  Ok(document)
+}
+
+/// Reads items from a versioned JSON document, upgrading older documents.
+pub fn decode<T: serde::de::DeserializeOwned>(content: &str) -> Result<Vec<T>, String> {
+  if content.trim().is_empty() {
+    return Ok(Vec::new());
+  }
+
+  let document = upgrade(serde_json::from_str(content).map_err(|err| err.to_string())?)?;
+  match document.get("items") {
+    Some(items) => serde_json::from_value(items.clone()).map_err(|err| err.to_string()),
+    None => Err("document has no items".to_string()),
+  }
+}
+
+/// Writes items as a JSON document of the current schema version.
+pub fn encode<T: serde::ser::Serialize>(items: &Vec<T>) -> Result<String, String> {
+  serde_json::to_string_pretty(&serde_json::json!({
+    "version": SCHEMA_VERSION,
+    "items": items,
+  }))
+  .map_err(|err| err.to_string())
+}
+
+pub fn check_version(version: u64, supported_version: u64) -> Result<(), String> {
+  if version > supported_version {
+    return Err(format!(
+      "data was written by a newer busy (schema version {}, supported up to {}), please upgrade busy",
+      version, supported_version
+    ));
+  }
+  Ok(())
+}
+
+fn upgrade(mut document: serde_json::Value) -> Result<serde_json::Value, String> {
+  let version = document_version(&document)?;
+  check_version(version, SCHEMA_VERSION)?;
+
+  for migration in MIGRATIONS.iter().filter(|m| m.version > version) {
+    debug!(
+      "migrate document to version {}: {}",
+      migration.version, migration.description
+    );
+    document = (migration.migrate)(document)?;
+  }
+  return Ok(document);
+}
+
+fn document_version(document: &serde_json::Value) -> Result<u64, String> {
+  // Before versioning items were stored as a plain array.
+  if document.is_array() {
+    return Ok(0);
+  }
+  match document.get("version").and_then(|version| version.as_u64()) {
+    Some(version) => Ok(version),
+    None => Err("document has no schema version".to_string()),
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{decode, encode, SCHEMA_VERSION};
+
+  #[test]
+  fn decode_legacy_array() {
+    let items: Vec<String> = decode(r#"["first", "second"]"#).unwrap();
+    assert_eq!(items, vec!["first", "second"]);
+  }
+
+  #[test]
+  fn encode_decode_current_version() {
+    let content = encode(&vec!["item".to_string()]).unwrap();
+    assert!(content.contains(&format!("\"version\": {}", SCHEMA_VERSION)));
+
+    let items: Vec<String> = decode(&content).unwrap();
+    assert_eq!(items, vec!["item"]);
+  }
+
+  #[test]
+  fn upgrade_older_version() {
+    let items: Vec<String> = decode(r#"{"version": 2, "items": ["item"]}"#).unwrap();
+    assert_eq!(items, vec!["item"]);
+  }
+
+  #[test]
+  fn refuse_newer_version() {
+    let content = format!(r#"{{"version": {}, "items": []}}"#, SCHEMA_VERSION + 1);
+    let err = decode::<String>(&content).err().unwrap();
+    assert!(err.contains("newer busy"));
+  }
+}
@@ -1,25 +1,103 @@
-use crate::{project::Project, tag::Tag, task::Task};
+use crate::{project::Project, tag::Tag, task::Task, traits::Indexable};
 
-pub trait Storage {
-  fn shorten_id(&self, id: uuid::Uuid) -> String;
-  fn resolve_id(&self, id: &str) -> Option<uuid::Uuid>;
+use super::StorageError;
+
+/// Removed records. They are hidden from `tasks()`, `projects()` and `tags()`
+/// until they are restored or purged.
+#[derive(Debug, Default, Clone)]
+pub struct Trash {
+  pub tasks: Vec<Task>,
+  pub projects: Vec<Project>,
+  pub tags: Vec<Tag>,
+}
+
+impl Trash {
+  pub fn is_empty(&self) -> bool {
+    self.tasks.is_empty() && self.projects.is_empty() && self.tags.is_empty()
+  }
 
+  pub fn len(&self) -> usize {
+    self.tasks.len() + self.projects.len() + self.tags.len()
+  }
+}
+
+pub trait Storage {
   fn tasks(&self) -> Vec<Task>;
-  fn add_task(&mut self, task: &Task);
-  fn remove_task(&mut self, task_id: uuid::Uuid) -> Result<(), String>;
-  fn replace_task(&mut self, task: &Task) -> Result<(), String>;
-  fn replace_tasks(&mut self, tasks: Vec<Task>);
-
-  fn add_tag(&mut self, tag: &Tag);
-  fn replace_tag(&mut self, tag: &Tag) -> Result<(), String>;
-  fn replace_tags(&mut self, tags: Vec<Tag>);
+  fn add_task(&mut self, task: &Task) -> Result<(), StorageError>;
+  fn remove_task(&mut self, task_id: uuid::Uuid) -> Result<(), StorageError>;
+  fn replace_task(&mut self, task: &Task) -> Result<(), StorageError>;
+  /// Replaces every task which is not in the trash.
+  fn replace_tasks(&mut self, tasks: Vec<Task>) -> Result<(), StorageError>;
+
+  fn add_tag(&mut self, tag: &Tag) -> Result<(), StorageError>;
+  fn replace_tag(&mut self, tag: &Tag) -> Result<(), StorageError>;
+  fn remove_tag(&mut self, tag_id: uuid::Uuid) -> Result<(), StorageError>;
+  /// Replaces every tag which is not in the trash.
+  fn replace_tags(&mut self, tags: Vec<Tag>) -> Result<(), StorageError>;
   fn tags(&self) -> Vec<Tag>;
-  fn tag_by_id(&self, id: uuid::Uuid) -> Option<&Tag>;
-  fn find_tag_by_name(&self, tag_name: &str) -> Option<Tag>;
-  fn find_tag_by_names(&self, tag_names: &Vec<String>) -> Vec<Tag>;
-  fn find_tags(&self, tag_ids: &Vec<uuid::Uuid>) -> Vec<Tag>;
 
-  fn add_project(&mut self, project: &Project);
-  fn replace_project(&mut self, project: &Project) -> Result<(), String>;
+  fn add_project(&mut self, project: &Project) -> Result<(), StorageError>;
+  fn replace_project(&mut self, project: &Project) -> Result<(), StorageError>;
+  fn remove_project(&mut self, project_id: uuid::Uuid) -> Result<(), StorageError>;
   fn projects(&self) -> Vec<Project>;
+
+  fn trash(&self) -> Trash;
+  /// Marks a task, project or tag as deleted at `deleted_at`.
+  fn move_to_trash(
+    &mut self,
+    id: uuid::Uuid,
+    deleted_at: chrono::DateTime<chrono::Local>,
+  ) -> Result<(), StorageError>;
+  fn restore_from_trash(&mut self, id: uuid::Uuid) -> Result<(), StorageError>;
+  /// Removes records deleted before `deleted_before` for good and returns them.
+  fn purge_trash(
+    &mut self,
+    deleted_before: chrono::DateTime<chrono::Local>,
+  ) -> Result<Trash, StorageError>;
+
+  fn tag_by_id(&self, id: uuid::Uuid) -> Option<Tag> {
+    self.tags().into_iter().find(|tag| tag.id() == id)
+  }
+
+  fn find_tag_by_name(&self, tag_name: &str) -> Option<Tag> {
+    self.tags().into_iter().find(|tag| tag.name() == tag_name)
+  }
+
+  fn find_tag_by_names(&self, tag_names: &[String]) -> Vec<Tag> {
+    let tags = self.tags();
+    let mut found_tags = Vec::with_capacity(tag_names.len());
+    for tag_name in tag_names.iter() {
+      if let Some(found_tag) = tags.iter().find(|tag| tag.name() == tag_name) {
+        found_tags.push(found_tag.clone());
+      }
+    }
+    return found_tags;
+  }
+
+  fn find_tags(&self, tag_ids: &[uuid::Uuid]) -> Vec<Tag> {
+    let tags = self.tags();
+    let mut found_tags = Vec::new();
+    for tag_id in tag_ids.iter() {
+      if let Some(found_tag) = tags.iter().find(|tag| tag.id() == *tag_id) {
+        found_tags.push(found_tag.clone());
+      }
+    }
+    return found_tags;
+  }
+}
+
+/// Copies every project, tag and task from `from` into `to`, trash included.
+pub fn migrate(from: &dyn Storage, to: &mut dyn Storage) -> Result<(), StorageError> {
+  let trash = from.trash();
+  for project in from.projects().iter().chain(trash.projects.iter()) {
+    to.add_project(project)?;
+  }
+  for tag in from.tags().iter().chain(trash.tags.iter()) {
+    to.add_tag(tag)?;
+  }
+  to.replace_tasks(from.tasks())?;
+  for task in trash.tasks.iter() {
+    to.add_task(task)?;
+  }
+  Ok(())
 }
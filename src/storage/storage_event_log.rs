@@ -0,0 +1,760 @@
+use std::io::{BufRead, Read, Seek, SeekFrom, Write};
+use std::rc::Rc;
+
+use log::debug;
+
+use crate::{
+  clock::Clock,
+  project::Project,
+  tag::Tag,
+  task::Task,
+  traits::{Indexable, Trashable},
+};
+
+use super::{
+  atomic,
+  lock::{StorageLock, LOCK_TIMEOUT},
+  schema,
+  storage::{Storage, Trash},
+  StorageError,
+};
+
+const COMPACT_THRESHOLD: usize = 1000;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+  TaskAdded {
+    task: Task,
+  },
+  TaskStopped {
+    task: Task,
+  },
+  TaskPaused {
+    task: Task,
+  },
+  TaskResumed {
+    task: Task,
+  },
+  TaskReplaced {
+    task: Task,
+  },
+  TaskRemoved {
+    task_id: uuid::Uuid,
+  },
+  TasksReplaced {
+    tasks: Vec<Task>,
+  },
+  TagAdded {
+    tag: Tag,
+  },
+  TagReplaced {
+    tag: Tag,
+  },
+  TagRemoved {
+    tag_id: uuid::Uuid,
+  },
+  TagsReplaced {
+    tags: Vec<Tag>,
+  },
+  ProjectAdded {
+    project: Project,
+  },
+  ProjectReplaced {
+    project: Project,
+  },
+  ProjectRemoved {
+    project_id: uuid::Uuid,
+  },
+  Trashed {
+    id: uuid::Uuid,
+    deleted_at: chrono::DateTime<chrono::Local>,
+  },
+  Restored {
+    id: uuid::Uuid,
+  },
+  TrashPurged {
+    deleted_before: chrono::DateTime<chrono::Local>,
+  },
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct LogEntry {
+  pub at: chrono::DateTime<chrono::Local>,
+  #[serde(flatten)]
+  pub event: Event,
+}
+
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+struct Snapshot {
+  version: u64,
+  tasks: Vec<Task>,
+  projects: Vec<Project>,
+  tags: Vec<Tag>,
+}
+
+/// Storage which appends every mutation to `events.jsonl` and rebuilds the
+/// current state by replaying the log on top of the last `snapshot.json`.
+/// Events carry whole records, so replaying an event twice is harmless.
+pub struct EventLogStorage {
+  lock_path: String,
+  log_path: String,
+  archive_path: String,
+  snapshot_path: String,
+  state: Snapshot,
+  log_size: usize,
+  // events are stamped with it
+  clock: Rc<dyn Clock>,
+}
+
+impl EventLogStorage {
+  pub fn new(database_folder: &str, clock: Rc<dyn Clock>) -> Result<Self, StorageError> {
+    let database_path = std::path::Path::new(database_folder);
+    let join_path = |filename: &str| database_path.join(filename).to_str().unwrap().to_owned();
+
+    let mut storage = Self {
+      lock_path: join_path(".busy.lock"),
+      log_path: join_path("events.jsonl"),
+      archive_path: join_path("events.archive.jsonl"),
+      snapshot_path: join_path("snapshot.json"),
+      state: Snapshot::default(),
+      log_size: 0,
+      clock,
+    };
+
+    let _lock = StorageLock::shared(&storage.lock_path, LOCK_TIMEOUT)?;
+    storage.restore()?;
+
+    debug!(
+      "replayed {} events from: {}",
+      storage.log_size, storage.log_path
+    );
+
+    Ok(storage)
+  }
+
+  /// Every event recorded since the last compaction.
+  pub fn events(&self) -> Result<Vec<LogEntry>, String> {
+    read_log(&self.log_path)
+  }
+
+  /// Folds the log into the snapshot. Compacted events are moved into
+  /// `events.archive.jsonl`, so the audit trail is kept.
+  pub fn compact(&mut self) -> Result<(), StorageError> {
+    let _lock = StorageLock::exclusive(&self.lock_path, LOCK_TIMEOUT)?;
+    self.restore()?;
+    self.compact_locked()
+  }
+
+  fn compact_locked(&mut self) -> Result<(), StorageError> {
+    let log = match std::fs::read(&self.log_path) {
+      Ok(log) => log,
+      Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+      Err(err) => return Err(format!("can't read event log: {}", err).into()),
+    };
+
+    self.state.version = schema::SCHEMA_VERSION;
+    let snapshot = serde_json::to_string_pretty(&self.state).map_err(|err| err.to_string())?;
+    atomic::write_file(
+      std::path::Path::new(&self.snapshot_path),
+      snapshot.as_bytes(),
+    )
+    .map_err(|err| format!("can't write snapshot: {}", err))?;
+
+    append(&self.archive_path, &log).map_err(|err| format!("can't archive event log: {}", err))?;
+    std::fs::write(&self.log_path, "")
+      .map_err(|err| format!("can't truncate event log: {}", err))?;
+
+    debug!(
+      "compacted {} events into: {}",
+      self.log_size, self.snapshot_path
+    );
+    self.log_size = 0;
+    Ok(())
+  }
+
+  fn restore(&mut self) -> Result<(), StorageError> {
+    self.state = match std::fs::read_to_string(&self.snapshot_path) {
+      Ok(content) => {
+        let snapshot: Snapshot = serde_json::from_str(&content)
+          .map_err(|err| format!("can't load snapshot: {}, err: {}", self.snapshot_path, err))?;
+        schema::check_version(snapshot.version, schema::SCHEMA_VERSION)?;
+        snapshot
+      }
+      Err(err) if err.kind() == std::io::ErrorKind::NotFound => Snapshot::default(),
+      Err(err) => return Err(format!("can't read snapshot: {}", err).into()),
+    };
+
+    let entries = read_log(&self.log_path)?;
+    self.log_size = entries.len();
+    for entry in entries {
+      apply(&mut self.state, entry.event);
+    }
+    Ok(())
+  }
+
+  fn record<F>(&mut self, make_event: F) -> Result<(), StorageError>
+  where
+    F: FnOnce(&Snapshot) -> Result<Event, String>,
+  {
+    let _lock = StorageLock::exclusive(&self.lock_path, LOCK_TIMEOUT)?;
+    self.restore()?;
+
+    let entry = LogEntry {
+      at: self.clock.now(),
+      event: make_event(&self.state)?,
+    };
+    let mut line = serde_json::to_string(&entry).map_err(|err| err.to_string())?;
+    line.push('\n');
+    append(&self.log_path, line.as_bytes())
+      .map_err(|err| format!("can't write event log: {}", err))?;
+
+    apply(&mut self.state, entry.event);
+    self.log_size += 1;
+
+    if self.log_size >= COMPACT_THRESHOLD {
+      self.compact_locked()?;
+    }
+    Ok(())
+  }
+}
+
+impl Storage for EventLogStorage {
+  fn tasks(&self) -> Vec<Task> {
+    let mut tasks = alive(&self.state.tasks);
+    tasks.sort_by_key(|a| a.start_time());
+    return tasks;
+  }
+
+  fn add_task(&mut self, task: &Task) -> Result<(), StorageError> {
+    self.record(|_| Ok(Event::TaskAdded { task: task.clone() }))
+  }
+
+  fn remove_task(&mut self, task_id: uuid::Uuid) -> Result<(), StorageError> {
+    self.record(|state| {
+      if find(&state.tasks, task_id).is_none() {
+        return Err(format!("task with id: {} not found", task_id));
+      }
+      Ok(Event::TaskRemoved { task_id })
+    })
+  }
+
+  fn replace_task(&mut self, task: &Task) -> Result<(), StorageError> {
+    self.record(|state| match find(&state.tasks, task.id()) {
+      Some(position) => Ok(classify(&state.tasks[position], task)),
+      None => Err(format!("task with id: {} not found", task.id())),
+    })
+  }
+
+  fn replace_tasks(&mut self, tasks: Vec<Task>) -> Result<(), StorageError> {
+    self.record(|_| Ok(Event::TasksReplaced { tasks }))
+  }
+
+  fn add_tag(&mut self, tag: &Tag) -> Result<(), StorageError> {
+    self.record(|_| Ok(Event::TagAdded { tag: tag.clone() }))
+  }
+
+  fn replace_tag(&mut self, tag: &Tag) -> Result<(), StorageError> {
+    self.record(|state| match find(&state.tags, tag.id()) {
+      Some(_) => Ok(Event::TagReplaced { tag: tag.clone() }),
+      None => Err(format!("tag with id: {} not found", tag.id())),
+    })
+  }
+
+  fn remove_tag(&mut self, tag_id: uuid::Uuid) -> Result<(), StorageError> {
+    self.record(|state| match find(&state.tags, tag_id) {
+      Some(_) => Ok(Event::TagRemoved { tag_id }),
+      None => Err(format!("tag with id: {} not found", tag_id)),
+    })
+  }
+
+  fn replace_tags(&mut self, tags: Vec<Tag>) -> Result<(), StorageError> {
+    self.record(|_| Ok(Event::TagsReplaced { tags }))
+  }
+
+  fn tags(&self) -> Vec<Tag> {
+    alive(&self.state.tags)
+  }
+
+  fn add_project(&mut self, project: &Project) -> Result<(), StorageError> {
+    self.record(|_| {
+      Ok(Event::ProjectAdded {
+        project: project.clone(),
+      })
+    })
+  }
+
+  fn replace_project(&mut self, project: &Project) -> Result<(), StorageError> {
+    self.record(|state| match find(&state.projects, project.id()) {
+      Some(_) => Ok(Event::ProjectReplaced {
+        project: project.clone(),
+      }),
+      None => Err(format!("project with id: {} not found", project.id())),
+    })
+  }
+
+  fn remove_project(&mut self, project_id: uuid::Uuid) -> Result<(), StorageError> {
+    self.record(|state| match find(&state.projects, project_id) {
+      Some(_) => Ok(Event::ProjectRemoved { project_id }),
+      None => Err(format!("project with id: {} not found", project_id)),
+    })
+  }
+
+  fn projects(&self) -> Vec<Project> {
+    alive(&self.state.projects)
+  }
+
+  fn trash(&self) -> Trash {
+    Trash {
+      tasks: trashed(&self.state.tasks),
+      projects: trashed(&self.state.projects),
+      tags: trashed(&self.state.tags),
+    }
+  }
+
+  fn move_to_trash(
+    &mut self,
+    id: uuid::Uuid,
+    deleted_at: chrono::DateTime<chrono::Local>,
+  ) -> Result<(), StorageError> {
+    self.record(|state| match trashed_state(state, id) {
+      Some(false) => Ok(Event::Trashed { id, deleted_at }),
+      _ => Err(format!("item with id: {} not found", id)),
+    })
+  }
+
+  fn restore_from_trash(&mut self, id: uuid::Uuid) -> Result<(), StorageError> {
+    self.record(|state| match trashed_state(state, id) {
+      Some(true) => Ok(Event::Restored { id }),
+      _ => Err(format!("item with id: {} not found in trash", id)),
+    })
+  }
+
+  fn purge_trash(
+    &mut self,
+    deleted_before: chrono::DateTime<chrono::Local>,
+  ) -> Result<Trash, StorageError> {
+    let is_expired =
+      |at: Option<chrono::DateTime<chrono::Local>>| at.is_some_and(|at| at < deleted_before);
+    let mut purged = self.trash();
+    purged.tasks.retain(|task| is_expired(task.deleted_at()));
+    purged
+      .projects
+      .retain(|project| is_expired(project.deleted_at()));
+    purged.tags.retain(|tag| is_expired(tag.deleted_at()));
+    if purged.is_empty() {
+      return Ok(purged);
+    }
+
+    self.record(|_| Ok(Event::TrashPurged { deleted_before }))?;
+    Ok(purged)
+  }
+}
+
+// Storage only sees whole task replacements, so the kind of change is derived
+// from the difference between the stored and the new task.
+fn classify(old_task: &Task, new_task: &Task) -> Event {
+  let task = new_task.clone();
+  let old_times = old_task.times();
+  let new_times = new_task.times();
+  // hand-edited data can have tasks without intervals
+  let last_index = match old_times.len().checked_sub(1) {
+    Some(last_index) if !new_times.is_empty() => last_index,
+    _ => return Event::TaskReplaced { task },
+  };
+  let same_times_prefix = |len: usize| {
+    old_times[..len]
+      .iter()
+      .zip(new_times[..len].iter())
+      .all(|(old, new)| old.start_time == new.start_time && old.stop_time == new.stop_time)
+  };
+
+  let was_running = old_task.stop_time().is_none();
+  let is_running = new_task.stop_time().is_none();
+
+  if old_times.len() == new_times.len() && same_times_prefix(last_index) {
+    let old_last = old_times.last().unwrap();
+    let new_last = new_times.last().unwrap();
+    if was_running && !is_running && old_last.start_time == new_last.start_time {
+      return match new_task.is_paused() {
+        true => Event::TaskPaused { task },
+        false => Event::TaskStopped { task },
+      };
+    }
+  }
+
+  if new_times.len() == old_times.len() + 1
+    && same_times_prefix(old_times.len())
+    && !was_running
+    && is_running
+  {
+    return Event::TaskResumed { task };
+  }
+
+  return Event::TaskReplaced { task };
+}
+
+fn apply(state: &mut Snapshot, event: Event) {
+  match event {
+    Event::TaskAdded { task }
+    | Event::TaskStopped { task }
+    | Event::TaskPaused { task }
+    | Event::TaskResumed { task }
+    | Event::TaskReplaced { task } => upsert(&mut state.tasks, task),
+    Event::TaskRemoved { task_id } => state.tasks.retain(|task| task.id() != task_id),
+    Event::TasksReplaced { tasks } => replace_alive(&mut state.tasks, tasks),
+    Event::TagAdded { tag } | Event::TagReplaced { tag } => upsert(&mut state.tags, tag),
+    Event::TagRemoved { tag_id } => state.tags.retain(|tag| tag.id() != tag_id),
+    Event::TagsReplaced { tags } => replace_alive(&mut state.tags, tags),
+    Event::ProjectAdded { project } | Event::ProjectReplaced { project } => {
+      upsert(&mut state.projects, project)
+    }
+    Event::ProjectRemoved { project_id } => {
+      state.projects.retain(|project| project.id() != project_id)
+    }
+    Event::Trashed { id, deleted_at } => set_deleted_at(state, id, Some(deleted_at)),
+    Event::Restored { id } => set_deleted_at(state, id, None),
+    Event::TrashPurged { deleted_before } => {
+      let is_kept =
+        |at: Option<chrono::DateTime<chrono::Local>>| at.is_none_or(|at| at >= deleted_before);
+      state.tasks.retain(|task| is_kept(task.deleted_at()));
+      state
+        .projects
+        .retain(|project| is_kept(project.deleted_at()));
+      state.tags.retain(|tag| is_kept(tag.deleted_at()));
+    }
+  }
+}
+
+fn set_deleted_at(
+  state: &mut Snapshot,
+  id: uuid::Uuid,
+  deleted_at: Option<chrono::DateTime<chrono::Local>>,
+) {
+  fn update<T: Indexable + Trashable>(
+    items: &mut [T],
+    id: uuid::Uuid,
+    deleted_at: Option<chrono::DateTime<chrono::Local>>,
+  ) {
+    if let Some(item) = items.iter_mut().find(|item| item.id() == id) {
+      item.set_deleted_at(deleted_at);
+    }
+  }
+  update(&mut state.tasks, id, deleted_at);
+  update(&mut state.projects, id, deleted_at);
+  update(&mut state.tags, id, deleted_at);
+}
+
+// Returns whether the record with the id is in the trash, `None` if there is
+// no such record.
+fn trashed_state(state: &Snapshot, id: uuid::Uuid) -> Option<bool> {
+  fn lookup<T: Indexable + Trashable>(items: &[T], id: uuid::Uuid) -> Option<bool> {
+    items
+      .iter()
+      .find(|item| item.id() == id)
+      .map(|item| item.is_deleted())
+  }
+  lookup(&state.tasks, id)
+    .or_else(|| lookup(&state.projects, id))
+    .or_else(|| lookup(&state.tags, id))
+}
+
+fn alive<T: Trashable + Clone>(items: &[T]) -> Vec<T> {
+  items
+    .iter()
+    .filter(|item| !item.is_deleted())
+    .cloned()
+    .collect()
+}
+
+fn trashed<T: Trashable + Clone>(items: &[T]) -> Vec<T> {
+  items
+    .iter()
+    .filter(|item| item.is_deleted())
+    .cloned()
+    .collect()
+}
+
+// Trashed records are kept unless the new items replace them.
+fn replace_alive<T: Indexable + Trashable>(items: &mut Vec<T>, new_items: Vec<T>) {
+  items.retain(|item| item.is_deleted() && !new_items.iter().any(|new| new.id() == item.id()));
+  items.extend(new_items);
+}
+
+fn upsert<T: Indexable>(items: &mut Vec<T>, item: T) {
+  match find(items, item.id()) {
+    Some(position) => items[position] = item,
+    None => items.push(item),
+  }
+}
+
+fn find<T: Indexable>(items: &[T], id: uuid::Uuid) -> Option<usize> {
+  items.iter().position(|item| item.id() == id)
+}
+
+fn read_log(log_path: &str) -> Result<Vec<LogEntry>, String> {
+  let file = match std::fs::File::open(log_path) {
+    Ok(file) => file,
+    Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+    Err(err) => return Err(format!("can't read event log: {}, err: {}", log_path, err)),
+  };
+
+  let mut entries = Vec::new();
+  let lines: Vec<String> = std::io::BufReader::new(file)
+    .lines()
+    .collect::<std::io::Result<_>>()
+    .map_err(|err| format!("can't read event log: {}, err: {}", log_path, err))?;
+  let lines_count = lines.len();
+  for (index, line) in lines.into_iter().enumerate() {
+    if line.trim().is_empty() {
+      continue;
+    }
+    match serde_json::from_str(&line) {
+      Ok(entry) => entries.push(entry),
+      // a crash in the middle of an append leaves the last line unfinished
+      Err(err) if index + 1 == lines_count => {
+        debug!("skip unfinished event log line: {}, err: {}", line, err)
+      }
+      Err(err) => {
+        return Err(format!(
+          "event log: {} is corrupted at line {}, err: {}",
+          log_path,
+          index + 1,
+          err
+        ))
+      }
+    }
+  }
+  return Ok(entries);
+}
+
+fn append(filepath: &str, content: &[u8]) -> std::io::Result<()> {
+  let mut file = std::fs::OpenOptions::new()
+    .create(true)
+    .truncate(false)
+    .read(true)
+    .write(true)
+    .open(filepath)?;
+  let end = drop_unfinished_line(&mut file)?;
+  file.seek(SeekFrom::Start(end))?;
+  file.write_all(content)?;
+  file.sync_all()
+}
+
+// A crash in the middle of an append leaves the last line unfinished, it's
+// cut off so the next line isn't glued to it. Returns the new file length.
+fn drop_unfinished_line(file: &mut std::fs::File) -> std::io::Result<u64> {
+  let len = file.metadata()?.len();
+  let mut chunk = [0u8; 4096];
+  let mut end = len;
+  while end > 0 {
+    let start = end.saturating_sub(chunk.len() as u64);
+    let size = (end - start) as usize;
+    file.seek(SeekFrom::Start(start))?;
+    file.read_exact(&mut chunk[..size])?;
+    if let Some(position) = chunk[..size].iter().rposition(|byte| *byte == b'\n') {
+      end = start + position as u64 + 1;
+      break;
+    }
+    end = start;
+  }
+  if end < len {
+    debug!("drop unfinished event log line of {} bytes", len - end);
+    file.set_len(end)?;
+  }
+  Ok(end)
+}
+
+#[cfg(test)]
+mod test {
+  use std::rc::Rc;
+
+  use super::{Event, EventLogStorage};
+  use crate::clock::{ManualClock, SystemClock};
+  use crate::{project::Project, storage::Storage, task::Task, traits::Indexable};
+
+  fn open(tmp_dir: &tempfile::TempDir) -> EventLogStorage {
+    EventLogStorage::new(tmp_dir.path().to_str().unwrap(), Rc::new(SystemClock)).unwrap()
+  }
+
+  fn get_new_storage() -> (tempfile::TempDir, EventLogStorage) {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let storage = open(&tmp_dir);
+    (tmp_dir, storage)
+  }
+
+  #[test]
+  fn event_log_replay() {
+    let (tmp_dir, mut storage) = get_new_storage();
+    let project = Project::new("project");
+    storage.add_project(&project).unwrap();
+
+    let mut task = Task::new(project.id(), "title", vec![], None, None, &SystemClock);
+    storage.add_task(&task).unwrap();
+    task.pause(chrono::Local::now()).unwrap();
+    storage.replace_task(&task).unwrap();
+    task.resume(chrono::Local::now()).unwrap();
+    storage.replace_task(&task).unwrap();
+    task.stop(chrono::Local::now()).unwrap();
+    storage.replace_task(&task).unwrap();
+
+    let events: Vec<Event> = storage
+      .events()
+      .unwrap()
+      .into_iter()
+      .map(|entry| entry.event)
+      .collect();
+    assert!(matches!(events[0], Event::ProjectAdded { .. }));
+    assert!(matches!(events[1], Event::TaskAdded { .. }));
+    assert!(matches!(events[2], Event::TaskPaused { .. }));
+    assert!(matches!(events[3], Event::TaskResumed { .. }));
+    assert!(matches!(events[4], Event::TaskStopped { .. }));
+
+    let replayed = open(&tmp_dir);
+    let tasks = replayed.tasks();
+    assert_eq!(tasks.len(), 1);
+    assert_eq!(tasks[0].times().len(), 2);
+    assert!(tasks[0].stop_time().is_some());
+    assert_eq!(replayed.projects().len(), 1);
+  }
+
+  #[test]
+  fn event_log_compaction() {
+    let (tmp_dir, mut storage) = get_new_storage();
+    let task = Task::new(
+      uuid::Uuid::new_v4(),
+      "title",
+      vec![],
+      None,
+      None,
+      &SystemClock,
+    );
+    storage.add_task(&task).unwrap();
+    storage.compact().unwrap();
+    storage.remove_task(task.id()).unwrap();
+    storage
+      .add_task(&Task::new(
+        uuid::Uuid::new_v4(),
+        "new",
+        vec![],
+        None,
+        None,
+        &SystemClock,
+      ))
+      .unwrap();
+
+    assert_eq!(storage.events().unwrap().len(), 2);
+
+    let replayed = open(&tmp_dir);
+    let tasks = replayed.tasks();
+    assert_eq!(tasks.len(), 1);
+    assert_eq!(tasks[0].title(), "new");
+  }
+
+  #[test]
+  fn event_log_trash_replay() {
+    let (tmp_dir, mut storage) = get_new_storage();
+    let first = Task::new(
+      uuid::Uuid::new_v4(),
+      "first",
+      vec![],
+      None,
+      None,
+      &SystemClock,
+    );
+    let second = Task::new(
+      uuid::Uuid::new_v4(),
+      "second",
+      vec![],
+      None,
+      None,
+      &SystemClock,
+    );
+    storage.add_task(&first).unwrap();
+    storage.add_task(&second).unwrap();
+
+    let deleted_at = chrono::Local::now();
+    storage.move_to_trash(first.id(), deleted_at).unwrap();
+    storage.move_to_trash(second.id(), deleted_at).unwrap();
+    storage.restore_from_trash(second.id()).unwrap();
+    storage.replace_tasks(vec![second.clone()]).unwrap();
+
+    let replayed = open(&tmp_dir);
+    assert_eq!(replayed.tasks()[0].id(), second.id());
+    assert_eq!(replayed.trash().tasks[0].id(), first.id());
+
+    storage
+      .purge_trash(deleted_at + chrono::Duration::seconds(1))
+      .unwrap();
+    let replayed = open(&tmp_dir);
+    assert!(replayed.trash().is_empty());
+    assert_eq!(replayed.tasks().len(), 1);
+  }
+
+  #[test]
+  fn event_log_uses_clock() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let now = chrono::Local::now() - chrono::Duration::days(3);
+    let clock = Rc::new(ManualClock::new(now));
+    let mut storage =
+      EventLogStorage::new(tmp_dir.path().to_str().unwrap(), clock.clone()).unwrap();
+    storage.add_project(&Project::new("project")).unwrap();
+    clock.advance(chrono::Duration::minutes(5));
+    storage.add_project(&Project::new("other")).unwrap();
+
+    let stamps: Vec<_> = storage
+      .events()
+      .unwrap()
+      .into_iter()
+      .map(|entry| entry.at)
+      .collect();
+    assert_eq!(stamps, vec![now, now + chrono::Duration::minutes(5)]);
+  }
+
+  #[test]
+  fn event_log_drops_unfinished_line() {
+    let (tmp_dir, mut storage) = get_new_storage();
+    storage.add_project(&Project::new("first")).unwrap();
+    let log_path = tmp_dir.path().join("events.jsonl");
+    let mut log = std::fs::OpenOptions::new()
+      .append(true)
+      .open(&log_path)
+      .unwrap();
+    std::io::Write::write_all(&mut log, b"{\"at\":\"2024-03-14T09:").unwrap();
+
+    let mut storage = open(&tmp_dir);
+    storage.add_project(&Project::new("second")).unwrap();
+    storage.add_project(&Project::new("third")).unwrap();
+
+    let replayed = open(&tmp_dir);
+    assert_eq!(replayed.events().unwrap().len(), 3);
+    let mut names: Vec<String> = replayed
+      .projects()
+      .iter()
+      .map(|project| project.name().to_owned())
+      .collect();
+    names.sort();
+    assert_eq!(names, ["first", "second", "third"]);
+  }
+
+  #[test]
+  fn event_log_replace_task_without_times() {
+    let (_tmp_dir, mut storage) = get_new_storage();
+    let task = Task::new(
+      uuid::Uuid::new_v4(),
+      "title",
+      vec![],
+      None,
+      None,
+      &SystemClock,
+    );
+    let mut broken = task.clone();
+    broken.set_times(vec![]);
+    storage.add_task(&broken).unwrap();
+    storage.replace_task(&task).unwrap();
+
+    let events = storage.events().unwrap();
+    assert!(matches!(events[1].event, Event::TaskReplaced { .. }));
+    assert_eq!(storage.tasks()[0].times().len(), 1);
+  }
+}
@@ -1,28 +1,61 @@
-use std::io::{Seek, Write};
-
 use log::debug;
 
-use crate::{project::Project, tag::Tag, task::Task, traits::Indexable};
-
-use super::storage::Storage;
+use crate::{
+  project::Project,
+  tag::Tag,
+  task::Task,
+  traits::{Indexable, Trashable},
+};
+
+use super::{
+  atomic,
+  lock::{StorageLock, LOCK_TIMEOUT},
+  schema,
+  storage::{Storage, Trash},
+  StorageError,
+};
 
 pub struct JsonStorage {
+  lock_path: String,
   tasks: JsonStorageItem<Task>,
   projects: JsonStorageItem<Project>,
   tags: JsonStorageItem<Tag>,
 }
 
 impl JsonStorage {
-  pub fn new(database_folder: &str) -> Self {
+  pub fn new(database_folder: &str) -> Result<Self, StorageError> {
     let database_path = std::path::Path::new(database_folder);
 
     let join_path = |filename: &str| database_path.join(filename).to_str().unwrap().to_owned();
 
-    Self {
-      tasks: JsonStorageItem::new(join_path("tasks.json").as_str()),
-      projects: JsonStorageItem::new(join_path("projects.json").as_str()),
-      tags: JsonStorageItem::new(join_path("tags.json").as_str()),
-    }
+    let lock_path = join_path(".busy.lock");
+    let _lock = StorageLock::shared(&lock_path, LOCK_TIMEOUT)?;
+
+    Ok(Self {
+      tasks: JsonStorageItem::new(join_path("tasks.json").as_str())?,
+      projects: JsonStorageItem::new(join_path("projects.json").as_str())?,
+      tags: JsonStorageItem::new(join_path("tags.json").as_str())?,
+      lock_path,
+    })
+  }
+
+  // Another busy process could change the files since they were loaded, so
+  // every mutation reloads them under the exclusive lock before writing.
+  fn locked<F>(&mut self, mutate: F) -> Result<(), StorageError>
+  where
+    F: FnOnce(&mut Self) -> Result<(), StorageError>,
+  {
+    let _lock = StorageLock::exclusive(&self.lock_path, LOCK_TIMEOUT)?;
+    self.tasks.restore()?;
+    self.projects.restore()?;
+    self.tags.restore()?;
+    mutate(self)
+  }
+
+  pub fn exists(database_folder: &str) -> bool {
+    std::path::Path::new(database_folder)
+      .join("tasks.json")
+      .exists()
   }
 
   pub fn tasks_filepath(&self) -> &str {
@@ -32,133 +65,128 @@ impl JsonStorage {
   pub fn tags_filepath(&self) -> &str {
     self.tags.storage_path()
   }
-
-  fn ids(&self) -> Vec<uuid::Uuid> {
-    // TODO: optimize
-    let mut ids = Vec::new();
-    for task in self.tasks() {
-      ids.push(task.id().clone());
-    }
-    for project in self.projects() {
-      ids.push(project.id().clone());
-    }
-    for tag in self.tags() {
-      ids.push(tag.id().clone());
-    }
-    return ids;
-  }
 }
 
 impl Storage for JsonStorage {
-  fn shorten_id(&self, id: uuid::Uuid) -> String {
-    let id_string = id.as_simple().to_string();
-    format!(
-      "{}..{}",
-      &id_string[0..4],
-      &id_string[id_string.len() - 4..id_string.len()]
-    )
-  }
-
-  fn resolve_id(&self, short_id: &str) -> Option<uuid::Uuid> {
-    let ids = self.ids();
-    let item = ids.iter().find(|&id| {
-      let formatted_id = self.shorten_id(*id);
-      return formatted_id == short_id;
-    });
-
-    if item.is_some() {
-      return Some(item.unwrap().clone());
-    }
-    return None;
-  }
-
-  fn add_task(&mut self, task: &Task) {
-    self.tasks.add(task.clone());
+  fn add_task(&mut self, task: &Task) -> Result<(), StorageError> {
+    self.locked(|storage| storage.tasks.add(task.clone()))
   }
 
-  fn remove_task(&mut self, task_id: uuid::Uuid) -> Result<(), String> {
-    self.tasks.remove(task_id)
+  fn remove_task(&mut self, task_id: uuid::Uuid) -> Result<(), StorageError> {
+    self.locked(|storage| storage.tasks.remove(task_id))
   }
 
-  fn replace_task(&mut self, task: &Task) -> Result<(), String> {
-    self.tasks.replace(task)
+  fn replace_task(&mut self, task: &Task) -> Result<(), StorageError> {
+    self.locked(|storage| storage.tasks.replace(task))
   }
 
-  fn replace_tasks(&mut self, tasks: Vec<Task>) {
-    self.tasks.replace_all(tasks);
+  fn replace_tasks(&mut self, tasks: Vec<Task>) -> Result<(), StorageError> {
+    self.locked(|storage| storage.tasks.replace_alive(tasks))
   }
 
-  fn replace_tags(&mut self, tags: Vec<Tag>) {
-    self.tags.replace_all(tags);
+  fn replace_tags(&mut self, tags: Vec<Tag>) -> Result<(), StorageError> {
+    self.locked(|storage| storage.tags.replace_alive(tags))
   }
 
   fn tasks(&self) -> Vec<Task> {
-    let mut tasks = self.tasks.all();
-    tasks.sort_by(|a, b| a.start_time().cmp(&b.start_time()));
+    let mut tasks = self.tasks.alive();
+    tasks.sort_by_key(|a| a.start_time());
     return tasks;
   }
 
-  fn find_tag_by_name(&self, tag: &str) -> Option<Tag> {
-    match self.tags.all().iter().find(|t| t.name() == tag) {
-      Some(found_tag) => Some(found_tag.clone()),
-      _ => None,
-    }
+  fn tag_by_id(&self, id: uuid::Uuid) -> Option<Tag> {
+    self
+      .tags
+      .get_by_id(id)
+      .filter(|tag| !tag.is_deleted())
+      .cloned()
   }
 
-  fn find_tag_by_names(&self, tag_strs: &Vec<String>) -> Vec<Tag> {
-    let mut tags = Vec::with_capacity(tag_strs.len());
-    for tag_str in tag_strs.iter() {
-      let found_tag = self.find_tag_by_name(tag_str);
-      if found_tag.is_some() {
-        tags.push(found_tag.unwrap().clone());
-      }
-    }
-    return tags;
+  fn add_tag(&mut self, tag: &Tag) -> Result<(), StorageError> {
+    self.locked(|storage| storage.tags.add(tag.clone()))
   }
 
-  fn find_tags(&self, tag_ids: &Vec<uuid::Uuid>) -> Vec<Tag> {
-    let mut tags = Vec::new();
-    for tag_id in tag_ids.iter() {
-      match self.tag_by_id(*tag_id) {
-        Some(found_tag) => tags.push(found_tag.clone()),
-        _ => {}
-      };
-    }
-    return tags;
+  fn tags(&self) -> Vec<Tag> {
+    self.tags.alive()
   }
 
-  fn tag_by_id(&self, id: uuid::Uuid) -> Option<&Tag> {
-    self.tags.get_by_id(id)
+  fn replace_tag(&mut self, tag: &Tag) -> Result<(), StorageError> {
+    self.locked(|storage| storage.tags.replace(tag))
   }
 
-  fn add_tag(&mut self, tag: &Tag) {
-    self.tags.add(tag.clone());
+  fn remove_tag(&mut self, tag_id: uuid::Uuid) -> Result<(), StorageError> {
+    self.locked(|storage| storage.tags.remove(tag_id))
   }
 
-  fn tags(&self) -> Vec<Tag> {
-    self.tags.all()
+  fn add_project(&mut self, project: &Project) -> Result<(), StorageError> {
+    self.locked(|storage| storage.projects.add(project.clone()))
+  }
+
+  fn projects(&self) -> Vec<Project> {
+    self.projects.alive()
   }
 
-  fn replace_tag(&mut self, tag: &Tag) -> Result<(), String> {
-    self.tags.replace(tag)
+  fn replace_project(&mut self, project: &Project) -> Result<(), StorageError> {
+    self.locked(|storage| storage.projects.replace(project))
   }
 
-  fn add_project(&mut self, project: &Project) {
-    self.projects.add(project.clone());
+  fn remove_project(&mut self, project_id: uuid::Uuid) -> Result<(), StorageError> {
+    self.locked(|storage| storage.projects.remove(project_id))
   }
 
-  fn projects(&self) -> Vec<Project> {
-    self.projects.all()
+  fn trash(&self) -> Trash {
+    Trash {
+      tasks: self.tasks.trashed(),
+      projects: self.projects.trashed(),
+      tags: self.tags.trashed(),
+    }
+  }
+
+  fn move_to_trash(
+    &mut self,
+    id: uuid::Uuid,
+    deleted_at: chrono::DateTime<chrono::Local>,
+  ) -> Result<(), StorageError> {
+    self.locked(|storage| {
+      let found = storage.tasks.set_deleted_at(id, Some(deleted_at))?
+        || storage.projects.set_deleted_at(id, Some(deleted_at))?
+        || storage.tags.set_deleted_at(id, Some(deleted_at))?;
+      match found {
+        true => Ok(()),
+        false => Err(format!("item with id: {} not found", id).into()),
+      }
+    })
   }
 
-  fn replace_project(&mut self, project: &Project) -> Result<(), String> {
-    self.projects.replace(project)
+  fn restore_from_trash(&mut self, id: uuid::Uuid) -> Result<(), StorageError> {
+    self.locked(|storage| {
+      let found = storage.tasks.set_deleted_at(id, None)?
+        || storage.projects.set_deleted_at(id, None)?
+        || storage.tags.set_deleted_at(id, None)?;
+      match found {
+        true => Ok(()),
+        false => Err(format!("item with id: {} not found in trash", id).into()),
+      }
+    })
+  }
+
+  fn purge_trash(
+    &mut self,
+    deleted_before: chrono::DateTime<chrono::Local>,
+  ) -> Result<Trash, StorageError> {
+    let mut purged = Trash::default();
+    self.locked(|storage| {
+      purged.tasks = storage.tasks.purge(deleted_before)?;
+      purged.projects = storage.projects.purge(deleted_before)?;
+      purged.tags = storage.tags.purge(deleted_before)?;
+      Ok(())
+    })?;
+    Ok(purged)
   }
 }
 
 struct JsonStorageItem<T> {
   filepath: String,
-  file: std::fs::File,
   buffer: Vec<T>,
 }
 
@@ -166,19 +194,13 @@ impl<T> JsonStorageItem<T>
 where
   T: Indexable + Clone + serde::de::DeserializeOwned + serde::ser::Serialize,
 {
-  fn new(filepath: &str) -> Self {
+  fn new(filepath: &str) -> Result<Self, StorageError> {
     let mut storage_item = Self {
       filepath: filepath.to_owned(),
-      file: std::fs::OpenOptions::new()
-        .create(true)
-        .append(true)
-        .read(true)
-        .open(filepath)
-        .unwrap(),
       buffer: Vec::new(),
     };
 
-    storage_item.restore();
+    storage_item.restore()?;
 
     debug!(
       "restored {} items from: {}",
@@ -186,56 +208,70 @@ where
       filepath
     );
 
-    return storage_item;
+    return Ok(storage_item);
   }
 
   fn storage_path(&self) -> &str {
     self.filepath.as_str()
   }
 
+  fn backup_path(&self) -> String {
+    format!("{}.bak", self.filepath)
+  }
+
   fn get_by_id(&self, id: uuid::Uuid) -> Option<&T> {
     self.buffer.iter().find(|item| item.id() == id)
   }
 
-  fn restore(&mut self) {
-    self.file.rewind().unwrap();
-    self.buffer = serde_json::from_reader(&self.file).unwrap_or_default();
+  fn restore(&mut self) -> Result<(), StorageError> {
+    let content = match std::fs::read_to_string(&self.filepath) {
+      Ok(content) => content,
+      Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+      Err(err) => {
+        return Err(format!("can't read db file: {}, err: {}", self.filepath, err).into())
+      }
+    };
+
+    self.buffer = schema::decode(&content).map_err(|err| {
+      let mut message = format!("can't load db file: {}, err: {}", self.filepath, err);
+      let backup_path = self.backup_path();
+      if std::path::Path::new(&backup_path).exists() {
+        message.push_str(&format!(". Previous version is kept in: {}", backup_path));
+      }
+      message
+    })?;
+    Ok(())
   }
 
-  fn add(&mut self, item: T) {
-    self.buffer.push(item.clone());
-    self.flush();
+  fn add(&mut self, item: T) -> Result<(), StorageError> {
+    let mut buffer = self.buffer.clone();
+    buffer.push(item);
+    self.save(buffer)
   }
 
-  fn remove(&mut self, id: uuid::Uuid) -> Result<(), String> {
+  fn remove(&mut self, id: uuid::Uuid) -> Result<(), StorageError> {
     let position = self.position_by_id(id);
     if position.is_none() {
-      return Err(format!("task with id: {} not found", id));
+      return Err(format!("item with id: {} not found", id).into());
     }
 
-    self.buffer.remove(position.unwrap());
-    self.flush();
-
-    Ok(())
+    let mut buffer = self.buffer.clone();
+    buffer.remove(position.unwrap());
+    self.save(buffer)
   }
 
-  fn replace(&mut self, item: &T) -> Result<(), String> {
-    let position = self.position_by_id(item.id().clone());
+  fn replace(&mut self, item: &T) -> Result<(), StorageError> {
+    let position = self.position_by_id(item.id());
     if position.is_none() {
-      return Err(format!("task with id: {} not found", item.id()));
+      return Err(format!("item with id: {} not found", item.id()).into());
     }
 
-    self.buffer[position.unwrap()] = item.clone();
-    self.flush();
-
-    Ok(())
-  }
-
-  fn replace_all(&mut self, items: Vec<T>) {
-    self.buffer = items;
-    self.flush();
+    let mut buffer = self.buffer.clone();
+    buffer[position.unwrap()] = item.clone();
+    self.save(buffer)
   }
 
+  #[cfg(test)]
   fn all(&self) -> Vec<T> {
     self.buffer.clone()
   }
@@ -244,25 +280,99 @@ where
     self.buffer.iter().position(|item| item.id() == id)
   }
 
-  fn flush(&mut self) {
-    self.file.set_len(0).unwrap();
-    self.file.rewind().unwrap();
+  fn save(&mut self, buffer: Vec<T>) -> Result<(), StorageError> {
     self
-      .file
-      .write_all(
-        serde_json::to_string_pretty(&self.buffer)
-          .unwrap()
-          .as_bytes(),
-      )
-      .expect("can't write information to db");
+      .flush(&buffer)
+      .map_err(|err| format!("can't save db file: {}, err: {}", self.filepath, err))?;
+    self.buffer = buffer;
+    Ok(())
+  }
 
-    self.file.flush().expect("save db erorr");
+  fn flush(&self, buffer: &Vec<T>) -> std::io::Result<()> {
+    let content = schema::encode(buffer).map_err(std::io::Error::other)?;
+
+    let filepath = std::path::Path::new(&self.filepath);
+    if filepath.exists() {
+      std::fs::copy(filepath, self.backup_path())?;
+    }
+    atomic::write_file(filepath, content.as_bytes())
+  }
+}
+
+impl<T> JsonStorageItem<T>
+where
+  T: Indexable + Trashable + Clone + serde::de::DeserializeOwned + serde::ser::Serialize,
+{
+  fn alive(&self) -> Vec<T> {
+    self
+      .buffer
+      .iter()
+      .filter(|item| !item.is_deleted())
+      .cloned()
+      .collect()
+  }
+
+  fn trashed(&self) -> Vec<T> {
+    self
+      .buffer
+      .iter()
+      .filter(|item| item.is_deleted())
+      .cloned()
+      .collect()
+  }
+
+  // Returns false if there is no item with the id which could be moved
+  // to or restored from the trash.
+  fn set_deleted_at(
+    &mut self,
+    id: uuid::Uuid,
+    deleted_at: Option<chrono::DateTime<chrono::Local>>,
+  ) -> Result<bool, StorageError> {
+    let position = self
+      .buffer
+      .iter()
+      .position(|item| item.id() == id && item.is_deleted() != deleted_at.is_some());
+    if position.is_none() {
+      return Ok(false);
+    }
+
+    let mut buffer = self.buffer.clone();
+    buffer[position.unwrap()].set_deleted_at(deleted_at);
+    self.save(buffer)?;
+    Ok(true)
+  }
+
+  fn purge(
+    &mut self,
+    deleted_before: chrono::DateTime<chrono::Local>,
+  ) -> Result<Vec<T>, StorageError> {
+    let (purged, kept): (Vec<T>, Vec<T>) = self
+      .buffer
+      .iter()
+      .cloned()
+      .partition(|item| item.deleted_at().is_some_and(|at| at < deleted_before));
+    if !purged.is_empty() {
+      self.save(kept)?;
+    }
+    Ok(purged)
+  }
+
+  fn replace_alive(&mut self, items: Vec<T>) -> Result<(), StorageError> {
+    let mut buffer: Vec<T> = self
+      .buffer
+      .iter()
+      .filter(|item| item.is_deleted() && !items.iter().any(|new| new.id() == item.id()))
+      .cloned()
+      .collect();
+    buffer.extend(items);
+    self.save(buffer)
   }
 }
 
 #[cfg(test)]
 mod test {
   use super::{Indexable, JsonStorageItem};
+  use crate::clock::SystemClock;
 
   #[derive(Clone, serde::Serialize, serde::Deserialize)]
   struct TestType {
@@ -296,7 +406,7 @@ mod test {
       .tempfile()
       .unwrap();
 
-    JsonStorageItem::<TestType>::new(tmp_file.into_temp_path().to_str().unwrap())
+    JsonStorageItem::<TestType>::new(tmp_file.into_temp_path().to_str().unwrap()).unwrap()
   }
 
   #[test]
@@ -304,7 +414,7 @@ mod test {
     let mut storage = get_new_storage();
     let new_item = TestType::new("Hello");
 
-    storage.add(new_item);
+    storage.add(new_item).unwrap();
     let all_items = storage.all();
 
     assert_eq!(all_items.len(), 1);
@@ -314,13 +424,13 @@ mod test {
   fn storage_item_remove() {
     let mut storage = get_new_storage();
     let new_item = TestType::new("Hello");
-    let id = new_item.id().clone();
+    let id = new_item.id();
 
-    storage.add(new_item);
-    storage.remove(id.clone()).unwrap();
+    storage.add(new_item).unwrap();
+    storage.remove(id).unwrap();
     let all_items = storage.all();
 
-    assert_eq!(all_items.is_empty(), true);
+    assert!(all_items.is_empty());
   }
 
   #[test]
@@ -336,7 +446,7 @@ mod test {
     let mut storage = get_new_storage();
     let item = TestType::new("Hello");
     let id = item.id();
-    storage.add(item);
+    storage.add(item).unwrap();
 
     let mut new_item = TestType::new("Hello, world!");
     new_item.id = id;
@@ -347,4 +457,114 @@ mod test {
     assert_eq!(all_items.len(), 1);
     assert_eq!(all_items[0].title(), "Hello, world!");
   }
+
+  #[test]
+  fn storage_item_keeps_backup() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let filepath = tmp_dir.path().join("items.json");
+    let mut storage = JsonStorageItem::<TestType>::new(filepath.to_str().unwrap()).unwrap();
+
+    storage.add(TestType::new("first")).unwrap();
+    storage.add(TestType::new("second")).unwrap();
+
+    let backup_path = tmp_dir.path().join("items.json.bak");
+    let backup: Vec<TestType> =
+      super::schema::decode(&std::fs::read_to_string(backup_path).unwrap()).unwrap();
+    assert_eq!(backup.len(), 1);
+    assert_eq!(backup[0].title(), "first");
+
+    let restored = JsonStorageItem::<TestType>::new(filepath.to_str().unwrap()).unwrap();
+    assert_eq!(restored.all().len(), 2);
+  }
+
+  #[test]
+  fn storage_item_corrupted_file() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let filepath = tmp_dir.path().join("items.json");
+    std::fs::write(&filepath, "[{\"id\": ").unwrap();
+
+    let err = JsonStorageItem::<TestType>::new(filepath.to_str().unwrap())
+      .err()
+      .unwrap()
+      .to_string();
+    assert!(!err.contains("Previous version"), "{}", err);
+
+    std::fs::write(tmp_dir.path().join("items.json.bak"), "[]").unwrap();
+    let err = JsonStorageItem::<TestType>::new(filepath.to_str().unwrap())
+      .err()
+      .unwrap()
+      .to_string();
+    assert!(err.contains("Previous version is kept in"), "{}", err);
+  }
+
+  #[test]
+  fn storage_keeps_concurrent_writes() {
+    use crate::{storage::Storage, task::Task};
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let folder = tmp_dir.path().to_str().unwrap();
+    let mut first = super::JsonStorage::new(folder).unwrap();
+    let mut second = super::JsonStorage::new(folder).unwrap();
+
+    let project_id = uuid::Uuid::new_v4();
+    first
+      .add_task(&Task::new(
+        project_id,
+        "first",
+        vec![],
+        None,
+        None,
+        &SystemClock,
+      ))
+      .unwrap();
+    second
+      .add_task(&Task::new(
+        project_id,
+        "second",
+        vec![],
+        None,
+        None,
+        &SystemClock,
+      ))
+      .unwrap();
+
+    assert_eq!(super::JsonStorage::new(folder).unwrap().tasks().len(), 2);
+  }
+
+  #[test]
+  fn storage_trash() {
+    use crate::{storage::Storage, task::Task};
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut storage = super::JsonStorage::new(tmp_dir.path().to_str().unwrap()).unwrap();
+    let task = Task::new(
+      uuid::Uuid::new_v4(),
+      "title",
+      vec![],
+      None,
+      None,
+      &SystemClock,
+    );
+    storage.add_task(&task).unwrap();
+
+    let deleted_at = chrono::Local::now();
+    storage.move_to_trash(task.id(), deleted_at).unwrap();
+    assert!(storage.tasks().is_empty());
+    assert_eq!(storage.trash().tasks[0].id(), task.id());
+
+    storage.replace_tasks(vec![]).unwrap();
+    assert_eq!(storage.trash().tasks.len(), 1);
+
+    storage.restore_from_trash(task.id()).unwrap();
+    assert_eq!(storage.tasks().len(), 1);
+    assert!(storage.restore_from_trash(task.id()).is_err());
+
+    storage.move_to_trash(task.id(), deleted_at).unwrap();
+    assert!(storage.purge_trash(deleted_at).unwrap().is_empty());
+    let purged = storage
+      .purge_trash(deleted_at + chrono::Duration::seconds(1))
+      .unwrap();
+    assert_eq!(purged.tasks.len(), 1);
+    assert!(storage.trash().is_empty());
+  }
 }
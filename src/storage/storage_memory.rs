@@ -0,0 +1,264 @@
+use crate::{
+  project::Project,
+  tag::Tag,
+  task::Task,
+  traits::{Indexable, Trashable},
+};
+
+use super::{
+  storage::{Storage, Trash},
+  StorageError,
+};
+
+/// Storage which keeps everything in memory and loses it on drop.
+/// Handy for tests and for programs embedding busy.
+#[derive(Default)]
+pub struct InMemoryStorage {
+  tasks: Items<Task>,
+  projects: Items<Project>,
+  tags: Items<Tag>,
+}
+
+impl InMemoryStorage {
+  pub fn new() -> Self {
+    Self::default()
+  }
+}
+
+impl Storage for InMemoryStorage {
+  fn tasks(&self) -> Vec<Task> {
+    let mut tasks = self.tasks.alive();
+    tasks.sort_by_key(|a| a.start_time());
+    return tasks;
+  }
+
+  fn add_task(&mut self, task: &Task) -> Result<(), StorageError> {
+    self.tasks.add(task)
+  }
+
+  fn remove_task(&mut self, task_id: uuid::Uuid) -> Result<(), StorageError> {
+    self.tasks.remove(task_id)
+  }
+
+  fn replace_task(&mut self, task: &Task) -> Result<(), StorageError> {
+    self.tasks.replace(task)
+  }
+
+  fn replace_tasks(&mut self, tasks: Vec<Task>) -> Result<(), StorageError> {
+    self.tasks.replace_alive(tasks);
+    Ok(())
+  }
+
+  fn add_tag(&mut self, tag: &Tag) -> Result<(), StorageError> {
+    self.tags.add(tag)
+  }
+
+  fn replace_tag(&mut self, tag: &Tag) -> Result<(), StorageError> {
+    self.tags.replace(tag)
+  }
+
+  fn remove_tag(&mut self, tag_id: uuid::Uuid) -> Result<(), StorageError> {
+    self.tags.remove(tag_id)
+  }
+
+  fn replace_tags(&mut self, tags: Vec<Tag>) -> Result<(), StorageError> {
+    self.tags.replace_alive(tags);
+    Ok(())
+  }
+
+  fn tags(&self) -> Vec<Tag> {
+    self.tags.alive()
+  }
+
+  fn add_project(&mut self, project: &Project) -> Result<(), StorageError> {
+    self.projects.add(project)
+  }
+
+  fn replace_project(&mut self, project: &Project) -> Result<(), StorageError> {
+    self.projects.replace(project)
+  }
+
+  fn remove_project(&mut self, project_id: uuid::Uuid) -> Result<(), StorageError> {
+    self.projects.remove(project_id)
+  }
+
+  fn projects(&self) -> Vec<Project> {
+    self.projects.alive()
+  }
+
+  fn trash(&self) -> Trash {
+    Trash {
+      tasks: self.tasks.trashed(),
+      projects: self.projects.trashed(),
+      tags: self.tags.trashed(),
+    }
+  }
+
+  fn move_to_trash(
+    &mut self,
+    id: uuid::Uuid,
+    deleted_at: chrono::DateTime<chrono::Local>,
+  ) -> Result<(), StorageError> {
+    let found = self.tasks.set_deleted_at(id, Some(deleted_at))
+      || self.projects.set_deleted_at(id, Some(deleted_at))
+      || self.tags.set_deleted_at(id, Some(deleted_at));
+    match found {
+      true => Ok(()),
+      false => Err(format!("item with id: {} not found", id).into()),
+    }
+  }
+
+  fn restore_from_trash(&mut self, id: uuid::Uuid) -> Result<(), StorageError> {
+    let found = self.tasks.set_deleted_at(id, None)
+      || self.projects.set_deleted_at(id, None)
+      || self.tags.set_deleted_at(id, None);
+    match found {
+      true => Ok(()),
+      false => Err(format!("item with id: {} not found in trash", id).into()),
+    }
+  }
+
+  fn purge_trash(
+    &mut self,
+    deleted_before: chrono::DateTime<chrono::Local>,
+  ) -> Result<Trash, StorageError> {
+    Ok(Trash {
+      tasks: self.tasks.purge(deleted_before),
+      projects: self.projects.purge(deleted_before),
+      tags: self.tags.purge(deleted_before),
+    })
+  }
+}
+
+struct Items<T> {
+  items: Vec<T>,
+}
+
+impl<T> Default for Items<T> {
+  fn default() -> Self {
+    Self { items: Vec::new() }
+  }
+}
+
+impl<T> Items<T>
+where
+  T: Indexable + Trashable + Clone,
+{
+  fn add(&mut self, item: &T) -> Result<(), StorageError> {
+    if self.position_by_id(item.id()).is_some() {
+      return Err(format!("item with id: {} already exists", item.id()).into());
+    }
+    self.items.push(item.clone());
+    Ok(())
+  }
+
+  fn remove(&mut self, id: uuid::Uuid) -> Result<(), StorageError> {
+    match self.position_by_id(id) {
+      Some(position) => {
+        self.items.remove(position);
+        Ok(())
+      }
+      None => Err(format!("item with id: {} not found", id).into()),
+    }
+  }
+
+  fn replace(&mut self, item: &T) -> Result<(), StorageError> {
+    match self.position_by_id(item.id()) {
+      Some(position) => {
+        self.items[position] = item.clone();
+        Ok(())
+      }
+      None => Err(format!("item with id: {} not found", item.id()).into()),
+    }
+  }
+
+  fn position_by_id(&self, id: uuid::Uuid) -> Option<usize> {
+    self.items.iter().position(|item| item.id() == id)
+  }
+
+  fn alive(&self) -> Vec<T> {
+    self
+      .items
+      .iter()
+      .filter(|item| !item.is_deleted())
+      .cloned()
+      .collect()
+  }
+
+  fn trashed(&self) -> Vec<T> {
+    self
+      .items
+      .iter()
+      .filter(|item| item.is_deleted())
+      .cloned()
+      .collect()
+  }
+
+  fn set_deleted_at(
+    &mut self,
+    id: uuid::Uuid,
+    deleted_at: Option<chrono::DateTime<chrono::Local>>,
+  ) -> bool {
+    let found = self
+      .items
+      .iter_mut()
+      .find(|item| item.id() == id && item.is_deleted() != deleted_at.is_some());
+    match found {
+      Some(item) => {
+        item.set_deleted_at(deleted_at);
+        true
+      }
+      None => false,
+    }
+  }
+
+  fn purge(&mut self, deleted_before: chrono::DateTime<chrono::Local>) -> Vec<T> {
+    let (purged, kept) = self
+      .items
+      .drain(..)
+      .partition(|item| item.deleted_at().is_some_and(|at| at < deleted_before));
+    self.items = kept;
+    purged
+  }
+
+  fn replace_alive(&mut self, items: Vec<T>) {
+    self
+      .items
+      .retain(|item| item.is_deleted() && !items.iter().any(|new| new.id() == item.id()));
+    self.items.extend(items);
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::InMemoryStorage;
+  use crate::clock::SystemClock;
+  use crate::{project::Project, storage::Storage, task::Task, traits::Indexable};
+
+  #[test]
+  fn in_memory_trash() {
+    let mut storage = InMemoryStorage::new();
+    let project = Project::new("project");
+    let task = Task::new(project.id(), "task", vec![], None, None, &SystemClock);
+    storage.add_project(&project).unwrap();
+    storage.add_task(&task).unwrap();
+    assert!(storage.add_task(&task).is_err());
+
+    let deleted_at = chrono::Local::now();
+    storage.move_to_trash(task.id(), deleted_at).unwrap();
+    assert!(storage.tasks().is_empty());
+    storage.replace_tasks(vec![]).unwrap();
+    assert_eq!(storage.trash().tasks.len(), 1);
+
+    storage.restore_from_trash(task.id()).unwrap();
+    assert_eq!(storage.tasks().len(), 1);
+
+    storage.move_to_trash(task.id(), deleted_at).unwrap();
+    assert!(storage.purge_trash(deleted_at).unwrap().is_empty());
+    let purged = storage
+      .purge_trash(deleted_at + chrono::Duration::seconds(1))
+      .unwrap();
+    assert_eq!(purged.tasks.len(), 1);
+    assert!(storage.trash().is_empty());
+  }
+}
@@ -0,0 +1,795 @@
+use std::collections::HashMap;
+
+use log::debug;
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::{
+  project::Project,
+  tag::Tag,
+  task::Task,
+  time::DateTimeInterval,
+  traits::{Indexable, Trashable},
+};
+
+use super::{
+  lock::LOCK_TIMEOUT,
+  schema,
+  storage::{Storage, Trash},
+  StorageError,
+};
+
+// `PRAGMA user_version` keeps the number of applied migrations.
+// Append new migrations to the end.
+const MIGRATIONS: &[&str] = &[
+  "
+    CREATE TABLE IF NOT EXISTS projects (
+      id TEXT PRIMARY KEY NOT NULL,
+      name TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS tags (
+      id TEXT PRIMARY KEY NOT NULL,
+      name TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS tasks (
+      id TEXT PRIMARY KEY NOT NULL,
+      project_id TEXT NOT NULL,
+      title TEXT NOT NULL,
+      is_paused INTEGER NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS task_intervals (
+      task_id TEXT NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+      position INTEGER NOT NULL,
+      start_time TEXT NOT NULL,
+      stop_time TEXT,
+      PRIMARY KEY (task_id, position)
+    );
+
+    CREATE TABLE IF NOT EXISTS task_tags (
+      task_id TEXT NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+      tag_id TEXT NOT NULL,
+      position INTEGER NOT NULL,
+      PRIMARY KEY (task_id, position)
+    );
+  ",
+  "
+    ALTER TABLE projects ADD COLUMN deleted_at TEXT;
+    ALTER TABLE tags ADD COLUMN deleted_at TEXT;
+    ALTER TABLE tasks ADD COLUMN deleted_at TEXT;
+  ",
+  "
+    ALTER TABLE projects ADD COLUMN description TEXT;
+    ALTER TABLE projects ADD COLUMN client TEXT;
+    ALTER TABLE projects ADD COLUMN color TEXT;
+    ALTER TABLE projects ADD COLUMN archived INTEGER NOT NULL DEFAULT 0;
+  ",
+  "
+    ALTER TABLE tags ADD COLUMN color TEXT;
+  ",
+];
+
+pub struct SqliteStorage {
+  filepath: String,
+  connection: Connection,
+  // rows are read on open and after every change, so a broken row is
+  // reported by the call which read it instead of panicking in a getter
+  tasks: Vec<Task>,
+  projects: Vec<Project>,
+  tags: Vec<Tag>,
+  trash: Trash,
+}
+
+impl SqliteStorage {
+  pub fn new(database_folder: &str) -> Result<Self, StorageError> {
+    let path = std::path::Path::new(database_folder).join("busy.sqlite");
+    let filepath = path
+      .to_str()
+      .ok_or_else(|| format!("sqlite db path isn't valid UTF-8: {}", path.display()))?
+      .to_owned();
+
+    let connection = Connection::open(&filepath).map_err(storage_error)?;
+    connection
+      .busy_timeout(LOCK_TIMEOUT)
+      .map_err(storage_error)?;
+    connection
+      .execute_batch("PRAGMA foreign_keys = ON;")
+      .map_err(storage_error)?;
+
+    let mut storage = Self {
+      filepath,
+      connection,
+      tasks: Vec::new(),
+      projects: Vec::new(),
+      tags: Vec::new(),
+      trash: Trash::default(),
+    };
+    storage
+      .migrate()
+      .and_then(|_| storage.reload())
+      .map_err(|err| {
+        err.map(|err| format!("can't open sqlite db: {}, err: {}", storage.filepath, err))
+      })?;
+
+    debug!("opened sqlite storage: {}", storage.filepath);
+
+    Ok(storage)
+  }
+
+  fn migrate(&mut self) -> Result<(), StorageError> {
+    let version: u64 = self
+      .connection
+      .query_row("PRAGMA user_version", [], |row| row.get(0))
+      .map_err(storage_error)?;
+    schema::check_version(version, MIGRATIONS.len() as u64)?;
+
+    let transaction = self.connection.transaction().map_err(storage_error)?;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+      debug!("apply sqlite migration to version {}", index + 1);
+      transaction
+        .execute_batch(migration)
+        .map_err(storage_error)?;
+      transaction
+        .execute_batch(&format!("PRAGMA user_version = {}", index + 1))
+        .map_err(storage_error)?;
+    }
+    transaction.commit().map_err(storage_error)
+  }
+
+  fn reload(&mut self) -> Result<(), StorageError> {
+    let read_error = |what: &str| {
+      let what = what.to_owned();
+      move |err: rusqlite::Error| format!("can't read {} from db: {}", what, err)
+    };
+    let mut tasks = self.read_tasks(false).map_err(read_error("tasks"))?;
+    tasks.sort_by_key(|a| a.start_time());
+    let projects = self.read_projects(false).map_err(read_error("projects"))?;
+    let tags = self.read_tags(false).map_err(read_error("tags"))?;
+    let trash = Trash {
+      tasks: self.read_tasks(true).map_err(read_error("trashed tasks"))?,
+      projects: self
+        .read_projects(true)
+        .map_err(read_error("trashed projects"))?,
+      tags: self.read_tags(true).map_err(read_error("trashed tags"))?,
+    };
+    self.tasks = tasks;
+    self.projects = projects;
+    self.tags = tags;
+    self.trash = trash;
+    Ok(())
+  }
+
+  // Runs a change and reads the rows again.
+  fn write<F>(&mut self, mutate: F) -> Result<(), StorageError>
+  where
+    F: FnOnce(&mut Connection) -> Result<(), StorageError>,
+  {
+    mutate(&mut self.connection)?;
+    self.reload()
+  }
+
+  pub fn storage_path(&self) -> &str {
+    self.filepath.as_str()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    let has_rows = |table: &str| {
+      self
+        .connection
+        .query_row(&format!("SELECT 1 FROM {table} LIMIT 1"), [], |_| Ok(()))
+        .optional()
+        .map(|row| row.is_some())
+        .unwrap_or_default()
+    };
+    return !has_rows("tasks") && !has_rows("projects") && !has_rows("tags");
+  }
+
+  fn read_tasks(&self, trashed: bool) -> rusqlite::Result<Vec<Task>> {
+    let mut intervals: HashMap<String, Vec<DateTimeInterval>> = HashMap::new();
+    let mut statement = self.connection.prepare(
+      "SELECT task_id, start_time, stop_time FROM task_intervals ORDER BY task_id, position",
+    )?;
+    let rows = statement.query_map([], |row| {
+      Ok((
+        row.get::<_, String>(0)?,
+        row.get::<_, String>(1)?,
+        row.get::<_, Option<String>>(2)?,
+      ))
+    })?;
+    for row in rows {
+      let (task_id, start_time, stop_time) = row?;
+      intervals
+        .entry(task_id)
+        .or_default()
+        .push(DateTimeInterval {
+          start_time: parse_time(&start_time)?,
+          stop_time: stop_time.as_deref().map(parse_time).transpose()?,
+        });
+    }
+
+    let mut tags: HashMap<String, Vec<uuid::Uuid>> = HashMap::new();
+    let mut statement = self
+      .connection
+      .prepare("SELECT task_id, tag_id FROM task_tags ORDER BY task_id, position")?;
+    let rows = statement.query_map([], |row| {
+      Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    })?;
+    for row in rows {
+      let (task_id, tag_id) = row?;
+      tags.entry(task_id).or_default().push(parse_id(&tag_id)?);
+    }
+
+    let mut statement = self.connection.prepare(&format!(
+      "SELECT id, project_id, title, is_paused, deleted_at FROM tasks WHERE {}",
+      deleted_filter(trashed)
+    ))?;
+    let rows = statement.query_map([], |row| {
+      Ok((
+        row.get::<_, String>(0)?,
+        row.get::<_, String>(1)?,
+        row.get::<_, String>(2)?,
+        row.get::<_, bool>(3)?,
+        row.get::<_, Option<String>>(4)?,
+      ))
+    })?;
+
+    let mut tasks = Vec::new();
+    for row in rows {
+      let (id, project_id, title, is_paused, deleted_at) = row?;
+      let mut task = Task::from_parts(
+        parse_id(&id)?,
+        parse_id(&project_id)?,
+        intervals.remove(&id).unwrap_or_default(),
+        &title,
+        tags.remove(&id).unwrap_or_default(),
+        is_paused,
+      );
+      task.set_deleted_at(deleted_at.as_deref().map(parse_time).transpose()?);
+      tasks.push(task);
+    }
+    return Ok(tasks);
+  }
+
+  fn read_tags(&self, trashed: bool) -> rusqlite::Result<Vec<Tag>> {
+    let mut statement = self.connection.prepare(&format!(
+      "SELECT id, name, color, deleted_at FROM tags WHERE {} ORDER BY rowid",
+      deleted_filter(trashed)
+    ))?;
+    let rows = statement.query_map([], |row| {
+      Ok((
+        row.get::<_, String>(0)?,
+        row.get::<_, String>(1)?,
+        row.get::<_, Option<String>>(2)?,
+        row.get::<_, Option<String>>(3)?,
+      ))
+    })?;
+
+    let mut tags = Vec::new();
+    for row in rows {
+      let (id, name, color, deleted_at) = row?;
+      let mut tag = Tag::from_parts(parse_id(&id)?, &name);
+      // colors which aren't known anymore are dropped
+      tag.set_color(color).unwrap_or_default();
+      tag.set_deleted_at(deleted_at.as_deref().map(parse_time).transpose()?);
+      tags.push(tag);
+    }
+    return Ok(tags);
+  }
+
+  fn read_projects(&self, trashed: bool) -> rusqlite::Result<Vec<Project>> {
+    let mut statement = self.connection.prepare(&format!(
+      "SELECT id, name, description, client, color, archived, deleted_at FROM projects WHERE {} ORDER BY rowid",
+      deleted_filter(trashed)
+    ))?;
+    let rows = statement.query_map([], |row| {
+      Ok((
+        row.get::<_, String>(0)?,
+        row.get::<_, String>(1)?,
+        row.get::<_, Option<String>>(2)?,
+        row.get::<_, Option<String>>(3)?,
+        row.get::<_, Option<String>>(4)?,
+        row.get::<_, bool>(5)?,
+        row.get::<_, Option<String>>(6)?,
+      ))
+    })?;
+
+    let mut projects = Vec::new();
+    for row in rows {
+      let (id, name, description, client, color, archived, deleted_at) = row?;
+      let mut project = Project::from_parts(parse_id(&id)?, &name);
+      project.set_description(description);
+      project.set_client(client);
+      // colors which aren't known anymore are dropped
+      project.set_color(color).unwrap_or_default();
+      project.set_archived(archived);
+      project.set_deleted_at(deleted_at.as_deref().map(parse_time).transpose()?);
+      projects.push(project);
+    }
+    return Ok(projects);
+  }
+}
+
+impl Storage for SqliteStorage {
+  fn tasks(&self) -> Vec<Task> {
+    self.tasks.clone()
+  }
+
+  fn add_task(&mut self, task: &Task) -> Result<(), StorageError> {
+    self.write(|connection| {
+      let transaction = connection.transaction().map_err(storage_error)?;
+      insert_task(&transaction, task).map_err(storage_error)?;
+      transaction.commit().map_err(storage_error)
+    })
+  }
+
+  fn remove_task(&mut self, task_id: uuid::Uuid) -> Result<(), StorageError> {
+    self.write(|connection| {
+      let removed = connection
+        .execute("DELETE FROM tasks WHERE id = ?1", [task_id.to_string()])
+        .map_err(storage_error)?;
+      if removed == 0 {
+        return Err(format!("task with id: {} not found", task_id).into());
+      }
+      Ok(())
+    })
+  }
+
+  fn replace_task(&mut self, task: &Task) -> Result<(), StorageError> {
+    self.write(|connection| {
+      let transaction = connection.transaction().map_err(storage_error)?;
+      let removed = transaction
+        .execute("DELETE FROM tasks WHERE id = ?1", [task.id().to_string()])
+        .map_err(storage_error)?;
+      if removed == 0 {
+        return Err(format!("task with id: {} not found", task.id()).into());
+      }
+      insert_task(&transaction, task).map_err(storage_error)?;
+      transaction.commit().map_err(storage_error)
+    })
+  }
+
+  fn replace_tasks(&mut self, tasks: Vec<Task>) -> Result<(), StorageError> {
+    self.write(|connection| {
+      let transaction = connection.transaction().map_err(storage_error)?;
+      transaction
+        .execute("DELETE FROM tasks WHERE deleted_at IS NULL", [])
+        .map_err(storage_error)?;
+      for task in tasks.iter() {
+        transaction
+          .execute("DELETE FROM tasks WHERE id = ?1", [task.id().to_string()])
+          .map_err(storage_error)?;
+        insert_task(&transaction, task).map_err(storage_error)?;
+      }
+      transaction.commit().map_err(storage_error)
+    })
+  }
+
+  fn add_tag(&mut self, tag: &Tag) -> Result<(), StorageError> {
+    self.write(|connection| insert_tag(connection, tag).map_err(storage_error))
+  }
+
+  fn replace_tag(&mut self, tag: &Tag) -> Result<(), StorageError> {
+    self.write(|connection| {
+      let updated = connection
+        .execute(
+          "UPDATE tags SET name = ?2, color = ?3, deleted_at = ?4 WHERE id = ?1",
+          params![
+            tag.id().to_string(),
+            tag.name(),
+            tag.color(),
+            tag.deleted_at().map(|at| at.to_rfc3339())
+          ],
+        )
+        .map_err(storage_error)?;
+      if updated == 0 {
+        return Err(format!("tag with id: {} not found", tag.id()).into());
+      }
+      Ok(())
+    })
+  }
+
+  fn remove_tag(&mut self, tag_id: uuid::Uuid) -> Result<(), StorageError> {
+    self.write(|connection| {
+      let removed = connection
+        .execute("DELETE FROM tags WHERE id = ?1", [tag_id.to_string()])
+        .map_err(storage_error)?;
+      if removed == 0 {
+        return Err(format!("tag with id: {} not found", tag_id).into());
+      }
+      Ok(())
+    })
+  }
+
+  fn replace_tags(&mut self, tags: Vec<Tag>) -> Result<(), StorageError> {
+    self.write(|connection| {
+      let transaction = connection.transaction().map_err(storage_error)?;
+      transaction
+        .execute("DELETE FROM tags WHERE deleted_at IS NULL", [])
+        .map_err(storage_error)?;
+      for tag in tags.iter() {
+        transaction
+          .execute("DELETE FROM tags WHERE id = ?1", [tag.id().to_string()])
+          .map_err(storage_error)?;
+        insert_tag(&transaction, tag).map_err(storage_error)?;
+      }
+      transaction.commit().map_err(storage_error)
+    })
+  }
+
+  fn tags(&self) -> Vec<Tag> {
+    self.tags.clone()
+  }
+
+  fn add_project(&mut self, project: &Project) -> Result<(), StorageError> {
+    self.write(|connection| insert_project(connection, project).map_err(storage_error))
+  }
+
+  fn replace_project(&mut self, project: &Project) -> Result<(), StorageError> {
+    self.write(|connection| {
+      let updated = connection
+        .execute(
+          "UPDATE projects SET name = ?2, description = ?3, client = ?4, color = ?5, archived = ?6, deleted_at = ?7 WHERE id = ?1",
+          params![
+            project.id().to_string(),
+            project.name(),
+            project.description(),
+            project.client(),
+            project.color(),
+            project.is_archived(),
+            project.deleted_at().map(|at| at.to_rfc3339())
+          ],
+        )
+        .map_err(storage_error)?;
+      if updated == 0 {
+        return Err(format!("project with id: {} not found", project.id()).into());
+      }
+      Ok(())
+    })
+  }
+
+  fn remove_project(&mut self, project_id: uuid::Uuid) -> Result<(), StorageError> {
+    self.write(|connection| {
+      let removed = connection
+        .execute(
+          "DELETE FROM projects WHERE id = ?1",
+          [project_id.to_string()],
+        )
+        .map_err(storage_error)?;
+      if removed == 0 {
+        return Err(format!("project with id: {} not found", project_id).into());
+      }
+      Ok(())
+    })
+  }
+
+  fn projects(&self) -> Vec<Project> {
+    self.projects.clone()
+  }
+
+  fn trash(&self) -> Trash {
+    self.trash.clone()
+  }
+
+  fn move_to_trash(
+    &mut self,
+    id: uuid::Uuid,
+    deleted_at: chrono::DateTime<chrono::Local>,
+  ) -> Result<(), StorageError> {
+    self.write(|connection| {
+      if update_deleted_at(connection, id, Some(deleted_at)).map_err(storage_error)? == 0 {
+        return Err(format!("item with id: {} not found", id).into());
+      }
+      Ok(())
+    })
+  }
+
+  fn restore_from_trash(&mut self, id: uuid::Uuid) -> Result<(), StorageError> {
+    self.write(|connection| {
+      if update_deleted_at(connection, id, None).map_err(storage_error)? == 0 {
+        return Err(format!("item with id: {} not found in trash", id).into());
+      }
+      Ok(())
+    })
+  }
+
+  fn purge_trash(
+    &mut self,
+    deleted_before: chrono::DateTime<chrono::Local>,
+  ) -> Result<Trash, StorageError> {
+    let is_expired =
+      |at: Option<chrono::DateTime<chrono::Local>>| at.is_some_and(|at| at < deleted_before);
+    let mut purged = self.trash();
+    purged.tasks.retain(|task| is_expired(task.deleted_at()));
+    purged
+      .projects
+      .retain(|project| is_expired(project.deleted_at()));
+    purged.tags.retain(|tag| is_expired(tag.deleted_at()));
+
+    self.write(|connection| {
+      let transaction = connection.transaction().map_err(storage_error)?;
+      let delete = |table: &str, id: uuid::Uuid| {
+        transaction
+          .execute(
+            &format!("DELETE FROM {table} WHERE id = ?1"),
+            [id.to_string()],
+          )
+          .map_err(storage_error)
+      };
+      for task in purged.tasks.iter() {
+        delete("tasks", task.id())?;
+      }
+      for project in purged.projects.iter() {
+        delete("projects", project.id())?;
+      }
+      for tag in purged.tags.iter() {
+        delete("tags", tag.id())?;
+      }
+      transaction.commit().map_err(storage_error)
+    })?;
+    Ok(purged)
+  }
+}
+
+fn deleted_filter(trashed: bool) -> &'static str {
+  match trashed {
+    true => "deleted_at IS NOT NULL",
+    false => "deleted_at IS NULL",
+  }
+}
+
+// Returns the number of updated rows over all tables.
+fn update_deleted_at(
+  connection: &mut Connection,
+  id: uuid::Uuid,
+  deleted_at: Option<chrono::DateTime<chrono::Local>>,
+) -> rusqlite::Result<usize> {
+  let transaction = connection.transaction()?;
+  let mut updated = 0;
+  for table in ["tasks", "projects", "tags"] {
+    updated += transaction.execute(
+      &format!(
+        "UPDATE {table} SET deleted_at = ?2 WHERE id = ?1 AND {}",
+        deleted_filter(deleted_at.is_none())
+      ),
+      params![id.to_string(), deleted_at.map(|at| at.to_rfc3339())],
+    )?;
+  }
+  transaction.commit()?;
+  Ok(updated)
+}
+
+fn insert_tag(connection: &Connection, tag: &Tag) -> rusqlite::Result<()> {
+  connection.execute(
+    "INSERT INTO tags (id, name, color, deleted_at) VALUES (?1, ?2, ?3, ?4)",
+    params![
+      tag.id().to_string(),
+      tag.name(),
+      tag.color(),
+      tag.deleted_at().map(|at| at.to_rfc3339())
+    ],
+  )?;
+  Ok(())
+}
+
+fn insert_project(connection: &Connection, project: &Project) -> rusqlite::Result<()> {
+  connection.execute(
+    "INSERT INTO projects (id, name, description, client, color, archived, deleted_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+    params![
+      project.id().to_string(),
+      project.name(),
+      project.description(),
+      project.client(),
+      project.color(),
+      project.is_archived(),
+      project.deleted_at().map(|at| at.to_rfc3339())
+    ],
+  )?;
+  Ok(())
+}
+
+fn insert_task(connection: &Connection, task: &Task) -> rusqlite::Result<()> {
+  let task_id = task.id().to_string();
+  connection.execute(
+    "INSERT INTO tasks (id, project_id, title, is_paused, deleted_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+    params![
+      task_id,
+      task.project_id().to_string(),
+      task.title(),
+      task.is_paused(),
+      task.deleted_at().map(|at| at.to_rfc3339())
+    ],
+  )?;
+
+  for (position, interval) in task.times().iter().enumerate() {
+    connection.execute(
+      "INSERT INTO task_intervals (task_id, position, start_time, stop_time) VALUES (?1, ?2, ?3, ?4)",
+      params![
+        task_id,
+        position,
+        interval.start_time.to_rfc3339(),
+        interval.stop_time.map(|stop_time| stop_time.to_rfc3339())
+      ],
+    )?;
+  }
+
+  for (position, tag_id) in task.tags().iter().enumerate() {
+    connection.execute(
+      "INSERT INTO task_tags (task_id, tag_id, position) VALUES (?1, ?2, ?3)",
+      params![task_id, tag_id.to_string(), position],
+    )?;
+  }
+  Ok(())
+}
+
+fn parse_time(value: &str) -> rusqlite::Result<chrono::DateTime<chrono::Local>> {
+  chrono::DateTime::parse_from_rfc3339(value)
+    .map(|time| time.with_timezone(&chrono::Local))
+    .map_err(|err| {
+      rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, err.into())
+    })
+}
+
+fn parse_id(value: &str) -> rusqlite::Result<uuid::Uuid> {
+  uuid::Uuid::parse_str(value).map_err(|err| {
+    rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, err.into())
+  })
+}
+
+fn storage_error(err: rusqlite::Error) -> StorageError {
+  if err.sqlite_error_code() == Some(rusqlite::ErrorCode::DatabaseBusy) {
+    return StorageError::Busy(format!(
+      "database busy: locked by another busy process, try again later ({})",
+      err
+    ));
+  }
+  StorageError::Failed(err.to_string())
+}
+
+#[cfg(test)]
+mod test {
+  use super::SqliteStorage;
+  use crate::clock::SystemClock;
+  use crate::{project::Project, storage::Storage, tag::Tag, task::Task, traits::Indexable};
+
+  fn get_new_storage() -> (tempfile::TempDir, SqliteStorage) {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let storage = SqliteStorage::new(tmp_dir.path().to_str().unwrap()).unwrap();
+    (tmp_dir, storage)
+  }
+
+  #[test]
+  fn sqlite_task_roundtrip() {
+    let (_tmp_dir, mut storage) = get_new_storage();
+    let project = Project::new("project");
+    let tag = Tag::new("tag");
+    storage.add_project(&project).unwrap();
+    storage.add_tag(&tag).unwrap();
+
+    let mut task = Task::new(
+      project.id(),
+      "title",
+      vec![tag.id()],
+      None,
+      None,
+      &SystemClock,
+    );
+    storage.add_task(&task).unwrap();
+    task.pause(chrono::Local::now()).unwrap();
+    task.resume(chrono::Local::now()).unwrap();
+    storage.replace_task(&task).unwrap();
+
+    let tasks = storage.tasks();
+    assert_eq!(tasks.len(), 1);
+    assert_eq!(tasks[0].id(), task.id());
+    assert_eq!(tasks[0].project_id(), project.id());
+    assert_eq!(tasks[0].tags(), &vec![tag.id()]);
+    assert_eq!(tasks[0].times().len(), 2);
+    assert!(tasks[0].stop_time().is_none());
+    assert_eq!(storage.find_tag_by_name("tag").unwrap().id(), tag.id());
+  }
+
+  #[test]
+  fn sqlite_project_and_tag_metadata() {
+    let (_tmp_dir, mut storage) = get_new_storage();
+    let mut project = Project::new("project");
+    project.set_description(Some("description".to_owned()));
+    storage.add_project(&project).unwrap();
+    assert_eq!(storage.projects(), vec![project.clone()]);
+
+    project.set_client(Some("client".to_owned()));
+    project.set_color(Some("bright blue".to_owned())).unwrap();
+    project.set_archived(true);
+    storage.replace_project(&project).unwrap();
+    assert_eq!(storage.projects(), vec![project]);
+
+    let mut tag = Tag::new("tag");
+    tag.set_color(Some("cyan".to_owned())).unwrap();
+    storage.add_tag(&tag).unwrap();
+    assert_eq!(storage.tags(), vec![tag.clone()]);
+    tag.set_color(None).unwrap();
+    storage.replace_tag(&tag).unwrap();
+    assert_eq!(storage.tags(), vec![tag]);
+  }
+
+  #[test]
+  fn sqlite_remove_task() {
+    let (_tmp_dir, mut storage) = get_new_storage();
+    let task = Task::new(
+      uuid::Uuid::new_v4(),
+      "title",
+      vec![],
+      None,
+      None,
+      &SystemClock,
+    );
+    storage.add_task(&task).unwrap();
+    storage.remove_task(task.id()).unwrap();
+
+    assert!(storage.tasks().is_empty());
+    storage
+      .remove_task(task.id())
+      .expect_err("shouldn't remove missing task");
+  }
+
+  #[test]
+  fn sqlite_trash() {
+    let (_tmp_dir, mut storage) = get_new_storage();
+    let project = Project::new("project");
+    storage.add_project(&project).unwrap();
+    let task = Task::new(project.id(), "title", vec![], None, None, &SystemClock);
+    storage.add_task(&task).unwrap();
+
+    let deleted_at = chrono::Local::now();
+    storage.move_to_trash(task.id(), deleted_at).unwrap();
+    storage.move_to_trash(project.id(), deleted_at).unwrap();
+    assert!(storage.tasks().is_empty());
+    assert!(storage.projects().is_empty());
+
+    storage.replace_tasks(vec![]).unwrap();
+    let trash = storage.trash();
+    assert_eq!(trash.tasks[0].id(), task.id());
+    assert_eq!(trash.projects[0].id(), project.id());
+
+    storage.restore_from_trash(project.id()).unwrap();
+    assert_eq!(storage.projects().len(), 1);
+
+    let purged = storage
+      .purge_trash(deleted_at + chrono::Duration::seconds(1))
+      .unwrap();
+    assert_eq!(purged.tasks.len(), 1);
+    assert!(storage.trash().is_empty());
+  }
+
+  #[test]
+  fn sqlite_report_broken_rows() {
+    let (tmp_dir, mut storage) = get_new_storage();
+    storage
+      .connection
+      .execute_batch("INSERT INTO tags (id, name) VALUES ('not-an-id', 'tag')")
+      .unwrap();
+    let err = storage.add_project(&Project::new("project")).unwrap_err();
+    assert!(err.to_string().contains("can't read tags"));
+    drop(storage);
+
+    let err = SqliteStorage::new(tmp_dir.path().to_str().unwrap())
+      .err()
+      .unwrap();
+    assert!(err.to_string().contains("can't read tags"));
+  }
+
+  #[test]
+  fn sqlite_refuse_newer_version() {
+    let (tmp_dir, storage) = get_new_storage();
+    storage
+      .connection
+      .execute_batch("PRAGMA user_version = 1000")
+      .unwrap();
+    drop(storage);
+
+    let err = SqliteStorage::new(tmp_dir.path().to_str().unwrap())
+      .err()
+      .unwrap();
+    assert!(err.to_string().contains("newer busy"));
+  }
+}
@@ -0,0 +1,62 @@
+/// The candidate closest to a mistyped `name`, if it's close enough to be
+/// a typo: at most one edit per three characters, case is ignored.
+pub fn closest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+  let name = name.to_lowercase();
+  let max_distance = std::cmp::max(1, name.chars().count() / 3);
+  candidates
+    .into_iter()
+    .map(|candidate| (edit_distance(&name, &candidate.to_lowercase()), candidate))
+    .filter(|(distance, _)| *distance <= max_distance)
+    .min_by_key(|(distance, _)| *distance)
+    .map(|(_, candidate)| candidate)
+}
+
+/// Insertions, deletions, substitutions and swaps of adjacent characters
+/// needed to turn `a` into `b`.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+  let a: Vec<char> = a.chars().collect();
+  let b: Vec<char> = b.chars().collect();
+  // rows for the prefixes of `a` of length i - 2, i - 1 and i
+  let mut before_previous = vec![0; b.len() + 1];
+  let mut previous: Vec<usize> = (0..=b.len()).collect();
+  let mut current = vec![0; b.len() + 1];
+  for i in 1..=a.len() {
+    current[0] = i;
+    for j in 1..=b.len() {
+      let substitution = previous[j - 1] + usize::from(a[i - 1] != b[j - 1]);
+      current[j] = substitution.min(previous[j] + 1).min(current[j - 1] + 1);
+      if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+        current[j] = current[j].min(before_previous[j - 2] + 1);
+      }
+    }
+    std::mem::swap(&mut before_previous, &mut previous);
+    std::mem::swap(&mut previous, &mut current);
+  }
+  previous[b.len()]
+}
+
+#[cfg(test)]
+mod test {
+  use super::{closest, edit_distance};
+
+  #[test]
+  fn distances() {
+    assert_eq!(edit_distance("", ""), 0);
+    assert_eq!(edit_distance("review", ""), 6);
+    assert_eq!(edit_distance("review", "review"), 0);
+    assert_eq!(edit_distance("review", "reveiw"), 1);
+    assert_eq!(edit_distance("acme-backend", "acme_backend"), 1);
+    assert_eq!(edit_distance("acme-backnd", "acme-backend"), 1);
+    assert_eq!(edit_distance("kitten", "sitting"), 3);
+  }
+
+  #[test]
+  fn closest_names() {
+    let names = ["acme-backend", "acme-frontend", "review", "meeting"];
+    assert_eq!(closest("acme_backend", names), Some("acme-backend"));
+    assert_eq!(closest("Acme-Frontnd", names), Some("acme-frontend"));
+    assert_eq!(closest("reveiw", names), Some("review"));
+    assert_eq!(closest("research", names), None);
+    assert_eq!(closest("x", names), None);
+  }
+}
@@ -5,5 +5,22 @@ pub enum SyncerConfig {
     key_file: Option<String>,
     remote: String,
     remote_branch: Option<String>,
+    #[serde(default)]
+    merge_driver: MergeDriver,
   },
+  Directory {
+    path: String,
+  },
+}
+
+/// Binary git runs to merge busy records, see `busy merge-driver`.
+#[derive(Debug, Default, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum MergeDriver {
+  /// The running executable, which is busy itself for the CLI.
+  #[default]
+  CurrentExe,
+  /// Path to the busy binary, e.g. when busy is used as a library.
+  Binary(String),
+  /// The driver isn't registered, git merges the files as text.
+  Disabled,
 }
@@ -0,0 +1,325 @@
+use std::collections::{HashMap, HashSet};
+
+use serde_json::{Map, Value};
+
+use crate::{
+  project::Project,
+  storage::{decode, encode},
+  tag::Tag,
+  task::Task,
+  traits::Indexable,
+};
+
+/// Storage files which can be merged record by record.
+pub const MERGED_FILES: &[&str] = &["tasks.json", "projects.json", "tags.json"];
+
+/// Prefix of the lines `busy merge-driver` prints for every conflict,
+/// so they can be picked out of the git output.
+pub const CONFLICT_REPORT_PREFIX: &str = "busy merge:";
+
+/// Record which was changed differently on both sides. It's still merged,
+/// `reason` tells which version was kept.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+  pub id: uuid::Uuid,
+  pub reason: String,
+}
+
+impl std::fmt::Display for Conflict {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "record {}: {}", self.id, self.reason)
+  }
+}
+
+pub struct Merged<T> {
+  pub items: Vec<T>,
+  pub conflicts: Vec<Conflict>,
+}
+
+/// Three-way merge of records keyed by their ids.
+///
+/// A record changed on one side only takes that change. When both sides
+/// changed a record, its fields are merged one by one; a field changed
+/// differently on both sides gets the greatest of the two values, so the
+/// result doesn't depend on which side is "ours". A record removed on one
+/// side and changed on the other one is kept.
+pub fn merge<T>(base: &[T], ours: &[T], theirs: &[T]) -> Result<Merged<T>, String>
+where
+  T: Indexable + serde::ser::Serialize + serde::de::DeserializeOwned,
+{
+  let to_values = |items: &[T]| -> Result<Vec<(uuid::Uuid, Value)>, String> {
+    items
+      .iter()
+      .map(|item| {
+        Ok((
+          item.id(),
+          serde_json::to_value(item).map_err(|err| err.to_string())?,
+        ))
+      })
+      .collect()
+  };
+  let base = to_values(base)?;
+  let ours = to_values(ours)?;
+  let theirs = to_values(theirs)?;
+
+  // the first record wins if an id is repeated
+  fn index(items: &[(uuid::Uuid, Value)]) -> HashMap<uuid::Uuid, &Value> {
+    let mut index = HashMap::with_capacity(items.len());
+    for (id, value) in items {
+      index.entry(*id).or_insert(value);
+    }
+    index
+  }
+  let (base_index, ours_index, theirs_index) = (index(&base), index(&ours), index(&theirs));
+
+  let mut seen = HashSet::new();
+  let ids: Vec<uuid::Uuid> = ours
+    .iter()
+    .chain(theirs.iter())
+    .map(|(id, _)| *id)
+    .filter(|id| seen.insert(*id))
+    .collect();
+  let find = |index: &HashMap<uuid::Uuid, &Value>, id: uuid::Uuid| {
+    index.get(&id).map(|value| (*value).clone())
+  };
+
+  let mut merged = Merged {
+    items: Vec::new(),
+    conflicts: Vec::new(),
+  };
+  for id in ids {
+    let (value, conflict) = merge_record(
+      find(&base_index, id),
+      find(&ours_index, id),
+      find(&theirs_index, id),
+    );
+    if let Some(reason) = conflict {
+      merged.conflicts.push(Conflict { id, reason });
+    }
+    if let Some(value) = value {
+      merged
+        .items
+        .push(serde_json::from_value(value).map_err(|err| err.to_string())?);
+    }
+  }
+  return Ok(merged);
+}
+
+/// Merges versions of a storage file, the kind of records is taken from
+/// the file name. Returns the merged content and the conflicts found.
+pub fn merge_file(
+  path: &str,
+  base: &str,
+  ours: &str,
+  theirs: &str,
+) -> Result<(String, Vec<Conflict>), String> {
+  let filename = std::path::Path::new(path)
+    .file_name()
+    .and_then(|filename| filename.to_str())
+    .unwrap_or_default();
+  match filename {
+    "tasks.json" => merge_content::<Task>(base, ours, theirs),
+    "projects.json" => merge_content::<Project>(base, ours, theirs),
+    "tags.json" => merge_content::<Tag>(base, ours, theirs),
+    _ => Err(format!("busy doesn't know how to merge: {}", path)),
+  }
+}
+
+fn merge_content<T>(base: &str, ours: &str, theirs: &str) -> Result<(String, Vec<Conflict>), String>
+where
+  T: Indexable + serde::ser::Serialize + serde::de::DeserializeOwned,
+{
+  let merged = merge::<T>(&decode(base)?, &decode(ours)?, &decode(theirs)?)?;
+  Ok((encode(&merged.items)?, merged.conflicts))
+}
+
+fn merge_record(
+  base: Option<Value>,
+  ours: Option<Value>,
+  theirs: Option<Value>,
+) -> (Option<Value>, Option<String>) {
+  if ours == theirs || theirs == base {
+    return (ours, None);
+  }
+  if ours == base {
+    return (theirs, None);
+  }
+
+  match (ours, theirs) {
+    (Some(ours), Some(theirs)) => {
+      let empty = Map::new();
+      let base_fields = match base.as_ref() {
+        Some(Value::Object(fields)) => fields,
+        _ => &empty,
+      };
+      match (ours, theirs) {
+        (Value::Object(ours), Value::Object(theirs)) => {
+          let (fields, conflicting) = merge_fields(base_fields, &ours, &theirs);
+          let conflict = match conflicting.is_empty() {
+            true => None,
+            false => Some(format!(
+              "both sides changed: {}, kept the greatest values",
+              conflicting.join(", ")
+            )),
+          };
+          (Some(Value::Object(fields)), conflict)
+        }
+        (ours, theirs) => (
+          Some(greatest(ours, theirs)),
+          Some("both sides changed it, kept the greatest value".to_string()),
+        ),
+      }
+    }
+    (Some(changed), None) | (None, Some(changed)) => (
+      Some(changed),
+      Some("removed on one side and changed on the other, kept the changed one".to_string()),
+    ),
+    (None, None) => (None, None),
+  }
+}
+
+fn merge_fields(
+  base: &Map<String, Value>,
+  ours: &Map<String, Value>,
+  theirs: &Map<String, Value>,
+) -> (Map<String, Value>, Vec<String>) {
+  let mut keys: Vec<&String> = ours.keys().chain(theirs.keys()).collect();
+  keys.sort();
+  keys.dedup();
+
+  let mut fields = Map::new();
+  let mut conflicting = Vec::new();
+  for key in keys {
+    let (base, ours, theirs) = (base.get(key), ours.get(key), theirs.get(key));
+    let value = if ours == theirs || theirs == base {
+      ours.cloned()
+    } else if ours == base {
+      theirs.cloned()
+    } else {
+      conflicting.push(key.clone());
+      match (ours, theirs) {
+        (Some(ours), Some(theirs)) => Some(greatest(ours.clone(), theirs.clone())),
+        (changed, None) | (None, changed) => changed.cloned(),
+      }
+    };
+    if let Some(value) = value {
+      fields.insert(key.clone(), value);
+    }
+  }
+  return (fields, conflicting);
+}
+
+fn greatest(first: Value, second: Value) -> Value {
+  let (first_text, second_text) = (first.to_string(), second.to_string());
+  match first_text >= second_text {
+    true => first,
+    false => second,
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{merge, merge_file};
+  use crate::clock::SystemClock;
+  use crate::{project::Project, storage::encode, task::Task, traits::Indexable};
+
+  fn retitle(task: &Task, title: &str) -> Task {
+    Task::from_parts(
+      task.id(),
+      task.project_id(),
+      task.times().clone(),
+      title,
+      task.tags().clone(),
+      task.is_paused(),
+    )
+  }
+
+  #[test]
+  fn merge_concurrent_additions() {
+    let project_id = uuid::Uuid::new_v4();
+    let base = vec![Task::new(
+      project_id,
+      "base",
+      vec![],
+      None,
+      None,
+      &SystemClock,
+    )];
+    let mut ours = base.clone();
+    ours.push(Task::new(
+      project_id,
+      "ours",
+      vec![],
+      None,
+      None,
+      &SystemClock,
+    ));
+    let mut theirs = base.clone();
+    theirs.push(Task::new(
+      project_id,
+      "theirs",
+      vec![],
+      None,
+      None,
+      &SystemClock,
+    ));
+
+    let merged = merge(&base, &ours, &theirs).unwrap();
+    let titles: Vec<&str> = merged.items.iter().map(|task| task.title()).collect();
+    assert_eq!(titles, vec!["base", "ours", "theirs"]);
+    assert!(merged.conflicts.is_empty());
+  }
+
+  #[test]
+  fn merge_changes_of_different_fields() {
+    let project_id = uuid::Uuid::new_v4();
+    let task = Task::new(project_id, "title", vec![], None, None, &SystemClock);
+    let ours = retitle(&task, "new title");
+    let mut theirs = task.clone();
+    theirs.stop(chrono::Local::now()).unwrap();
+
+    let merged = merge(&[task], &[ours], &[theirs.clone()]).unwrap();
+    assert!(merged.conflicts.is_empty());
+    assert_eq!(merged.items[0].title(), "new title");
+    assert_eq!(merged.items[0].stop_time(), theirs.stop_time());
+  }
+
+  #[test]
+  fn merge_conflicts_deterministically() {
+    let project = Project::new("project");
+    let ours = [Project::from_parts(project.id(), "first")];
+    let theirs = [Project::from_parts(project.id(), "second")];
+    let base = [project];
+
+    let merged = merge(&base, &ours, &theirs).unwrap();
+    let swapped = merge(&base, &theirs, &ours).unwrap();
+    assert_eq!(merged.conflicts.len(), 1);
+    assert_eq!(merged.items[0].name(), "second");
+    assert_eq!(swapped.items[0].name(), "second");
+  }
+
+  #[test]
+  fn merge_removals() {
+    let project_id = uuid::Uuid::new_v4();
+    let removed = Task::new(project_id, "removed", vec![], None, None, &SystemClock);
+    let changed = Task::new(project_id, "changed", vec![], None, None, &SystemClock);
+    let base = vec![removed.clone(), changed.clone()];
+    let theirs_changed = retitle(&changed, "changed by them");
+
+    let merged = merge(&base, &[], &[removed, theirs_changed]).unwrap();
+    assert_eq!(merged.items.len(), 1);
+    assert_eq!(merged.items[0].title(), "changed by them");
+    assert_eq!(merged.conflicts.len(), 1);
+    assert_eq!(merged.conflicts[0].id, changed.id());
+  }
+
+  #[test]
+  fn merge_file_by_name() {
+    let project = Project::new("project");
+    let content = encode(&vec![project]).unwrap();
+    let (merged, conflicts) = merge_file("data/projects.json", "", &content, "").unwrap();
+    assert!(conflicts.is_empty());
+    assert_eq!(merged, content);
+    assert!(merge_file("busy.sqlite", "", "", "").is_err());
+  }
+}
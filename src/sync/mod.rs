@@ -1,9 +1,13 @@
 mod config;
+mod merge;
 mod syncer;
+mod syncer_directory;
 mod syncer_empty;
 mod syncer_git;
 
-pub use config::SyncerConfig;
+pub use config::{MergeDriver, SyncerConfig};
+pub use merge::{merge, merge_file, Conflict, Merged, CONFLICT_REPORT_PREFIX};
 pub use syncer::Syncer;
+pub use syncer_directory::DirectorySyncer;
 pub use syncer_empty::EmptySyncer;
 pub use syncer_git::GitSyncer;
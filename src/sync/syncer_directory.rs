@@ -0,0 +1,304 @@
+use log::debug;
+
+use crate::storage::{
+  atomic,
+  lock::{StorageLock, LOCK_TIMEOUT},
+};
+
+use super::{
+  merge::{merge_file, CONFLICT_REPORT_PREFIX, MERGED_FILES},
+  syncer::Syncer,
+};
+
+/// Syncs storage files through a shared folder, e.g. a NAS mount or a folder
+/// synced by another tool. Files are merged record by record against the
+/// state of the last sync, which is kept in `.sync-base`.
+pub struct DirectorySyncer {
+  main_folder_path: std::path::PathBuf,
+  remote_path: std::path::PathBuf,
+}
+
+impl DirectorySyncer {
+  pub fn new(main_folder_path: &str, path: &str) -> Self {
+    Self {
+      main_folder_path: std::path::PathBuf::from(main_folder_path),
+      remote_path: std::path::PathBuf::from(path),
+    }
+  }
+
+  fn base_path(&self) -> std::path::PathBuf {
+    self.main_folder_path.join(".sync-base")
+  }
+
+  // Both folders are locked, so neither busy processes working with the same
+  // storage nor other machines syncing through the folder interfere.
+  fn locked<F>(&self, action: F) -> std::io::Result<String>
+  where
+    F: FnOnce(&Self) -> std::io::Result<String>,
+  {
+    std::fs::create_dir_all(&self.remote_path)?;
+    std::fs::create_dir_all(self.base_path())?;
+
+    let lock = |folder: &std::path::Path| {
+      StorageLock::exclusive(folder.join(".busy.lock").to_str().unwrap(), LOCK_TIMEOUT)
+        .map_err(std::io::Error::other)
+    };
+    let _remote_lock = lock(&self.remote_path)?;
+    let _local_lock = lock(&self.main_folder_path)?;
+    action(self)
+  }
+
+  // Files missing in `from` are removed, so the copy matches it exactly.
+  fn copy_files(&self, from: &std::path::Path, to: &[std::path::PathBuf]) -> std::io::Result<()> {
+    for filename in MERGED_FILES.iter() {
+      let content = read_file(&from.join(filename))?;
+      for folder in to.iter() {
+        let filepath = folder.join(filename);
+        match content.as_ref() {
+          Some(content) => atomic::write_file(&filepath, content.as_bytes())?,
+          None => remove_file(&filepath)?,
+        }
+      }
+    }
+    Ok(())
+  }
+}
+
+impl Syncer for DirectorySyncer {
+  fn commit(&mut self, msg: &str) -> std::io::Result<String> {
+    return Ok(format!("cmd: 'commit', msg: {msg}"));
+  }
+
+  fn sync(&mut self) -> std::io::Result<String> {
+    self.locked(|syncer| {
+      let mut report = Vec::new();
+      for filename in MERGED_FILES.iter() {
+        let ours = read_file(&syncer.main_folder_path.join(filename))?;
+        let theirs = read_file(&syncer.remote_path.join(filename))?;
+        if ours.is_none() && theirs.is_none() {
+          continue;
+        }
+        let base = read_file(&syncer.base_path().join(filename))?;
+
+        let (content, conflicts) = merge_file(
+          filename,
+          &base.unwrap_or_default(),
+          &ours.unwrap_or_default(),
+          &theirs.unwrap_or_default(),
+        )
+        .map_err(std::io::Error::other)?;
+        for conflict in conflicts {
+          report.push(format!(
+            "{} {}: {}",
+            CONFLICT_REPORT_PREFIX, filename, conflict
+          ));
+        }
+
+        for folder in [
+          &syncer.main_folder_path,
+          &syncer.remote_path,
+          &syncer.base_path(),
+        ] {
+          atomic::write_file(&folder.join(filename), content.as_bytes())?;
+        }
+        debug!("synced {} with: {:?}", filename, syncer.remote_path);
+      }
+      return Ok(report.join("\n"));
+    })
+  }
+
+  fn push_force(&mut self) -> std::io::Result<String> {
+    self.locked(|syncer| {
+      syncer.copy_files(
+        &syncer.main_folder_path,
+        &[syncer.remote_path.clone(), syncer.base_path()],
+      )?;
+      Ok("push force success".to_string())
+    })
+  }
+
+  fn pull_force(&mut self) -> std::io::Result<String> {
+    self.locked(|syncer| {
+      syncer.copy_files(
+        &syncer.remote_path,
+        &[syncer.main_folder_path.clone(), syncer.base_path()],
+      )?;
+      Ok("pull force success".to_string())
+    })
+  }
+}
+
+fn read_file(filepath: &std::path::Path) -> std::io::Result<Option<String>> {
+  match std::fs::read_to_string(filepath) {
+    Ok(content) => Ok(Some(content)),
+    Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+    Err(err) => Err(err),
+  }
+}
+
+fn remove_file(filepath: &std::path::Path) -> std::io::Result<()> {
+  match std::fs::remove_file(filepath) {
+    Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err),
+    _ => Ok(()),
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::DirectorySyncer;
+  use crate::clock::SystemClock;
+  use crate::{
+    project::Project,
+    storage::{JsonStorage, Storage},
+    sync::Syncer,
+    task::Task,
+    traits::Indexable,
+  };
+
+  struct Client {
+    folder: tempfile::TempDir,
+    syncer: DirectorySyncer,
+  }
+
+  impl Client {
+    fn new(remote: &std::path::Path) -> Self {
+      let folder = tempfile::tempdir().unwrap();
+      let syncer = DirectorySyncer::new(folder.path().to_str().unwrap(), remote.to_str().unwrap());
+      Self { folder, syncer }
+    }
+
+    fn storage(&self) -> JsonStorage {
+      JsonStorage::new(self.folder.path().to_str().unwrap()).unwrap()
+    }
+  }
+
+  #[test]
+  fn directory_sync_merges_clients() {
+    let remote = tempfile::tempdir().unwrap();
+    let mut first = Client::new(remote.path());
+    let mut second = Client::new(remote.path());
+
+    let project = Project::new("project");
+    first.storage().add_project(&project).unwrap();
+    first
+      .storage()
+      .add_task(&Task::new(
+        project.id(),
+        "first",
+        vec![],
+        None,
+        None,
+        &SystemClock,
+      ))
+      .unwrap();
+    first.syncer.sync().unwrap();
+    second.syncer.sync().unwrap();
+
+    second
+      .storage()
+      .add_task(&Task::new(
+        project.id(),
+        "second",
+        vec![],
+        None,
+        None,
+        &SystemClock,
+      ))
+      .unwrap();
+    first
+      .storage()
+      .add_task(&Task::new(
+        project.id(),
+        "third",
+        vec![],
+        None,
+        None,
+        &SystemClock,
+      ))
+      .unwrap();
+    second.syncer.sync().unwrap();
+    first.syncer.sync().unwrap();
+    second.syncer.sync().unwrap();
+
+    for client in [&first, &second] {
+      let storage = client.storage();
+      assert_eq!(storage.tasks().len(), 3);
+      assert_eq!(storage.projects().len(), 1);
+    }
+  }
+
+  #[test]
+  fn directory_sync_reports_conflicts() {
+    let remote = tempfile::tempdir().unwrap();
+    let mut first = Client::new(remote.path());
+    let mut second = Client::new(remote.path());
+
+    let project = Project::new("project");
+    first.storage().add_project(&project).unwrap();
+    first.syncer.sync().unwrap();
+    second.syncer.sync().unwrap();
+
+    first
+      .storage()
+      .replace_project(&Project::from_parts(project.id(), "first"))
+      .unwrap();
+    second
+      .storage()
+      .replace_project(&Project::from_parts(project.id(), "second"))
+      .unwrap();
+    assert!(first.syncer.sync().unwrap().is_empty());
+    let report = second.syncer.sync().unwrap();
+    assert!(report.contains(&project.id().to_string()));
+    first.syncer.sync().unwrap();
+
+    assert_eq!(first.storage().projects()[0].name(), "second");
+    assert_eq!(second.storage().projects()[0].name(), "second");
+  }
+
+  #[test]
+  fn directory_force_pull() {
+    let remote = tempfile::tempdir().unwrap();
+    let mut first = Client::new(remote.path());
+    let mut second = Client::new(remote.path());
+
+    first.storage().add_project(&Project::new("first")).unwrap();
+    first.syncer.push_force().unwrap();
+    second
+      .storage()
+      .add_project(&Project::new("second"))
+      .unwrap();
+    second.syncer.pull_force().unwrap();
+
+    let projects = second.storage().projects();
+    assert_eq!(projects.len(), 1);
+    assert_eq!(projects[0].name(), "first");
+  }
+
+  #[test]
+  fn directory_force_removes_missing_files() {
+    let remote = tempfile::tempdir().unwrap();
+    let mut first = Client::new(remote.path());
+    let mut second = Client::new(remote.path());
+
+    first.storage().add_project(&Project::new("first")).unwrap();
+    first.syncer.push_force().unwrap();
+    let task = Task::new(
+      uuid::Uuid::new_v4(),
+      "local",
+      vec![],
+      None,
+      None,
+      &SystemClock,
+    );
+    second.storage().add_task(&task).unwrap();
+    second.syncer.push_force().unwrap();
+    assert!(remote.path().join("tasks.json").exists());
+
+    // the remote has no tasks after the first client forces its state
+    first.syncer.push_force().unwrap();
+    assert!(!remote.path().join("tasks.json").exists());
+    second.syncer.pull_force().unwrap();
+    assert!(second.storage().tasks().is_empty());
+    assert_eq!(second.storage().projects()[0].name(), "first");
+  }
+}
@@ -8,17 +8,23 @@ impl EmptySyncer {
   }
 }
 
+impl Default for EmptySyncer {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
 impl Syncer for EmptySyncer {
   fn commit(&mut self, msg: &str) -> std::io::Result<String> {
     return std::io::Result::Ok(format!("cmd: 'commit', msg: {msg}"));
   }
   fn sync(&mut self) -> std::io::Result<String> {
-    return std::io::Result::Ok(format!("cmd: 'sync'"));
+    return std::io::Result::Ok("cmd: 'sync'".to_string());
   }
   fn push_force(&mut self) -> std::io::Result<String> {
-    return std::io::Result::Ok(format!("cmd: 'push_force'"));
+    return std::io::Result::Ok("cmd: 'push_force'".to_string());
   }
   fn pull_force(&mut self) -> std::io::Result<String> {
-    return std::io::Result::Ok(format!("cmd: 'pull_force'"));
+    return std::io::Result::Ok("cmd: 'pull_force'".to_string());
   }
 }
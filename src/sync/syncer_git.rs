@@ -1,12 +1,13 @@
 use log::debug;
 
-use super::syncer::Syncer;
+use super::{config::MergeDriver, merge::MERGED_FILES, syncer::Syncer};
 
 pub struct GitSyncer {
   main_folder_path: String,
   key_file: Option<String>,
   remote: Option<String>,
   branch: String,
+  merge_driver: MergeDriver,
 }
 
 impl GitSyncer {
@@ -15,15 +16,17 @@ impl GitSyncer {
     key_file: Option<String>,
     remote: Option<String>,
     branch: Option<String>,
-  ) -> Self {
+    merge_driver: MergeDriver,
+  ) -> std::io::Result<Self> {
     let mut obj = Self {
       main_folder_path: main_folder_path.to_owned(),
       key_file,
       remote,
       branch: branch.unwrap_or("main".to_owned()),
+      merge_driver,
     };
-    _ = obj.init();
-    return obj;
+    obj.init()?;
+    return Ok(obj);
   }
 
   fn init(&mut self) -> std::io::Result<String> {
@@ -31,21 +34,80 @@ impl GitSyncer {
       .join(".git")
       .exists()
     {
+      self.ensure_gitignore()?;
+      self.ensure_gitattributes()?;
+      self.register_merge_driver()?;
       return self.set_remote();
     }
     self.git_with_args(&["init", "-b", self.branch.clone().as_str()])?;
+    self.register_merge_driver()?;
     _ = self.set_remote();
     _ = self.pull();
+    // written after the first pull, the remote may already have them
+    self.ensure_gitignore()?;
+    self.ensure_gitattributes()?;
 
     return Ok("initialization success".to_string());
   }
 
+  fn ensure_gitignore(&self) -> std::io::Result<()> {
+    let gitignore_path = std::path::Path::new(&self.main_folder_path).join(".gitignore");
+    // db backups, unfinished writes, locks and undo history are local only
+    let lines = ["*.bak", "*.tmp", "*.lock", "journal.json"].map(str::to_owned);
+    append_missing_lines(&gitignore_path, &lines)
+  }
+
+  fn ensure_gitattributes(&self) -> std::io::Result<()> {
+    let gitattributes_path = std::path::Path::new(&self.main_folder_path).join(".gitattributes");
+    let lines: Vec<String> = MERGED_FILES
+      .iter()
+      .map(|filename| format!("{} merge=busy", filename))
+      .collect();
+    append_missing_lines(&gitattributes_path, &lines)
+  }
+
+  // Merge drivers live in the local git config, so every clone registers
+  // the driver pointing to its own busy binary. The config is written only
+  // when the driver is missing or points elsewhere.
+  fn register_merge_driver(&mut self) -> std::io::Result<()> {
+    let busy_path = match &self.merge_driver {
+      MergeDriver::CurrentExe => std::env::current_exe()?,
+      MergeDriver::Binary(path) => std::path::PathBuf::from(path),
+      MergeDriver::Disabled => return Ok(()),
+    };
+    let driver = format!("'{}' merge-driver %O %A %B %P", busy_path.to_string_lossy());
+    // `git config --get` fails when the key is missing
+    let registered = self.git_with_args(&["config", "--get", "merge.busy.driver"]);
+    if registered.is_ok_and(|registered| registered.trim() == driver) {
+      return Ok(());
+    }
+    self.git_with_args(&["config", "merge.busy.name", "busy records merge"])?;
+    self.git_with_args(&["config", "merge.busy.driver", driver.as_str()])?;
+    Ok(())
+  }
+
   fn push(&mut self) -> std::io::Result<String> {
     return self.git_with_args(&["push", "-u", "origin", self.branch.clone().as_str()]);
   }
 
   fn pull(&mut self) -> std::io::Result<String> {
-    return self.git_with_args(&["pull", "origin", self.branch.clone().as_str()]);
+    return self.git_with_args(&[
+      "pull",
+      "--no-rebase",
+      "origin",
+      self.branch.clone().as_str(),
+    ]);
+  }
+
+  // The very first sync goes to an empty remote, there is nothing to pull.
+  fn remote_branch_exists(&mut self) -> std::io::Result<bool> {
+    let output = self.git_with_args(&[
+      "ls-remote",
+      "--heads",
+      "origin",
+      self.branch.clone().as_str(),
+    ])?;
+    return Ok(!output.trim().is_empty());
   }
 
   fn set_remote(&mut self) -> std::io::Result<String> {
@@ -84,7 +146,10 @@ impl Syncer for GitSyncer {
   }
 
   fn sync(&mut self) -> std::io::Result<String> {
-    let pull_output = self.pull()?;
+    let pull_output = match self.remote_branch_exists()? {
+      true => self.pull()?,
+      false => String::new(),
+    };
     let push_output = self.push()?;
     return Ok(format!(
       "git pull output:\n{}\n\ngit push output:\n{}",
@@ -113,20 +178,45 @@ impl Syncer for GitSyncer {
   }
 }
 
+// Lines the user already has are kept as they are.
+fn append_missing_lines(path: &std::path::Path, lines: &[String]) -> std::io::Result<()> {
+  let mut content = match std::fs::read_to_string(path) {
+    Ok(content) => content,
+    Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+    Err(err) => return Err(err),
+  };
+
+  let mut changed = false;
+  for line in lines {
+    if !content.lines().any(|existing| existing.trim() == line) {
+      if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+      }
+      content.push_str(line);
+      content.push('\n');
+      changed = true;
+    }
+  }
+  if !changed {
+    return Ok(());
+  }
+  std::fs::write(path, content)
+}
+
 fn git_with_args(cwd: &str, key_file: Option<&String>, args: &[&str]) -> std::io::Result<String> {
   debug!("run git with args: {args:?} cwd: {cwd} key_file: {key_file:?}");
 
   let mut command = std::process::Command::new("git");
   command.current_dir(cwd);
-  if key_file.is_some() {
-    command.env("GIT_SSH_COMMAND", format!("ssh -i {}", key_file.unwrap()));
+  if let Some(key_file) = key_file {
+    command.env("GIT_SSH_COMMAND", format!("ssh -i {}", key_file));
   }
   let output = command.args(args).output()?;
 
   let stdout = String::from_utf8(output.stdout.clone()).unwrap_or_default();
   if !output.status.success() {
     debug!("git with err: {} status: {}", stdout, output.status);
-    return Err(std::io::Error::new(std::io::ErrorKind::Other, stdout));
+    return Err(std::io::Error::other(stdout));
   }
 
   debug!("git with output: {:?} status: {}", stdout, output.status);
@@ -1,9 +1,17 @@
-use crate::traits::Indexable;
+use crate::{
+  color::parse_color,
+  traits::{Indexable, Trashable},
+};
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct Tag {
   id: uuid::Uuid,
   name: String,
+  /// Color name the viewer prints the tag with.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  color: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  deleted_at: Option<chrono::DateTime<chrono::Local>>,
 }
 
 impl Indexable for Tag {
@@ -12,15 +20,52 @@ impl Indexable for Tag {
   }
 }
 
+impl Trashable for Tag {
+  fn deleted_at(&self) -> Option<chrono::DateTime<chrono::Local>> {
+    self.deleted_at
+  }
+
+  fn set_deleted_at(&mut self, deleted_at: Option<chrono::DateTime<chrono::Local>>) {
+    self.deleted_at = deleted_at;
+  }
+}
+
 impl Tag {
   pub fn new(name: &str) -> Self {
     Self {
       id: uuid::Uuid::new_v4(),
       name: name.to_owned(),
+      color: None,
+      deleted_at: None,
+    }
+  }
+
+  pub(crate) fn from_parts(id: uuid::Uuid, name: &str) -> Self {
+    Self {
+      id,
+      name: name.to_owned(),
+      color: None,
+      deleted_at: None,
     }
   }
 
   pub fn name(&self) -> &str {
     self.name.as_str()
   }
+
+  pub fn color(&self) -> Option<&str> {
+    self.color.as_deref()
+  }
+
+  pub fn set_name(&mut self, name: &str) {
+    self.name = name.to_owned();
+  }
+
+  pub fn set_color(&mut self, color: Option<String>) -> Result<(), String> {
+    if let Some(color) = color.as_deref() {
+      parse_color(color)?;
+    }
+    self.color = color;
+    Ok(())
+  }
 }
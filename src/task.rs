@@ -1,6 +1,12 @@
-use crate::{tag::Tag, time::DateTimeInterval, traits::Indexable};
+use crate::{
+  clock::Clock,
+  tag::Tag,
+  time::DateTimeInterval,
+  traits::{Indexable, Trashable},
+  Error,
+};
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct Task {
   id: uuid::Uuid,
   project_id: uuid::Uuid,
@@ -8,6 +14,8 @@ pub struct Task {
   title: String,
   tags: Vec<uuid::Uuid>,
   is_paused: bool,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  deleted_at: Option<chrono::DateTime<chrono::Local>>,
 }
 
 impl Indexable for Task {
@@ -16,6 +24,16 @@ impl Indexable for Task {
   }
 }
 
+impl Trashable for Task {
+  fn deleted_at(&self) -> Option<chrono::DateTime<chrono::Local>> {
+    self.deleted_at
+  }
+
+  fn set_deleted_at(&mut self, deleted_at: Option<chrono::DateTime<chrono::Local>>) {
+    self.deleted_at = deleted_at;
+  }
+}
+
 impl Task {
   pub fn new(
     project_id: uuid::Uuid,
@@ -23,17 +41,38 @@ impl Task {
     tags: Vec<uuid::Uuid>,
     start_time: Option<chrono::DateTime<chrono::Local>>,
     finish_time: Option<chrono::DateTime<chrono::Local>>,
+    clock: &dyn Clock,
   ) -> Self {
     Self {
       id: uuid::Uuid::new_v4(),
       project_id,
       times: vec![DateTimeInterval {
-        start_time: start_time.unwrap_or(chrono::Local::now()),
+        start_time: start_time.unwrap_or_else(|| clock.now()),
         stop_time: finish_time,
       }],
       title: title.to_owned(),
       tags,
       is_paused: false,
+      deleted_at: None,
+    }
+  }
+
+  pub(crate) fn from_parts(
+    id: uuid::Uuid,
+    project_id: uuid::Uuid,
+    times: Vec<DateTimeInterval>,
+    title: &str,
+    tags: Vec<uuid::Uuid>,
+    is_paused: bool,
+  ) -> Self {
+    Self {
+      id,
+      project_id,
+      times,
+      title: title.to_owned(),
+      tags,
+      is_paused,
+      deleted_at: None,
     }
   }
 
@@ -61,37 +100,81 @@ impl Task {
     self.times.last().unwrap().stop_time
   }
 
-  pub fn duration(&self) -> chrono::Duration {
+  pub(crate) fn set_times(&mut self, times: Vec<DateTimeInterval>) {
+    self.times = times;
+  }
+
+  pub(crate) fn set_paused(&mut self, is_paused: bool) {
+    self.is_paused = is_paused;
+  }
+
+  pub(crate) fn set_project_id(&mut self, project_id: uuid::Uuid) {
+    self.project_id = project_id;
+  }
+
+  pub(crate) fn set_tags(&mut self, tags: Vec<uuid::Uuid>) {
+    self.tags = tags;
+  }
+
+  pub fn duration(&self, clock: &dyn Clock) -> chrono::Duration {
     let mut total_duration = chrono::Duration::zero();
     for interval in self.times.iter() {
-      total_duration = total_duration.checked_add(&interval.duration()).unwrap();
+      total_duration = total_duration
+        .checked_add(&interval.duration(clock))
+        .unwrap();
     }
     return total_duration;
   }
 
-  pub fn stop(&mut self) {
-    self.times.last_mut().unwrap().stop_time = Some(chrono::Local::now());
+  /// Stops the last interval at `at`, it can't be before the interval start.
+  pub fn stop(&mut self, at: chrono::DateTime<chrono::Local>) -> Result<(), Error> {
+    let last = self.times.last_mut().unwrap();
+    if at < last.start_time {
+      return Err(Error::InvalidTime(format!(
+        "stop time {} is before the start time {}",
+        format_time(&at),
+        format_time(&last.start_time)
+      )));
+    }
+    last.stop_time = Some(at);
     self.is_paused = false;
+    Ok(())
   }
 
   pub fn is_paused(&self) -> bool {
     self.is_paused
   }
 
-  pub fn pause(&mut self) {
-    self.stop();
+  pub fn pause(&mut self, at: chrono::DateTime<chrono::Local>) -> Result<(), Error> {
+    self.stop(at)?;
     self.is_paused = true;
+    Ok(())
   }
 
-  pub fn resume(&mut self) {
+  /// Starts a new interval at `at`, it can't overlap the previous ones.
+  pub fn resume(&mut self, at: chrono::DateTime<chrono::Local>) -> Result<(), Error> {
+    if let Some(stop_time) = self.stop_time() {
+      if at < stop_time {
+        return Err(Error::InvalidTime(format!(
+          "resume time {} overlaps the previous interval, which ends at {}",
+          format_time(&at),
+          format_time(&stop_time)
+        )));
+      }
+    }
     self.times.push(DateTimeInterval {
-      start_time: chrono::Local::now(),
+      start_time: at,
       stop_time: None,
     });
     self.is_paused = false;
+    Ok(())
   }
 }
 
+fn format_time(time: &chrono::DateTime<chrono::Local>) -> String {
+  time.format("%Y-%m-%d %H:%M").to_string()
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct TaskView {
   id: uuid::Uuid,
@@ -103,9 +186,9 @@ pub struct TaskView {
 }
 
 impl TaskView {
-  pub fn from_task(task: &Task, all_tags: &Vec<Tag>) -> Self {
+  pub fn from_task(task: &Task, all_tags: &[Tag]) -> Self {
     TaskView {
-      id: task.id().clone(),
+      id: task.id(),
       project_id: task.project_id(),
       times: task.times().clone(),
       title: task.title().to_owned(),
@@ -118,7 +201,7 @@ impl TaskView {
     }
   }
 
-  pub fn resolve_new_tags(&self, existing_tags: &Vec<Tag>) -> Vec<String> {
+  pub fn resolve_new_tags(&self, existing_tags: &[Tag]) -> Vec<String> {
     let mut new_tags = vec![];
     for tag_name in self.tags.iter() {
       if existing_tags
@@ -132,7 +215,7 @@ impl TaskView {
     return new_tags;
   }
 
-  pub fn to_task(&self, all_tags: &Vec<Tag>) -> Task {
+  pub fn to_task(&self, all_tags: &[Tag]) -> Task {
     let tag_ids = self
       .tags
       .iter()
@@ -152,6 +235,90 @@ impl TaskView {
       title: self.title.clone(),
       tags: tag_ids,
       is_paused: self.is_paused,
+      deleted_at: None,
     }
   }
 }
+
+#[cfg(test)]
+mod test {
+  use chrono::TimeZone;
+
+  use super::Task;
+  use crate::{
+    clock::{Clock, ManualClock},
+    Error,
+  };
+
+  fn clock() -> ManualClock {
+    ManualClock::new(
+      chrono::Local
+        .with_ymd_and_hms(2024, 3, 14, 9, 0, 0)
+        .unwrap(),
+    )
+  }
+
+  #[test]
+  fn task_start_and_stop() {
+    let clock = clock();
+    let mut task = Task::new(uuid::Uuid::new_v4(), "title", vec![], None, None, &clock);
+    assert_eq!(task.start_time(), clock.now());
+    assert!(task.stop_time().is_none());
+
+    clock.advance(chrono::Duration::minutes(30));
+    assert_eq!(task.duration(&clock), chrono::Duration::minutes(30));
+
+    task.stop(clock.now()).unwrap();
+    assert_eq!(task.stop_time(), Some(clock.now()));
+    clock.advance(chrono::Duration::hours(1));
+    assert_eq!(task.duration(&clock), chrono::Duration::minutes(30));
+  }
+
+  #[test]
+  fn task_pause_and_resume() {
+    let clock = clock();
+    let mut task = Task::new(uuid::Uuid::new_v4(), "title", vec![], None, None, &clock);
+    clock.advance(chrono::Duration::minutes(20));
+    task.pause(clock.now()).unwrap();
+    assert!(task.is_paused());
+
+    // time on pause isn't counted
+    clock.advance(chrono::Duration::hours(1));
+    assert_eq!(task.duration(&clock), chrono::Duration::minutes(20));
+
+    assert!(matches!(
+      task.resume(clock.now() - chrono::Duration::hours(2)),
+      Err(Error::InvalidTime(_))
+    ));
+    task.resume(clock.now()).unwrap();
+    assert!(!task.is_paused());
+    assert_eq!(task.times().len(), 2);
+    assert!(task.stop_time().is_none());
+
+    clock.advance(chrono::Duration::minutes(10));
+    task.stop(clock.now()).unwrap();
+    assert_eq!(task.duration(&clock), chrono::Duration::minutes(30));
+    assert_eq!(
+      task.start_time(),
+      chrono::Local
+        .with_ymd_and_hms(2024, 3, 14, 9, 0, 0)
+        .unwrap()
+    );
+  }
+
+  #[test]
+  fn task_with_explicit_times() {
+    let clock = clock();
+    let start = clock.now() - chrono::Duration::hours(2);
+    let task = Task::new(
+      uuid::Uuid::new_v4(),
+      "title",
+      vec![],
+      Some(start),
+      Some(start + chrono::Duration::minutes(45)),
+      &clock,
+    );
+    assert_eq!(task.start_time(), start);
+    assert_eq!(task.duration(&clock), chrono::Duration::minutes(45));
+  }
+}
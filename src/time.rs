@@ -1,46 +1,266 @@
-use chrono::{ParseResult, TimeZone};
+use chrono::{Datelike, TimeZone};
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+use crate::{clock::Clock, Error};
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct DateTimeInterval {
   pub start_time: chrono::DateTime<chrono::Local>,
   pub stop_time: Option<chrono::DateTime<chrono::Local>>,
 }
 
 impl DateTimeInterval {
-  pub fn duration(&self) -> chrono::Duration {
+  /// An interval which isn't stopped yet lasts till the clock's now.
+  pub fn duration(&self, clock: &dyn Clock) -> chrono::Duration {
     return self
       .stop_time
-      .unwrap_or(chrono::Local::now())
+      .unwrap_or_else(|| clock.now())
       .signed_duration_since(self.start_time);
   }
 }
 
-pub fn parse_datetime(datetime: &str) -> ParseResult<chrono::DateTime<chrono::Local>> {
-  let mut input = datetime.to_owned();
-  if !input.contains(" ") {
-    input = format!("{} {}", chrono::Local::now().format("%Y-%m-%d"), input);
+pub const TIME_FORMATS_HELP: &str =
+  "HH:MM, YYYY-mm-dd HH:MM, ISO 8601, now, noon, -25m, 2h ago, yesterday 17:30 or mon 9:00";
+
+/// Parses a moment in local time relative to the clock's now. Supported forms:
+/// `HH:MM` (today), `YYYY-mm-dd [HH:MM]`, ISO 8601 with or without offset,
+/// `now`, `noon`, `midnight`, `-25m`/`-1h30m`, `2h ago`,
+/// `today|yesterday|tomorrow [time]` and `mon..sun [time]` for the latest
+/// such day which isn't after today.
+pub fn parse_datetime(
+  datetime: &str,
+  clock: &dyn Clock,
+) -> Result<chrono::DateTime<chrono::Local>, Error> {
+  let input = datetime.trim().to_lowercase();
+  let invalid = |reason: &str| {
+    Error::InvalidTime(format!(
+      "can't parse time '{}': {}, expected {}",
+      datetime, reason, TIME_FORMATS_HELP
+    ))
+  };
+  if input.is_empty() {
+    return Err(invalid("it's empty"));
+  }
+
+  let upper = input.to_uppercase();
+  if let Ok(parsed) = chrono::DateTime::parse_from_rfc3339(&upper) {
+    return Ok(parsed.with_timezone(&chrono::Local));
+  }
+  for format in [
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%d %H:%M",
+  ] {
+    if let Ok(naive) = chrono::NaiveDateTime::parse_from_str(&upper, format) {
+      return to_local(naive).map_err(|reason| invalid(&reason));
+    }
+  }
+  if let Ok(date) = chrono::NaiveDate::parse_from_str(&input, "%Y-%m-%d") {
+    return to_local(date.and_hms_opt(0, 0, 0).unwrap()).map_err(|reason| invalid(&reason));
+  }
+
+  let now = clock.now();
+  if input == "now" {
+    return Ok(now);
+  }
+  if let Some(offset) = input
+    .strip_prefix('-')
+    .or_else(|| input.strip_suffix("ago"))
+  {
+    let duration = parse_duration(offset).map_err(|reason| invalid(&reason))?;
+    return now
+      .checked_sub_signed(duration)
+      .ok_or_else(|| invalid("it's out of range"));
+  }
+
+  let (day_part, time_part) = match input.split_once(char::is_whitespace) {
+    Some((day, time)) => (Some(day), Some(time.trim())),
+    None => match parse_day(&input, now.date_naive()) {
+      Some(_) => (Some(input.as_str()), None),
+      None => (None, Some(input.as_str())),
+    },
+  };
+  let date = match day_part {
+    Some(day) => {
+      parse_day(day, now.date_naive()).ok_or_else(|| invalid(&format!("unknown day '{}'", day)))?
+    }
+    None => now.date_naive(),
+  };
+  let time = match time_part {
+    Some(time) => parse_time(time).map_err(|reason| invalid(&reason))?,
+    None => chrono::NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
+  };
+  return to_local(date.and_time(time)).map_err(|reason| invalid(&reason));
+}
+
+fn to_local(naive: chrono::NaiveDateTime) -> Result<chrono::DateTime<chrono::Local>, String> {
+  chrono::Local
+    .from_local_datetime(&naive)
+    .earliest()
+    .ok_or_else(|| format!("{} doesn't exist in the local timezone", naive))
+}
+
+fn parse_day(day: &str, today: chrono::NaiveDate) -> Option<chrono::NaiveDate> {
+  let days_back = match day {
+    "today" => 0,
+    "yesterday" => 1,
+    "tomorrow" => -1,
+    _ => {
+      let weekday: chrono::Weekday = day.parse().ok()?;
+      let days_back =
+        today.weekday().num_days_from_monday() as i64 - weekday.num_days_from_monday() as i64;
+      days_back.rem_euclid(7)
+    }
+  };
+  today.checked_sub_signed(chrono::Duration::days(days_back))
+}
+
+fn parse_time(time: &str) -> Result<chrono::NaiveTime, String> {
+  match time {
+    "noon" => return Ok(chrono::NaiveTime::from_hms_opt(12, 0, 0).unwrap()),
+    "midnight" => return Ok(chrono::NaiveTime::from_hms_opt(0, 0, 0).unwrap()),
+    _ => {}
+  };
+  let (hours, minutes) = time
+    .split_once(':')
+    .ok_or_else(|| format!("unknown time '{}'", time))?;
+  let parse = |value: &str| {
+    value
+      .parse::<u32>()
+      .map_err(|_| format!("unknown time '{}'", time))
+  };
+  chrono::NaiveTime::from_hms_opt(parse(hours)?, parse(minutes)?, 0)
+    .ok_or_else(|| format!("time '{}' is out of range", time))
+}
+
+/// Parses durations like `25m`, `1h30m`, `2 hours` or `1h 15 min`.
+fn parse_duration(input: &str) -> Result<chrono::Duration, String> {
+  let input = input.trim();
+  if input.is_empty() {
+    return Err("duration is missing".to_string());
+  }
+
+  let mut total = chrono::Duration::zero();
+  let mut rest = input;
+  while !rest.is_empty() {
+    let digits_end = rest
+      .find(|c: char| !c.is_ascii_digit())
+      .unwrap_or(rest.len());
+    if digits_end == 0 {
+      return Err(format!("expected a number in '{}'", input));
+    }
+    let value: i64 = rest[..digits_end]
+      .parse()
+      .map_err(|_| format!("number in '{}' is too big", input))?;
+    rest = rest[digits_end..].trim_start();
+
+    let unit_end = rest
+      .find(|c: char| !c.is_ascii_alphabetic())
+      .unwrap_or(rest.len());
+    let unit = &rest[..unit_end];
+    rest = rest[unit_end..].trim_start();
+    let unit_seconds = match unit {
+      "s" | "sec" | "secs" | "second" | "seconds" => 1,
+      "m" | "min" | "mins" | "minute" | "minutes" => 60,
+      "h" | "hr" | "hrs" | "hour" | "hours" => 60 * 60,
+      "d" | "day" | "days" => 24 * 60 * 60,
+      "" => return Err(format!("time unit is missing in '{}'", input)),
+      _ => return Err(format!("unknown time unit '{}'", unit)),
+    };
+    // `Duration::seconds` panics on values it can't hold
+    total = value
+      .checked_mul(unit_seconds)
+      .filter(|seconds| *seconds <= chrono::Duration::max_value().num_seconds())
+      .and_then(|seconds| total.checked_add(&chrono::Duration::seconds(seconds)))
+      .ok_or_else(|| format!("duration '{}' is out of range", input))?;
   }
-  return chrono::Local.datetime_from_str(&input, "%Y-%m-%d %H:%M");
+  Ok(total)
 }
 
 #[cfg(test)]
 mod tests {
-  use crate::time::parse_datetime;
+  use chrono::TimeZone;
+
+  use crate::{clock::ManualClock, time::parse_datetime};
+
+  fn clock() -> ManualClock {
+    // Thursday
+    ManualClock::new(
+      chrono::Local
+        .with_ymd_and_hms(2024, 3, 14, 15, 40, 0)
+        .unwrap(),
+    )
+  }
+
+  fn parse(input: &str) -> String {
+    parse_datetime(input, &clock())
+      .unwrap()
+      .format("%Y-%m-%d %H:%M")
+      .to_string()
+  }
 
   #[test]
   fn test_parse_datetime() {
-    let datetime = parse_datetime("2020-01-01 00:00");
-    assert!(datetime.unwrap().format("%Y-%m-%d %H:%M:%S").to_string() == "2020-01-01 00:00:00");
+    assert_eq!(parse("2020-01-01 00:00"), "2020-01-01 00:00");
+    assert_eq!(parse("2020-01-01"), "2020-01-01 00:00");
+    assert_eq!(parse("2020-01-01T08:30"), "2020-01-01 08:30");
   }
 
   #[test]
   fn test_parse_time() {
-    let datetime = parse_datetime("11:00");
-    assert!(datetime.is_ok());
+    assert_eq!(parse("11:00"), "2024-03-14 11:00");
+    assert_eq!(parse("9:05"), "2024-03-14 09:05");
+    assert_eq!(parse("now"), "2024-03-14 15:40");
+    assert_eq!(parse("noon"), "2024-03-14 12:00");
+    assert_eq!(parse("Midnight"), "2024-03-14 00:00");
+  }
+
+  #[test]
+  fn test_parse_relative() {
+    assert_eq!(parse("-25m"), "2024-03-14 15:15");
+    assert_eq!(parse("-1h30m"), "2024-03-14 14:10");
+    assert_eq!(parse("2h ago"), "2024-03-14 13:40");
+    assert_eq!(parse("1 hour 5 min ago"), "2024-03-14 14:35");
+  }
 
+  #[test]
+  fn test_parse_days() {
+    assert_eq!(parse("yesterday 17:30"), "2024-03-13 17:30");
+    assert_eq!(parse("yesterday"), "2024-03-13 00:00");
+    assert_eq!(parse("today noon"), "2024-03-14 12:00");
+    assert_eq!(parse("mon 9:00"), "2024-03-11 09:00");
+    assert_eq!(parse("thursday 8:00"), "2024-03-14 08:00");
+    assert_eq!(parse("fri"), "2024-03-08 00:00");
+  }
+
+  #[test]
+  fn test_parse_iso8601_with_offset() {
+    let parsed = parse_datetime("2024-03-14T10:00:00+02:00", &clock()).unwrap();
     assert_eq!(
-      datetime.unwrap().format("%Y-%m-%d %H:%M").to_string(),
-      format!("{} {}", chrono::Local::now().format("%Y-%m-%d"), "11:00")
+      parsed,
+      chrono::FixedOffset::east_opt(2 * 3600)
+        .unwrap()
+        .with_ymd_and_hms(2024, 3, 14, 10, 0, 0)
+        .unwrap()
     );
+    let utc = parse_datetime("2024-03-14T10:00:00Z", &clock()).unwrap();
+    assert_eq!(
+      utc,
+      chrono::Utc.with_ymd_and_hms(2024, 3, 14, 10, 0, 0).unwrap()
+    );
+  }
+
+  #[test]
+  fn test_parse_errors() {
+    let error = |input: &str| parse_datetime(input, &clock()).unwrap_err().to_string();
+    assert!(error("25:00").contains("out of range"));
+    assert!(error("-25x").contains("unknown time unit 'x'"));
+    assert!(error("ago").contains("duration is missing"));
+    assert!(error("someday 10:00").contains("unknown day 'someday'"));
+    assert!(error("").contains("empty"));
+    assert!(error("later").contains("expected HH:MM"));
+    assert!(error("-9999999999999999h").contains("out of range"));
+    assert!(error("-99999999d").contains("out of range"));
+    assert!(error("99999999999d 99999999999d ago").contains("out of range"));
   }
 }
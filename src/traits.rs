@@ -1,3 +1,12 @@
 pub trait Indexable {
   fn id(&self) -> uuid::Uuid;
 }
+
+pub trait Trashable {
+  fn deleted_at(&self) -> Option<chrono::DateTime<chrono::Local>>;
+  fn set_deleted_at(&mut self, deleted_at: Option<chrono::DateTime<chrono::Local>>);
+
+  fn is_deleted(&self) -> bool {
+    self.deleted_at().is_some()
+  }
+}
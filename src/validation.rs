@@ -0,0 +1,548 @@
+//! Consistency checks of task intervals and fixes for what's found.
+
+use crate::{task::Task, time::DateTimeInterval, traits::Indexable};
+
+// Bounds the fixing loop, every fix removes an issue, but a trim could
+// uncover another one.
+const MAX_FIXES: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Issue {
+  /// Interval of the task stops before it starts.
+  NegativeInterval {
+    task_id: uuid::Uuid,
+    interval: usize,
+  },
+  /// Intervals of two tasks, or two intervals of the same task, overlap
+  /// from `from` to `to`.
+  Overlap {
+    task_id: uuid::Uuid,
+    interval: usize,
+    other_task_id: uuid::Uuid,
+    other_interval: usize,
+    from: chrono::DateTime<chrono::Local>,
+    to: chrono::DateTime<chrono::Local>,
+  },
+  /// More than one task is running at the same time.
+  SeveralActive { task_ids: Vec<uuid::Uuid> },
+  /// Paused task which has an interval that isn't stopped.
+  PausedWithOpenInterval { task_id: uuid::Uuid },
+  /// Nothing was tracked during the day from `from` to `to`.
+  Gap {
+    from: chrono::DateTime<chrono::Local>,
+    to: chrono::DateTime<chrono::Local>,
+  },
+}
+
+impl Issue {
+  pub fn task_ids(&self) -> Vec<uuid::Uuid> {
+    match self {
+      Issue::NegativeInterval { task_id, .. } => vec![*task_id],
+      Issue::Overlap {
+        task_id,
+        other_task_id,
+        ..
+      } => vec![*task_id, *other_task_id],
+      Issue::SeveralActive { task_ids } => task_ids.clone(),
+      Issue::PausedWithOpenInterval { task_id } => vec![*task_id],
+      Issue::Gap { .. } => vec![],
+    }
+  }
+}
+
+impl std::fmt::Display for Issue {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Issue::NegativeInterval { task_id, interval } => write!(
+        f,
+        "task {} interval #{} stops before it starts",
+        task_id,
+        interval + 1
+      ),
+      Issue::Overlap {
+        task_id,
+        other_task_id,
+        from,
+        to,
+        ..
+      } if task_id == other_task_id => write!(
+        f,
+        "intervals of task {} overlap from {} to {}",
+        task_id,
+        format_time(from),
+        format_time(to)
+      ),
+      Issue::Overlap {
+        task_id,
+        other_task_id,
+        from,
+        to,
+        ..
+      } => write!(
+        f,
+        "task {} overlaps task {} from {} to {}",
+        task_id,
+        other_task_id,
+        format_time(from),
+        format_time(to)
+      ),
+      Issue::SeveralActive { task_ids } => write!(
+        f,
+        "several tasks are active: {}",
+        task_ids
+          .iter()
+          .map(|id| id.to_string())
+          .collect::<Vec<String>>()
+          .join(", ")
+      ),
+      Issue::PausedWithOpenInterval { task_id } => {
+        write!(f, "task {} is paused, but isn't stopped", task_id)
+      }
+      Issue::Gap { from, to } => write!(
+        f,
+        "nothing is tracked from {} to {}",
+        format_time(from),
+        format_time(to)
+      ),
+    }
+  }
+}
+
+/// Change which repairs an issue: `task` replaces the stored one.
+#[derive(Debug, Clone)]
+pub struct Fix {
+  pub description: String,
+  pub task: Task,
+}
+
+/// Finds broken intervals and tasks. Intervals which aren't stopped last
+/// till `now`.
+pub fn check(tasks: &[Task], now: chrono::DateTime<chrono::Local>) -> Vec<Issue> {
+  let mut issues = Vec::new();
+  for task in tasks.iter() {
+    for (index, interval) in task.times().iter().enumerate() {
+      if interval
+        .stop_time
+        .is_some_and(|stop| stop < interval.start_time)
+      {
+        issues.push(Issue::NegativeInterval {
+          task_id: task.id(),
+          interval: index,
+        });
+      }
+    }
+    if task.is_paused() && task.stop_time().is_none() {
+      issues.push(Issue::PausedWithOpenInterval { task_id: task.id() });
+    }
+  }
+
+  let active: Vec<uuid::Uuid> = tasks
+    .iter()
+    .filter(|task| task.times().iter().any(|i| i.stop_time.is_none()))
+    .map(|task| task.id())
+    .collect();
+  if active.len() > 1 {
+    issues.push(Issue::SeveralActive { task_ids: active });
+  }
+
+  let spans = spans(tasks, now);
+  for (position, span) in spans.iter().enumerate() {
+    for other in spans[position + 1..].iter() {
+      if other.start >= span.end {
+        break;
+      }
+      issues.push(Issue::Overlap {
+        task_id: span.task_id,
+        interval: span.interval,
+        other_task_id: other.task_id,
+        other_interval: other.interval,
+        from: other.start,
+        to: std::cmp::min(span.end, other.end),
+      });
+    }
+  }
+  return issues;
+}
+
+/// Issues which involve the task, `tasks` should contain it.
+pub fn check_task(task: &Task, tasks: &[Task], now: chrono::DateTime<chrono::Local>) -> Vec<Issue> {
+  check(tasks, now)
+    .into_iter()
+    .filter(|issue| issue.task_ids().contains(&task.id()))
+    .collect()
+}
+
+/// Untracked time between tasks of the same day, at least `min_gap` long.
+pub fn gaps(
+  tasks: &[Task],
+  now: chrono::DateTime<chrono::Local>,
+  min_gap: chrono::Duration,
+) -> Vec<Issue> {
+  let mut gaps = Vec::new();
+  let mut covered_till: Option<chrono::DateTime<chrono::Local>> = None;
+  for span in spans(tasks, now) {
+    if let Some(till) = covered_till {
+      if span.start - till >= min_gap && span.start.date_naive() == till.date_naive() {
+        gaps.push(Issue::Gap {
+          from: till,
+          to: span.start,
+        });
+      }
+    }
+    covered_till = Some(covered_till.map_or(span.end, |till| till.max(span.end)));
+  }
+  return gaps;
+}
+
+/// Proposes trims and merges which repair the issues found by `check`.
+/// Several fixes of the same task are applied in order, the last one has
+/// the final version of the task.
+pub fn propose_fixes(tasks: &[Task], now: chrono::DateTime<chrono::Local>) -> Vec<Fix> {
+  let mut tasks = tasks.to_vec();
+  let mut fixes = Vec::new();
+  while fixes.len() < MAX_FIXES {
+    let fix = check(&tasks, now)
+      .iter()
+      .find_map(|issue| fix_issue(issue, &tasks));
+    let fix = match fix {
+      Some(fix) => fix,
+      None => break,
+    };
+    let position = tasks
+      .iter()
+      .position(|task| task.id() == fix.task.id())
+      .unwrap();
+    tasks[position] = fix.task.clone();
+    fixes.push(fix);
+  }
+  return fixes;
+}
+
+fn fix_issue(issue: &Issue, tasks: &[Task]) -> Option<Fix> {
+  let find = |id: uuid::Uuid| tasks.iter().find(|task| task.id() == id).cloned();
+  match issue {
+    Issue::NegativeInterval { task_id, interval } => {
+      let mut task = find(*task_id)?;
+      let mut times = task.times().clone();
+      let broken = &mut times[*interval];
+      let stop_time = broken.stop_time?;
+      broken.stop_time = Some(broken.start_time);
+      broken.start_time = stop_time;
+      task.set_times(times);
+      Some(Fix {
+        description: format!("swap start and stop of interval #{}", interval + 1),
+        task,
+      })
+    }
+    Issue::PausedWithOpenInterval { task_id } => {
+      let mut task = find(*task_id)?;
+      task.set_paused(false);
+      Some(Fix {
+        description: "mark the task as running".to_string(),
+        task,
+      })
+    }
+    Issue::SeveralActive { task_ids } => {
+      // the latest started task stays active
+      let mut active: Vec<Task> = task_ids.iter().filter_map(|id| find(*id)).collect();
+      active.sort_by_key(|task| task.times().last().unwrap().start_time);
+      let latest_start = active.last()?.times().last().unwrap().start_time;
+      let mut task = active.first()?.clone();
+      let mut times = task.times().clone();
+      let open = times.iter_mut().find(|i| i.stop_time.is_none())?;
+      open.stop_time = Some(std::cmp::max(latest_start, open.start_time));
+      task.set_times(times);
+      Some(Fix {
+        description: format!("stop at {}", format_time(&latest_start)),
+        task,
+      })
+    }
+    Issue::Overlap {
+      task_id,
+      interval,
+      other_task_id,
+      other_interval,
+      ..
+    } if task_id == other_task_id => {
+      let mut task = find(*task_id)?;
+      let mut times = task.times().clone();
+      let other = times[*other_interval].clone();
+      let merged = &mut times[*interval];
+      merged.start_time = merged.start_time.min(other.start_time);
+      merged.stop_time = match (merged.stop_time, other.stop_time) {
+        (Some(first), Some(second)) => Some(first.max(second)),
+        _ => None,
+      };
+      times.remove(*other_interval);
+      task.set_times(times);
+      Some(Fix {
+        description: format!(
+          "merge intervals #{} and #{}",
+          interval + 1,
+          other_interval + 1
+        ),
+        task,
+      })
+    }
+    Issue::Overlap {
+      task_id,
+      interval,
+      other_task_id,
+      other_interval,
+      from,
+      to,
+    } => {
+      let outer = find(*task_id)?.times()[*interval].clone();
+      let inner = find(*other_task_id)?.times()[*other_interval].clone();
+      // the interval which starts first, or lasts longer, gives way
+      let stops_later = match (outer.stop_time, inner.stop_time) {
+        (None, _) => true,
+        (Some(_), None) => false,
+        (Some(outer_stop), Some(inner_stop)) => outer_stop > inner_stop,
+      };
+      if outer.start_time > inner.start_time
+        || (outer.start_time == inner.start_time && !stops_later)
+      {
+        if outer == inner {
+          return None;
+        }
+        return fix_issue(
+          &Issue::Overlap {
+            task_id: *other_task_id,
+            interval: *other_interval,
+            other_task_id: *task_id,
+            other_interval: *interval,
+            from: *from,
+            to: *to,
+          },
+          tasks,
+        );
+      }
+
+      let mut task = find(*task_id)?;
+      let mut times = task.times().clone();
+      let mut kept = Vec::new();
+      if outer.start_time < inner.start_time {
+        kept.push(DateTimeInterval {
+          start_time: outer.start_time,
+          stop_time: Some(inner.start_time),
+        });
+      }
+      // the other task is inside the interval, the rest of it is kept
+      if let Some(inner_stop) = inner.stop_time {
+        if outer.stop_time.is_none_or(|stop| stop > inner_stop) {
+          kept.push(DateTimeInterval {
+            start_time: inner_stop,
+            stop_time: outer.stop_time,
+          });
+        }
+      }
+      let description = match kept.as_slice() {
+        [_, tail] => format!(
+          "split interval #{} around {} - {}",
+          interval + 1,
+          format_time(&inner.start_time),
+          format_time(&tail.start_time)
+        ),
+        [head] if head.start_time == outer.start_time => format!(
+          "trim interval #{} to stop at {}",
+          interval + 1,
+          format_time(&inner.start_time)
+        ),
+        [tail] => format!(
+          "trim interval #{} to start at {}",
+          interval + 1,
+          format_time(&tail.start_time)
+        ),
+        _ => return None,
+      };
+      times.splice(*interval..=*interval, kept);
+      task.set_times(times);
+      Some(Fix { description, task })
+    }
+    Issue::Gap { .. } => None,
+  }
+}
+
+struct Span {
+  task_id: uuid::Uuid,
+  interval: usize,
+  start: chrono::DateTime<chrono::Local>,
+  end: chrono::DateTime<chrono::Local>,
+}
+
+// Intervals of every task sorted by start, negative ones are skipped.
+fn spans(tasks: &[Task], now: chrono::DateTime<chrono::Local>) -> Vec<Span> {
+  let mut spans: Vec<Span> = tasks
+    .iter()
+    .flat_map(|task| {
+      task
+        .times()
+        .iter()
+        .enumerate()
+        .map(move |(interval, times)| Span {
+          task_id: task.id(),
+          interval,
+          start: times.start_time,
+          end: times.stop_time.unwrap_or(now),
+        })
+    })
+    .filter(|span| span.start <= span.end)
+    .collect();
+  spans.sort_by_key(|span| span.start);
+  return spans;
+}
+
+fn format_time(time: &chrono::DateTime<chrono::Local>) -> String {
+  time.format("%Y-%m-%d %H:%M").to_string()
+}
+
+#[cfg(test)]
+mod test {
+  use chrono::TimeZone;
+
+  use super::{check, gaps, propose_fixes, Issue};
+  use crate::{clock::SystemClock, task::Task, time::DateTimeInterval, traits::Indexable};
+
+  fn at(hour: u32, minute: u32) -> chrono::DateTime<chrono::Local> {
+    chrono::Local
+      .with_ymd_and_hms(2024, 3, 14, hour, minute, 0)
+      .unwrap()
+  }
+
+  // (start hour, start minute, stop)
+  type Interval = (u32, u32, Option<(u32, u32)>);
+
+  fn task(times: &[Interval]) -> Task {
+    let mut task = Task::new(
+      uuid::Uuid::new_v4(),
+      "task",
+      vec![],
+      None,
+      None,
+      &SystemClock,
+    );
+    task.set_times(
+      times
+        .iter()
+        .map(|(hour, minute, stop)| DateTimeInterval {
+          start_time: at(*hour, *minute),
+          stop_time: stop.map(|(hour, minute)| at(hour, minute)),
+        })
+        .collect(),
+    );
+    task
+  }
+
+  fn fixed(tasks: &[Task]) -> Vec<Task> {
+    let mut tasks = tasks.to_vec();
+    for fix in propose_fixes(&tasks, at(18, 0)) {
+      let position = tasks.iter().position(|t| t.id() == fix.task.id()).unwrap();
+      tasks[position] = fix.task;
+    }
+    tasks
+  }
+
+  #[test]
+  fn valid_tasks() {
+    let tasks = [
+      task(&[(9, 0, Some((10, 0))), (10, 30, Some((11, 0)))]),
+      task(&[(11, 0, Some((12, 0)))]),
+      task(&[(13, 0, None)]),
+    ];
+    assert!(check(&tasks, at(18, 0)).is_empty());
+  }
+
+  #[test]
+  fn overlaps_are_trimmed() {
+    let tasks = [
+      task(&[(9, 0, Some((10, 30)))]),
+      task(&[(10, 0, Some((11, 0)))]),
+    ];
+    let issues = check(&tasks, at(18, 0));
+    assert_eq!(issues.len(), 1);
+    assert!(
+      matches!(issues[0], Issue::Overlap { from, to, .. } if from == at(10, 0) && to == at(10, 30))
+    );
+
+    let tasks = fixed(&tasks);
+    assert!(check(&tasks, at(18, 0)).is_empty());
+    assert_eq!(tasks[0].stop_time(), Some(at(10, 0)));
+  }
+
+  #[test]
+  fn contained_task_splits_interval() {
+    let tasks = [
+      task(&[(9, 0, Some((12, 0)))]),
+      task(&[(10, 0, Some((11, 0)))]),
+    ];
+    let tasks = fixed(&tasks);
+    assert!(check(&tasks, at(18, 0)).is_empty());
+    assert_eq!(tasks[0].times().len(), 2);
+    assert_eq!(tasks[0].duration(&SystemClock), chrono::Duration::hours(2));
+
+    // with the same start the longer interval gives way
+    let tasks = [
+      task(&[(9, 0, Some((11, 0)))]),
+      task(&[(9, 0, Some((14, 0)))]),
+    ];
+    let tasks = fixed(&tasks);
+    assert!(check(&tasks, at(18, 0)).is_empty());
+    assert_eq!(tasks[0].stop_time(), Some(at(11, 0)));
+    assert_eq!(tasks[1].start_time(), at(11, 0));
+  }
+
+  #[test]
+  fn intervals_of_the_same_task_are_merged() {
+    let tasks = [task(&[(9, 0, Some((10, 30))), (10, 0, Some((11, 0)))])];
+    let tasks = fixed(&tasks);
+    assert!(check(&tasks, at(18, 0)).is_empty());
+    assert_eq!(tasks[0].times().len(), 1);
+    assert_eq!(tasks[0].stop_time(), Some(at(11, 0)));
+  }
+
+  #[test]
+  fn broken_tasks() {
+    let mut paused = task(&[(15, 0, None)]);
+    paused.set_paused(true);
+    let tasks = [
+      task(&[(9, 0, Some((8, 0)))]),
+      task(&[(10, 0, None)]),
+      paused,
+    ];
+    let issues = check(&tasks, at(18, 0));
+    assert!(issues.contains(&Issue::NegativeInterval {
+      task_id: tasks[0].id(),
+      interval: 0
+    }));
+    assert!(issues.contains(&Issue::PausedWithOpenInterval {
+      task_id: tasks[2].id()
+    }));
+    assert!(issues
+      .iter()
+      .any(|issue| matches!(issue, Issue::SeveralActive { task_ids } if task_ids.len() == 2)));
+
+    let tasks = fixed(&tasks);
+    assert!(check(&tasks, at(18, 0)).is_empty());
+    assert_eq!(tasks[0].start_time(), at(8, 0));
+    assert_eq!(tasks[1].stop_time(), Some(at(15, 0)));
+    assert!(!tasks[2].is_paused());
+  }
+
+  #[test]
+  fn gaps_within_a_day() {
+    let tasks = [
+      task(&[(9, 0, Some((10, 0)))]),
+      task(&[(10, 5, Some((11, 0)))]),
+      task(&[(12, 0, Some((13, 0)))]),
+    ];
+    let found = gaps(&tasks, at(18, 0), chrono::Duration::minutes(15));
+    assert_eq!(
+      found,
+      vec![Issue::Gap {
+        from: at(11, 0),
+        to: at(12, 0)
+      }]
+    );
+  }
+}
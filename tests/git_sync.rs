@@ -0,0 +1,415 @@
+//! End to end tests of the git sync. Every test creates local bare
+//! repositories and clients with their own storage folders and configs,
+//! which run the real busy binary.
+
+use std::{
+  path::{Path, PathBuf},
+  sync::atomic::{AtomicU32, Ordering},
+};
+
+// Every added task gets its own hour, busy refuses overlapping tasks.
+static NEXT_TASK_HOUR: AtomicU32 = AtomicU32::new(0);
+
+struct Remote {
+  path: PathBuf,
+}
+
+impl Remote {
+  fn new(root: &Path, name: &str) -> Self {
+    let path = root.join(name);
+    git(
+      root,
+      &["init", "--bare", "-b", "main", path.to_str().unwrap()],
+    );
+    Self { path }
+  }
+
+  fn url(&self) -> &str {
+    self.path.to_str().unwrap()
+  }
+
+  fn has_branch(&self, branch: &str) -> bool {
+    std::process::Command::new("git")
+      .args(["rev-parse", "--verify", "--quiet", branch])
+      .current_dir(&self.path)
+      .output()
+      .unwrap()
+      .status
+      .success()
+  }
+}
+
+struct Client {
+  root: PathBuf,
+  home: PathBuf,
+  env: Vec<(String, String)>,
+}
+
+impl Client {
+  fn new(root: &Path, name: &str, syncer: serde_json::Value) -> Self {
+    let client = Self {
+      root: root.join(name),
+      home: root.join(name).join("home"),
+      env: Vec::new(),
+    };
+    std::fs::create_dir_all(&client.home).unwrap();
+    client.configure(syncer);
+    client
+  }
+
+  fn configure(&self, syncer: serde_json::Value) {
+    let config = serde_json::json!({
+      "storage_dir_path": self.data_path().to_str().unwrap(),
+      "syncer": syncer,
+    });
+    std::fs::write(self.config_path(), config.to_string()).unwrap();
+  }
+
+  fn config_path(&self) -> PathBuf {
+    self.root.join("config.json")
+  }
+
+  fn data_path(&self) -> PathBuf {
+    self.root.join("data")
+  }
+
+  fn busy(&self, args: &[&str]) -> String {
+    let mut command = std::process::Command::new(env!("CARGO_BIN_EXE_busy"));
+    command
+      .args(args)
+      .env("BUSY_CONFIG", self.config_path())
+      .env("HOME", &self.home)
+      .env("GIT_CONFIG_NOSYSTEM", "1")
+      .env("GIT_AUTHOR_NAME", "busy")
+      .env("GIT_AUTHOR_EMAIL", "busy@localhost")
+      .env("GIT_COMMITTER_NAME", "busy")
+      .env("GIT_COMMITTER_EMAIL", "busy@localhost")
+      .env_remove("GIT_SSH_COMMAND");
+    for (key, value) in self.env.iter() {
+      command.env(key, value);
+    }
+    let output = command.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    assert!(
+      output.status.success(),
+      "busy {:?} failed: {}{}",
+      args,
+      stdout,
+      String::from_utf8_lossy(&output.stderr)
+    );
+    stdout
+  }
+
+  fn add_task(&self, title: &str) {
+    let hour = NEXT_TASK_HOUR.fetch_add(1, Ordering::SeqCst);
+    let start_time = format!("2024-01-{:02} {:02}:00", 1 + hour / 23, hour % 23);
+    let finish_time = format!("2024-01-{:02} {:02}:00", 1 + hour / 23, hour % 23 + 1);
+    self.busy(&[
+      "add",
+      "--start-time",
+      &start_time,
+      "--finish-time",
+      &finish_time,
+      "project",
+      title,
+    ]);
+  }
+
+  fn sync(&self, args: &[&str]) -> String {
+    let mut sync_args = vec!["sync"];
+    sync_args.extend_from_slice(args);
+    self.busy(&sync_args)
+  }
+
+  fn records(&self, filename: &str) -> Vec<serde_json::Value> {
+    let content = std::fs::read_to_string(self.data_path().join(filename)).unwrap();
+    let document: serde_json::Value = serde_json::from_str(&content).unwrap();
+    document["items"].as_array().unwrap().clone()
+  }
+
+  fn task_titles(&self) -> Vec<String> {
+    let mut titles: Vec<String> = self
+      .records("tasks.json")
+      .iter()
+      .map(|task| task["title"].as_str().unwrap().to_owned())
+      .collect();
+    titles.sort();
+    titles
+  }
+
+  fn git(&self, args: &[&str]) -> String {
+    git(&self.data_path(), args)
+  }
+}
+
+fn git(cwd: &Path, args: &[&str]) -> String {
+  let output = std::process::Command::new("git")
+    .args(args)
+    .current_dir(cwd)
+    .env("GIT_CONFIG_NOSYSTEM", "1")
+    .output()
+    .unwrap();
+  assert!(
+    output.status.success(),
+    "git {:?} failed: {}",
+    args,
+    String::from_utf8_lossy(&output.stderr)
+  );
+  String::from_utf8_lossy(&output.stdout).trim().to_owned()
+}
+
+fn git_syncer(remote: &str, branch: &str, key_file: Option<&str>) -> serde_json::Value {
+  serde_json::json!({
+    "Git": {
+      "key_file": key_file,
+      "remote": remote,
+      "remote_branch": branch,
+    }
+  })
+}
+
+fn setup() -> (tempfile::TempDir, Remote, Client, Client) {
+  let root = tempfile::tempdir().unwrap();
+  let remote = Remote::new(root.path(), "remote.git");
+  let first = Client::new(root.path(), "first", git_syncer(remote.url(), "main", None));
+  let second = Client::new(
+    root.path(),
+    "second",
+    git_syncer(remote.url(), "main", None),
+  );
+  (root, remote, first, second)
+}
+
+fn assert_sync_finished(output: &str) {
+  assert!(
+    output.contains("Syncing finished"),
+    "sync failed: {}",
+    output
+  );
+}
+
+#[test]
+fn git_first_time_init() {
+  let (_root, remote, first, _second) = setup();
+  first.busy(&["status"]);
+
+  let data_path = first.data_path();
+  assert!(data_path.join(".git").exists());
+  assert!(data_path.join(".gitignore").exists());
+  let gitattributes = std::fs::read_to_string(data_path.join(".gitattributes")).unwrap();
+  assert!(gitattributes.contains("tasks.json merge=busy"));
+  assert_eq!(first.git(&["remote", "get-url", "origin"]), remote.url());
+  assert!(first
+    .git(&["config", "merge.busy.driver"])
+    .contains("merge-driver %O %A %B %P"));
+  assert_eq!(first.git(&["branch", "--show-current"]), "main");
+}
+
+#[test]
+fn git_keeps_user_gitignore() {
+  let (_root, _remote, first, _second) = setup();
+  std::fs::create_dir_all(first.data_path()).unwrap();
+  first.git(&["init", "-b", "main"]);
+  std::fs::write(first.data_path().join(".gitignore"), "notes.txt").unwrap();
+  first.add_task("task");
+
+  let gitignore = std::fs::read_to_string(first.data_path().join(".gitignore")).unwrap();
+  assert_eq!(gitignore, "notes.txt\n*.bak\n*.tmp\n*.lock\njournal.json\n");
+  assert!(first.data_path().join("journal.json").exists());
+  first.git(&["add", "-A"]);
+  let tracked = first.git(&["ls-files"]);
+  assert!(!tracked.contains("journal.json"), "{}", tracked);
+  assert!(!tracked.contains(".lock"), "{}", tracked);
+}
+
+#[test]
+fn git_merge_driver_registration() {
+  let (root, remote, first, second) = setup();
+  first.busy(&["status"]);
+  // the driver is registered once, later runs keep the config as it is
+  first.git(&["config", "merge.busy.name", "custom"]);
+  first.busy(&["status"]);
+  assert_eq!(first.git(&["config", "merge.busy.name"]), "custom");
+
+  let mut syncer = git_syncer(remote.url(), "main", None);
+  syncer["Git"]["merge_driver"] = serde_json::json!({ "Binary": "/opt/busy/bin/busy" });
+  second.configure(syncer);
+  second.busy(&["status"]);
+  assert_eq!(
+    second.git(&["config", "merge.busy.driver"]),
+    "'/opt/busy/bin/busy' merge-driver %O %A %B %P"
+  );
+
+  let mut syncer = git_syncer(remote.url(), "main", None);
+  syncer["Git"]["merge_driver"] = serde_json::json!("Disabled");
+  let third = Client::new(root.path(), "third", syncer);
+  third.busy(&["status"]);
+  let git_config = std::fs::read_to_string(third.data_path().join(".git").join("config")).unwrap();
+  assert!(!git_config.contains("merge-driver"));
+}
+
+#[test]
+fn git_sync_between_clients() {
+  let (_root, remote, first, second) = setup();
+  first.add_task("first");
+  assert_sync_finished(&first.sync(&[]));
+  assert!(remote.has_branch("main"));
+
+  // a fresh client gets the data on init
+  second.busy(&["status"]);
+  assert_eq!(second.task_titles(), vec!["first"]);
+
+  second.add_task("second");
+  assert_sync_finished(&second.sync(&[]));
+  assert_sync_finished(&first.sync(&[]));
+  assert_eq!(first.task_titles(), vec!["first", "second"]);
+}
+
+#[test]
+fn git_sync_divergent_histories() {
+  let (_root, _remote, first, second) = setup();
+  first.add_task("base");
+  assert_sync_finished(&first.sync(&[]));
+  assert_sync_finished(&second.sync(&[]));
+
+  first.add_task("first");
+  second.add_task("second");
+  assert_sync_finished(&second.sync(&[]));
+  assert_sync_finished(&first.sync(&[]));
+  assert_sync_finished(&second.sync(&[]));
+
+  let expected = vec!["base", "first", "second"];
+  assert_eq!(first.task_titles(), expected);
+  assert_eq!(second.task_titles(), expected);
+  assert!(first.git(&["status", "--porcelain"]).is_empty());
+}
+
+#[test]
+fn git_sync_same_record_changes() {
+  let (_root, _remote, first, second) = setup();
+  first.add_task("base");
+  assert_sync_finished(&first.sync(&[]));
+  assert_sync_finished(&second.sync(&[]));
+
+  first.busy(&["project", "set", "project", "--description", "first"]);
+  first.busy(&["project", "set", "project", "--client", "acme"]);
+  second.busy(&["project", "set", "project", "--description", "second"]);
+  assert_sync_finished(&second.sync(&[]));
+
+  // the merge driver resolves the project record, git reports no conflict
+  let output = first.sync(&[]);
+  assert_sync_finished(&output);
+  assert!(
+    output.contains("Records changed on both sides were merged automatically"),
+    "no conflict report: {}",
+    output
+  );
+  assert!(output.contains("projects.json"), "{}", output);
+  assert!(
+    output.contains("both sides changed: description"),
+    "{}",
+    output
+  );
+  assert!(first.git(&["status", "--porcelain"]).is_empty());
+
+  assert_sync_finished(&second.sync(&[]));
+  for client in [&first, &second] {
+    let projects = client.records("projects.json");
+    assert_eq!(projects.len(), 1);
+    assert_eq!(projects[0]["description"], "second");
+    assert_eq!(projects[0]["client"], "acme");
+  }
+}
+
+#[test]
+fn git_force_push_and_pull() {
+  let (_root, remote, first, second) = setup();
+  first.add_task("base");
+  assert_sync_finished(&first.sync(&[]));
+  assert_sync_finished(&second.sync(&[]));
+
+  second.add_task("second");
+  assert!(second
+    .sync(&["--push-force"])
+    .contains("Sync push force success"));
+  assert_eq!(
+    git(&remote.path, &["rev-parse", "main"]),
+    second.git(&["rev-parse", "HEAD"])
+  );
+
+  assert!(first
+    .sync(&["--pull-force"])
+    .contains("Sync pull force success"));
+  assert_eq!(first.task_titles(), vec!["base", "second"]);
+}
+
+#[test]
+fn git_custom_branch_and_remote_change() {
+  let root = tempfile::tempdir().unwrap();
+  let remote = Remote::new(root.path(), "remote.git");
+  let other_remote = Remote::new(root.path(), "other.git");
+  let client = Client::new(
+    root.path(),
+    "client",
+    git_syncer(remote.url(), "work", None),
+  );
+
+  client.add_task("task");
+  assert_sync_finished(&client.sync(&[]));
+  assert!(remote.has_branch("work"));
+  assert!(!remote.has_branch("main"));
+
+  client.configure(git_syncer(other_remote.url(), "work", None));
+  client.busy(&["status"]);
+  assert_eq!(
+    client.git(&["remote", "get-url", "origin"]),
+    other_remote.url()
+  );
+  assert_sync_finished(&client.sync(&[]));
+  assert!(other_remote.has_branch("work"));
+}
+
+#[test]
+#[cfg(unix)]
+fn git_ssh_key_env() {
+  let root = tempfile::tempdir().unwrap();
+  let key_file = root.path().join("id_busy");
+  std::fs::write(&key_file, "").unwrap();
+
+  // fake ssh records its arguments and refuses to connect
+  let bin_path = root.path().join("bin");
+  let ssh_log = root.path().join("ssh.log");
+  std::fs::create_dir_all(&bin_path).unwrap();
+  let ssh_path = bin_path.join("ssh");
+  std::fs::write(
+    &ssh_path,
+    format!(
+      "#!/bin/sh\necho \"$@\" >> '{}'\nexit 1\n",
+      ssh_log.display()
+    ),
+  )
+  .unwrap();
+  use std::os::unix::fs::PermissionsExt;
+  std::fs::set_permissions(&ssh_path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+  let mut client = Client::new(
+    root.path(),
+    "client",
+    git_syncer(
+      "git@busy.invalid:busy/db.git",
+      "main",
+      Some(key_file.to_str().unwrap()),
+    ),
+  );
+  client.env.push((
+    "PATH".to_owned(),
+    format!("{}:{}", bin_path.display(), std::env::var("PATH").unwrap()),
+  ));
+
+  client.add_task("task");
+  assert!(client.sync(&[]).contains("Sync failed"));
+
+  let ssh_calls = std::fs::read_to_string(ssh_log).unwrap();
+  assert!(ssh_calls.contains(&format!("-i {}", key_file.display())));
+  assert!(ssh_calls.contains("git@busy.invalid"));
+}